@@ -0,0 +1,128 @@
+// Measures how long a Leader with a long uncommitted log takes to process the
+// acknowledgements that advance its commit index. Run with
+// `cargo bench --bench commit_index`.
+use little_raft::{
+    cluster::Cluster,
+    message::Message,
+    replica::{Replica, ReplicaID},
+    state_machine::{StateMachine, StateMachineTransition, TransitionState},
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+const PEERS: usize = 4;
+const ENTRIES: usize = 100_000;
+
+#[derive(Clone, Debug)]
+struct Increment {
+    id: usize,
+}
+
+impl StateMachineTransition for Increment {
+    type TransitionID = usize;
+    fn get_id(&self) -> usize {
+        self.id
+    }
+}
+
+#[derive(Default)]
+struct Counter {
+    value: usize,
+    pending_transitions: Vec<Increment>,
+}
+
+impl StateMachine<Increment> for Counter {
+    fn register_transition_state(&mut self, _: usize, _: TransitionState) {}
+
+    fn apply_transition(&mut self, _: Increment) {
+        self.value += 1;
+    }
+
+    fn get_pending_transitions(&mut self) -> Vec<Increment> {
+        std::mem::take(&mut self.pending_transitions)
+    }
+}
+
+struct NullCluster;
+
+impl Cluster<Increment> for NullCluster {
+    fn send_message(&mut self, _: usize, _: Message<Increment>) {}
+
+    fn receive_messages(&mut self) -> Vec<Message<Increment>> {
+        Vec::new()
+    }
+
+    fn halt(&self) -> bool {
+        false
+    }
+
+    fn register_leader(&mut self, _: Option<ReplicaID>) {}
+}
+
+fn main() {
+    let peer_ids: Vec<ReplicaID> = (1..=PEERS).collect();
+    let state_machine = Arc::new(Mutex::new(Counter::default()));
+    let mut replica = Replica::new(
+        0,
+        peer_ids.clone(),
+        Arc::new(Mutex::new(NullCluster)),
+        state_machine.clone(),
+        Increment { id: 0 },
+        Duration::from_millis(50),
+        (Duration::from_secs(60), Duration::from_secs(60)),
+    );
+
+    // Get elected, then let the peers confirm the leadership so that the
+    // Leader appends all pending transitions at once.
+    replica.set_confirm_leadership_before_writes(true);
+    replica.handle_timeout(Instant::now() + Duration::from_secs(60));
+    for peer_id in &peer_ids {
+        replica.handle_message(Message::VoteResponse {
+            from_id: *peer_id,
+            term: 1,
+            vote_granted: true,
+        });
+    }
+    state_machine.lock().unwrap().pending_transitions =
+        (1..=ENTRIES).map(|id| Increment { id }).collect();
+    for peer_id in &peer_ids {
+        replica.handle_message(Message::AppendEntryResponse {
+            from_id: *peer_id,
+            term: 1,
+            success: false,
+            last_index: 0,
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        });
+    }
+
+    // The peers acknowledge the log a bit at a time. Within each step, the
+    // commit index moves when the second peer acknowledges it.
+    let acks_per_peer = 1_000;
+    let started = Instant::now();
+    for step in 1..=acks_per_peer {
+        for peer_id in &peer_ids {
+            replica.handle_message(Message::AppendEntryResponse {
+                from_id: *peer_id,
+                term: 1,
+                success: true,
+                last_index: 1 + step * ENTRIES / acks_per_peer,
+                mismatch_index: None,
+                content_hash: None,
+                request_timestamp: None,
+            });
+        }
+    }
+    let elapsed = started.elapsed();
+
+    assert_eq!(replica.handle().commit_index(), ENTRIES + 1);
+    println!(
+        "{} acknowledgements in {:?} ({:?} each)",
+        acks_per_peer * PEERS,
+        elapsed,
+        elapsed / (acks_per_peer * PEERS) as u32
+    );
+}
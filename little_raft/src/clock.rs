@@ -0,0 +1,94 @@
+use crossbeam_channel::{bounded, Receiver, Sender};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Clock is the source of time of a Replica, see Replica::set_clock. It tells
+/// the time and fires the Replica's timers.
+pub trait Clock: Send + Sync {
+    /// now returns the current time.
+    fn now(&self) -> Instant;
+
+    /// at returns a channel that receives the time once now reaches deadline.
+    /// If deadline has passed already, the channel receives it right away.
+    fn at(&self, deadline: Instant) -> Receiver<Instant>;
+}
+
+/// SystemClock is the operating system's monotonic clock. Replicas use it by
+/// default.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn at(&self, deadline: Instant) -> Receiver<Instant> {
+        crossbeam_channel::at(deadline)
+    }
+}
+
+/// ManualClock only moves when told to, which makes the timing of Replicas
+/// that use it deterministic, for example in tests and simulations. Clones
+/// share the same time.
+#[derive(Clone)]
+pub struct ManualClock {
+    shared: Arc<Mutex<ManualTime>>,
+}
+
+struct ManualTime {
+    now: Instant,
+    // Timers waiting for their deadline.
+    timers: Vec<(Instant, Sender<Instant>)>,
+}
+
+impl ManualClock {
+    /// Create a clock that starts at the current time of the system clock.
+    pub fn new() -> ManualClock {
+        ManualClock {
+            shared: Arc::new(Mutex::new(ManualTime {
+                now: Instant::now(),
+                timers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Move the clock forward by duration and fire the timers that are due.
+    pub fn advance(&self, duration: Duration) {
+        let mut time = self.shared.lock().unwrap();
+        time.now += duration;
+        let now = time.now;
+        time.timers.retain(|(deadline, tx)| {
+            if *deadline > now {
+                return true;
+            }
+            let _ = tx.send(now);
+            false
+        });
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.shared.lock().unwrap().now
+    }
+
+    fn at(&self, deadline: Instant) -> Receiver<Instant> {
+        let (tx, rx) = bounded(1);
+        let mut time = self.shared.lock().unwrap();
+        if deadline <= time.now {
+            let _ = tx.send(time.now);
+        } else {
+            time.timers.push((deadline, tx));
+        }
+        rx
+    }
+}
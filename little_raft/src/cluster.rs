@@ -1,4 +1,9 @@
-use crate::{message::Message, replica::ReplicaID, state_machine::StateMachineTransition};
+use crate::{
+    message::{Message, MessagePriority},
+    replica::ReplicaID,
+    state_machine::StateMachineTransition,
+};
+use std::time::Duration;
 
 /// Cluster is used for the local Raft Replica to communicate with the rest of
 /// the Raft cluster. It is up to the user how to abstract that communication.
@@ -15,12 +20,108 @@ where
     /// exists to achieve consensus in spite of failures, after all.
     fn send_message(&mut self, to_id: usize, message: Message<T>);
 
+    /// This function is what the Replica actually calls to deliver messages,
+    /// along with the priority of the message. Transports that can deliver
+    /// some messages ahead of others should override it to send high priority
+    /// messages first. By default the priority is ignored and the message is
+    /// passed on to send_message.
+    fn send_message_with_priority(
+        &mut self,
+        to_id: usize,
+        message: Message<T>,
+        _priority: MessagePriority,
+    ) {
+        self.send_message(to_id, message);
+    }
+
+    /// This function is used by the Replica to send a message to each of
+    /// peer_ids at once, such as VoteRequests or the Leader's
+    /// AppendEntryRequests, with message_for building the message for a given
+    /// peer. Transports that support multicast or batched sends should
+    /// override it to send the messages in one go. By default each message is
+    /// passed on to send_message_with_priority in turn.
+    fn broadcast<F>(&mut self, peer_ids: &[ReplicaID], message_for: F)
+    where
+        F: Fn(ReplicaID) -> Message<T>,
+    {
+        for peer_id in peer_ids {
+            let message = message_for(*peer_id);
+            let priority = message.priority();
+            self.send_message_with_priority(*peer_id, message, priority);
+        }
+    }
+
     /// This function is used by the Replica to receive pending messages from
     /// the cluster. The receive_messages implementation must not block and must
     /// not return the same message more than once. Note that receive_messages
     /// is only called when the Replica is notified via the recv_msg channel.
     fn receive_messages(&mut self) -> Vec<Message<T>>;
 
+    /// This function is what the Replica actually calls to receive pending
+    /// messages. Transports that can hand messages over one at a time, say
+    /// from a channel, should override it to yield them lazily instead of
+    /// buffering them all. The Replica pulls the messages one by one and
+    /// processes each before pulling the next, without holding the lock on
+    /// the Cluster in between, which is why the iterator cannot borrow it.
+    /// The same rules as for receive_messages apply. By default the messages
+    /// returned by receive_messages are yielded.
+    fn drain_messages(&mut self) -> Box<dyn Iterator<Item = Message<T>>>
+    where
+        T: 'static,
+    {
+        Box::new(self.receive_messages().into_iter())
+    }
+
+    /// This function is used by the Replica to check that a received message
+    /// really comes from the from_id it claims. Messages for which verify_peer
+    /// returns false are dropped before the Replica processes them. This is
+    /// only advisory: the actual authentication of peers (e.g. with TLS
+    /// certificates) must be done by the transport. By default every peer is
+    /// trusted.
+    fn verify_peer(&self, _from_id: ReplicaID) -> bool {
+        true
+    }
+
+    /// This function is used by the Leader to find out which format of the
+    /// transitions a peer understands, for example while the cluster is
+    /// upgraded to a new version of the transitions. The Leader converts the
+    /// entries it sends to that peer with StateMachineTransition::for_format.
+    /// None means the peer understands the Leader's own format, which is the
+    /// default for every peer.
+    fn peer_format(&self, _peer_id: ReplicaID) -> Option<u32> {
+        None
+    }
+
+    /// This is a hook that the Leader calls when it resets what it knows of a
+    /// peer's log: for every peer when it becomes the Leader, and for a peer
+    /// whose log turned out to diverge, when the Leader moves back the point
+    /// it replicates to that peer from. Whatever the transport still has
+    /// buffered for the peer is outdated then, so it may clear its send buffer
+    /// or re-establish the connection. Does nothing by default.
+    fn on_peer_reset(&mut self, _peer_id: ReplicaID) {}
+
+    /// This is a hook that a follower calls when the timestamp of an
+    /// AppendEntryRequest differs from its own clock by more than the maximum
+    /// clock drift set with Replica::set_max_clock_drift. The drift includes
+    /// the time the request spent in transit. It is only reported to help
+    /// operators catch misbehaving clocks: Raft itself does not rely on them.
+    /// Does nothing by default.
+    fn register_clock_drift(&mut self, _leader_id: ReplicaID, _drift: Duration) {}
+
+    /// This is a hook that the Leader calls when a membership change would
+    /// leave the cluster able to tolerate fewer failures than the floor set
+    /// with Replica::set_fault_tolerance_floor, and fewer than it does now.
+    /// The members are those the change asked for, and tolerated_failures
+    /// how many of them may fail with the cluster still available. If
+    /// rejected is set, the change was dropped. Does nothing by default.
+    fn register_reduced_fault_tolerance(
+        &mut self,
+        _members: &[ReplicaID],
+        _tolerated_failures: usize,
+        _rejected: bool,
+    ) {
+    }
+
     /// By returning true from halt you can signal to the Replica that it should
     /// stop running.
     fn halt(&self) -> bool;
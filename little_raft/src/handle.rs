@@ -0,0 +1,344 @@
+use crate::{
+    clock::{Clock, SystemClock},
+    replica::{ReplicaID, State},
+    state_machine::{TransitionAbandonedReason, TransitionRejectedReason},
+};
+use crossbeam_channel::{bounded, Receiver, RecvTimeoutError, Sender};
+use std::{
+    cmp,
+    sync::{Arc, Condvar, Mutex},
+    time::{Duration, Instant},
+};
+
+/// ReadError describes why a read could not be served.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReadError {
+    /// The Replica did not apply the requested index before the deadline.
+    /// last_applied is the index it had applied by then.
+    Timeout { last_applied: usize },
+
+    /// The Replica has not caught up with the Leader recently enough.
+    /// staleness is how long ago it last did, or None if it never did.
+    TooStale { staleness: Option<Duration> },
+}
+
+/// ProposeError describes why a transition proposed with
+/// ProposeHandle::propose_blocking was not committed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ProposeError {
+    /// The Replica is not the Leader. leader_id is the Leader it knows of, if
+    /// any, where the transition can be proposed instead.
+    NotLeader { leader_id: Option<ReplicaID> },
+
+    /// The Replica abandoned the transition, see TransitionState::Abandoned.
+    Abandoned(TransitionAbandonedReason),
+
+    /// The state machine rejected the transition, see
+    /// TransitionState::Rejected.
+    Rejected(TransitionRejectedReason),
+
+    /// The Replica was dropped before the transition was committed.
+    Stopped,
+
+    /// The outcome of the transition was not known by the deadline, for
+    /// example because the Replica stepped down while its entry was being
+    /// replicated. The transition may still be committed later.
+    Timeout,
+}
+
+/// A transition proposed through a ProposeHandle, with where to report the
+/// outcome.
+pub(crate) type Proposal<T> = (T, Sender<Result<(), ProposeError>>);
+
+/// Metrics is a snapshot of the core counters of a Replica, see
+/// Replica::metrics and ReplicaHandle::metrics.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Metrics {
+    /// Current term of the Replica.
+    pub current_term: usize,
+
+    /// Index of the highest log entry known to be committed.
+    pub commit_index: usize,
+
+    /// Index of the highest log entry applied to the state machine.
+    pub last_applied: usize,
+
+    /// Number of entries in the log, including the entry that stands for the
+    /// last entry compacted into the snapshot.
+    pub log_len: usize,
+
+    /// Index of the first entry of the log.
+    pub index_offset: usize,
+
+    /// Role of the Replica.
+    pub state: State,
+
+    /// How long ago the Replica last heard from the Leader of its term or, on
+    /// the Leader, last sent AppendEntryRequests. None if it never did.
+    pub since_last_heartbeat: Option<Duration>,
+
+    /// Randomized election timeout that was picked last, see
+    /// Replica::last_election_timeout.
+    pub last_election_timeout: Duration,
+}
+
+/// Progress and role of the Replica, shared with its handles.
+#[derive(Default)]
+pub(crate) struct Progress {
+    pub(crate) commit_index: usize,
+    pub(crate) last_applied: usize,
+    pub(crate) caught_up_at: Option<Instant>,
+    pub(crate) apply_paused: bool,
+    pub(crate) current_term: usize,
+    pub(crate) is_leader: bool,
+    pub(crate) leader_id: Option<ReplicaID>,
+    pub(crate) shutdown_requested: bool,
+    pub(crate) log_len: usize,
+    pub(crate) index_offset: usize,
+    pub(crate) state: State,
+    pub(crate) last_heartbeat: Option<Instant>,
+    pub(crate) last_election_timeout: Duration,
+}
+
+/// ReplicaHandle lets other threads observe a Replica while it is running,
+/// pause applying entries to its state machine and shut it down.
+/// Replica::start borrows the Replica for as long as it runs, so get a handle
+/// with Replica::handle before starting it. Handles are cheap to clone.
+#[derive(Clone)]
+pub struct ReplicaHandle {
+    shared: Arc<(Mutex<Progress>, Condvar)>,
+    // The Replica's clock, see Replica::set_clock.
+    clock: Arc<Mutex<Arc<dyn Clock>>>,
+    // Wakes the Replica up when a shutdown is requested.
+    wake: (Sender<()>, Receiver<()>),
+}
+
+impl Default for ReplicaHandle {
+    fn default() -> Self {
+        ReplicaHandle {
+            shared: Arc::default(),
+            clock: Arc::new(Mutex::new(Arc::new(SystemClock))),
+            wake: bounded(1),
+        }
+    }
+}
+
+impl ReplicaHandle {
+    /// Returns the index of the highest log entry known to be committed.
+    pub fn commit_index(&self) -> usize {
+        self.shared.0.lock().unwrap().commit_index
+    }
+
+    /// Returns the index of the highest log entry applied to the state
+    /// machine.
+    pub fn last_applied(&self) -> usize {
+        self.shared.0.lock().unwrap().last_applied
+    }
+
+    /// Returns the current term of the Replica.
+    pub fn current_term(&self) -> usize {
+        self.shared.0.lock().unwrap().current_term
+    }
+
+    /// Returns whether the Replica is the Leader.
+    pub fn is_leader(&self) -> bool {
+        self.shared.0.lock().unwrap().is_leader
+    }
+
+    /// Returns the Leader the Replica knows of, as last reported to
+    /// Cluster::register_leader. Route client writes there.
+    pub fn leader_id(&self) -> Option<ReplicaID> {
+        self.shared.0.lock().unwrap().leader_id
+    }
+
+    /// Returns the Replica's core counters as of the end of its last
+    /// iteration, without waiting for the Replica.
+    pub fn metrics(&self) -> Metrics {
+        let progress = self.shared.0.lock().unwrap();
+        let now = self.now();
+        Metrics {
+            current_term: progress.current_term,
+            commit_index: progress.commit_index,
+            last_applied: progress.last_applied,
+            log_len: progress.log_len,
+            index_offset: progress.index_offset,
+            state: progress.state,
+            since_last_heartbeat: progress
+                .last_heartbeat
+                .map(|last_heartbeat| now.saturating_duration_since(last_heartbeat)),
+            last_election_timeout: progress.last_election_timeout,
+        }
+    }
+
+    /// Block until the Replica has applied the log entry at min_index, or
+    /// until the deadline. This gives read-your-writes on followers: once a
+    /// client's write is applied on the Leader, the Leader's last_applied is
+    /// at least the index of the write, and a follower whose state machine
+    /// is read after waiting for that index reflects the write. Returns the
+    /// index the Replica has applied.
+    pub fn wait_for_applied(
+        &self,
+        min_index: usize,
+        deadline: Instant,
+    ) -> Result<usize, ReadError> {
+        let (progress, applied) = &*self.shared;
+        let mut progress = progress.lock().unwrap();
+        while progress.last_applied < min_index {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(ReadError::Timeout {
+                    last_applied: progress.last_applied,
+                });
+            }
+            progress = applied.wait_timeout(progress, deadline - now).unwrap().0;
+        }
+        Ok(progress.last_applied)
+    }
+
+    /// Returns the index up to which the state machine can be read, provided
+    /// that it is at most max_staleness behind the Leader. A follower is as
+    /// fresh as the last AppendEntryRequest that brought its commit index up
+    /// to the Leader's. A Leader is as fresh as the time by which a majority
+    /// of the cluster last acknowledged it in its term. Staleness is measured
+    /// on the Replica's clock. This is cheaper than waiting for a specific
+    /// index, and suits reads that tolerate some staleness.
+    pub fn bounded_stale_read(&self, max_staleness: Duration) -> Result<usize, ReadError> {
+        let progress = self.shared.0.lock().unwrap();
+        let now = self.now();
+        let staleness = progress
+            .caught_up_at
+            .map(|caught_up_at| now.saturating_duration_since(caught_up_at));
+        match staleness {
+            Some(staleness) if staleness <= max_staleness => Ok(progress.last_applied),
+            _ => Err(ReadError::TooStale { staleness }),
+        }
+    }
+
+    /// Stop applying committed entries to the state machine, for example to
+    /// perform maintenance on it. The Replica keeps replicating and committing
+    /// entries in the meantime. See Replica::set_apply_backlog_limit to bound
+    /// the entries that pile up.
+    pub fn pause_apply(&self) {
+        self.shared.0.lock().unwrap().apply_paused = true;
+    }
+
+    /// Resume applying committed entries, in order, starting with the ones
+    /// committed while applying was paused. The Replica catches up the next
+    /// time it wakes up, at the latest on its next heartbeat or message.
+    pub fn resume_apply(&self) {
+        self.shared.0.lock().unwrap().apply_paused = false;
+    }
+
+    /// Make Replica::start return as soon as possible, without waiting for
+    /// the Cluster to halt the Replica or for the Replica's next timeout.
+    /// Committed entries that are not applied yet are handled as on a halt,
+    /// see StateMachine::drain_committed_entries. If start is not running, the
+    /// next call to start returns right away.
+    pub fn shutdown(&self) {
+        self.shared.0.lock().unwrap().shutdown_requested = true;
+        // A wake-up that is already pending is enough.
+        let _ = self.wake.0.try_send(());
+    }
+
+    pub(crate) fn take_shutdown_request(&self) -> bool {
+        std::mem::take(&mut self.shared.0.lock().unwrap().shutdown_requested)
+    }
+
+    pub(crate) fn wake_receiver(&self) -> Receiver<()> {
+        self.wake.1.clone()
+    }
+
+    pub(crate) fn apply_paused(&self) -> bool {
+        self.shared.0.lock().unwrap().apply_paused
+    }
+
+    pub(crate) fn record_caught_up(&self, at: Instant) {
+        let mut progress = self.shared.0.lock().unwrap();
+        progress.caught_up_at = cmp::max(progress.caught_up_at, Some(at));
+    }
+
+    pub(crate) fn set_clock(&self, clock: Arc<dyn Clock>) {
+        *self.clock.lock().unwrap() = clock;
+    }
+
+    fn now(&self) -> Instant {
+        self.clock.lock().unwrap().now()
+    }
+
+    pub(crate) fn update_leadership(
+        &self,
+        current_term: usize,
+        is_leader: bool,
+        leader_id: Option<ReplicaID>,
+    ) {
+        let mut progress = self.shared.0.lock().unwrap();
+        progress.current_term = current_term;
+        progress.is_leader = is_leader;
+        progress.leader_id = leader_id;
+    }
+
+    pub(crate) fn update_metrics(
+        &self,
+        log_len: usize,
+        index_offset: usize,
+        state: State,
+        last_heartbeat: Option<Instant>,
+        last_election_timeout: Duration,
+    ) {
+        let mut progress = self.shared.0.lock().unwrap();
+        progress.log_len = log_len;
+        progress.index_offset = index_offset;
+        progress.state = state;
+        progress.last_heartbeat = last_heartbeat;
+        progress.last_election_timeout = last_election_timeout;
+    }
+
+    pub(crate) fn update(&self, commit_index: usize, last_applied: usize) {
+        let (progress, applied) = &*self.shared;
+        let mut progress = progress.lock().unwrap();
+        if progress.commit_index != commit_index || progress.last_applied != last_applied {
+            progress.commit_index = commit_index;
+            progress.last_applied = last_applied;
+            applied.notify_all();
+        }
+    }
+}
+
+/// ProposeHandle lets other threads propose transitions to a running Replica
+/// and wait for them to commit, without going through
+/// StateMachine::get_pending_transitions. Get one with Replica::propose_handle
+/// before starting the Replica. Handles are cheap to clone.
+#[derive(Clone)]
+pub struct ProposeHandle<T> {
+    handle: ReplicaHandle,
+    proposals: Sender<Proposal<T>>,
+}
+
+impl<T> ProposeHandle<T> {
+    pub(crate) fn new(handle: ReplicaHandle, proposals: Sender<Proposal<T>>) -> ProposeHandle<T> {
+        ProposeHandle { handle, proposals }
+    }
+
+    /// Propose transition and block until it is committed, or until the
+    /// deadline. The transition is reported through
+    /// StateMachine::register_transition_state like any other. Fails right
+    /// away if the Replica is not the Leader, and later if the transition is
+    /// abandoned or rejected. Only Replicas run with start pick proposals up
+    /// as they arrive.
+    pub fn propose_blocking(&self, transition: T, deadline: Instant) -> Result<(), ProposeError> {
+        if !self.handle.is_leader() {
+            return Err(ProposeError::NotLeader {
+                leader_id: self.handle.leader_id(),
+            });
+        }
+        let (reply_tx, reply_rx) = bounded(1);
+        self.proposals
+            .send((transition, reply_tx))
+            .map_err(|_| ProposeError::Stopped)?;
+        match reply_rx.recv_deadline(deadline) {
+            Ok(result) => result,
+            Err(RecvTimeoutError::Timeout) => Err(ProposeError::Timeout),
+            Err(RecvTimeoutError::Disconnected) => Err(ProposeError::Stopped),
+        }
+    }
+}
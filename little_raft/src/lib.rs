@@ -10,9 +10,13 @@
 //! communication between the nodes, how to process client's messages, how to do
 //! service discovery, and what kind of state machine to replicate.
 //!
-//! The implementation is kept as simple as possible on purpose, with the entire
-//! library code base fitting in under 1,000 lines of code.
+//! The implementation is kept as simple as possible on purpose. Features beyond
+//! the core algorithm, such as snapshots, membership changes or leadership
+//! transfer, are only used when asked for.
+pub mod clock;
 pub mod cluster;
+pub mod handle;
+pub mod log_stream;
 pub mod message;
 pub mod replica;
 pub mod state_machine;
@@ -0,0 +1,163 @@
+use crate::{message::LogEntry, state_machine::StateMachineTransition};
+use std::slice;
+
+/// LogRecord is a committed log entry in a format that systems outside of the
+/// cluster can consume, for example to ship the log to a message queue.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LogRecord {
+    pub index: usize,
+    pub term: usize,
+    /// The entry's transition, as serialized by the encoder passed to
+    /// Replica::log_stream.
+    pub payload: Vec<u8>,
+}
+
+impl LogRecord {
+    /// Encode the record as a protobuf message prefixed with its length, the
+    /// way protobuf's delimited streams are written. The message is
+    ///
+    /// ```text
+    /// message LogRecord {
+    ///   uint64 index = 1;
+    ///   uint64 term = 2;
+    ///   bytes payload = 3;
+    /// }
+    /// ```
+    pub fn encode(&self) -> Vec<u8> {
+        let mut message = Vec::new();
+        put_varint(&mut message, INDEX_KEY);
+        put_varint(&mut message, self.index as u64);
+        put_varint(&mut message, TERM_KEY);
+        put_varint(&mut message, self.term as u64);
+        put_varint(&mut message, PAYLOAD_KEY);
+        put_varint(&mut message, self.payload.len() as u64);
+        message.extend_from_slice(&self.payload);
+
+        let mut record = Vec::with_capacity(message.len() + 10);
+        put_varint(&mut record, message.len() as u64);
+        record.extend(message);
+        record
+    }
+
+    /// Decode the record at the start of buf, as written by encode. Returns
+    /// the record and the number of bytes it took, or None if buf does not
+    /// start with a complete record. Unknown fields are skipped.
+    pub fn decode(buf: &[u8]) -> Option<(LogRecord, usize)> {
+        let (length, mut position) = get_varint(buf)?;
+        let end = position.checked_add(length as usize)?;
+        if end > buf.len() {
+            return None;
+        }
+
+        let mut record = LogRecord {
+            index: 0,
+            term: 0,
+            payload: Vec::new(),
+        };
+        while position < end {
+            let (key, read) = get_varint(&buf[position..end])?;
+            position += read;
+            match key & 0x7 {
+                VARINT => {
+                    let (value, read) = get_varint(&buf[position..end])?;
+                    position += read;
+                    match key {
+                        INDEX_KEY => record.index = value as usize,
+                        TERM_KEY => record.term = value as usize,
+                        _ => {}
+                    }
+                }
+                LENGTH_DELIMITED => {
+                    let (length, read) = get_varint(&buf[position..end])?;
+                    position += read;
+                    let field_end = position.checked_add(length as usize)?;
+                    if field_end > end {
+                        return None;
+                    }
+                    if key == PAYLOAD_KEY {
+                        record.payload = buf[position..field_end].to_vec();
+                    }
+                    position = field_end;
+                }
+                _ => return None,
+            }
+        }
+        Some((record, end))
+    }
+}
+
+/// LogStreamError describes why a log stream could not be started.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LogStreamError {
+    /// The requested entries were compacted into a snapshot. Restore the
+    /// snapshot (see Replica::snapshot) and resume streaming right after
+    /// snapshot_index.
+    Compacted { snapshot_index: usize },
+}
+
+/// LogStream iterates over committed log entries as LogRecords, in order. It
+/// is returned by Replica::log_stream.
+pub struct LogStream<'a, T, F>
+where
+    T: StateMachineTransition,
+    F: Fn(&T) -> Vec<u8>,
+{
+    entries: slice::Iter<'a, LogEntry<T>>,
+    encode: F,
+}
+
+impl<'a, T, F> LogStream<'a, T, F>
+where
+    T: StateMachineTransition,
+    F: Fn(&T) -> Vec<u8>,
+{
+    pub(crate) fn new(entries: &'a [LogEntry<T>], encode: F) -> LogStream<'a, T, F> {
+        LogStream {
+            entries: entries.iter(),
+            encode,
+        }
+    }
+}
+
+impl<'a, T, F> Iterator for LogStream<'a, T, F>
+where
+    T: StateMachineTransition,
+    F: Fn(&T) -> Vec<u8>,
+{
+    type Item = LogRecord;
+
+    fn next(&mut self) -> Option<LogRecord> {
+        self.entries.next().map(|entry| LogRecord {
+            index: entry.index,
+            term: entry.term,
+            payload: (self.encode)(&entry.transition),
+        })
+    }
+}
+
+// Wire types and keys (field number and wire type) of the protobuf encoding.
+const VARINT: u64 = 0;
+const LENGTH_DELIMITED: u64 = 2;
+const INDEX_KEY: u64 = 1 << 3 | VARINT;
+const TERM_KEY: u64 = 2 << 3 | VARINT;
+const PAYLOAD_KEY: u64 = 3 << 3 | LENGTH_DELIMITED;
+
+fn put_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+// Returns the varint at the start of buf and the number of bytes it took.
+fn get_varint(buf: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0;
+    for (i, byte) in buf.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
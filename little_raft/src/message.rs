@@ -1,5 +1,6 @@
 use crate::replica::ReplicaID;
-use crate::state_machine::StateMachineTransition;
+use crate::state_machine::{ClientId, StateMachineTransition};
+use std::{collections::BTreeMap, time::SystemTime};
 
 /// LogEntry is a state machine transition along with some metadata needed for
 /// Raft.
@@ -11,6 +12,37 @@ where
     pub transition: T,
     pub index: usize,
     pub term: usize,
+    /// Membership of the cluster from this entry on, if this is a
+    /// configuration entry appended by Replica::add_server,
+    /// Replica::remove_server or Replica::change_membership. Configuration
+    /// entries carry the no-op transition.
+    pub config: Option<Configuration>,
+}
+
+/// Configuration is the membership of the cluster that a configuration entry
+/// switches to.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd)]
+pub struct Configuration {
+    /// IDs of the members of the cluster.
+    pub members: Vec<ReplicaID>,
+
+    /// IDs of the members of the previous configuration, while the cluster
+    /// moves from them to members with joint consensus: elections and commits
+    /// then need a majority of both. None outside of a joint configuration.
+    pub old_members: Option<Vec<ReplicaID>>,
+}
+
+/// MessagePriority hints the transport at which messages to deliver first when
+/// it cannot deliver all of them right away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MessagePriority {
+    /// High priority messages are small and keep the cluster stable, such as
+    /// the Leader's heartbeats.
+    High,
+
+    /// Normal priority messages are everything else, including the bulk of
+    /// the entries sent to a follower that is catching up.
+    Normal,
 }
 
 /// Message describes messages that the replicas pass between each other to
@@ -32,6 +64,10 @@ where
         prev_log_term: usize,
         entries: Vec<LogEntry<T>>,
         commit_index: usize,
+        /// Wall-clock time at which the Leader sent the request. Used to
+        /// detect clock drift between Replicas, and echoed back in the
+        /// response.
+        timestamp: Option<SystemTime>,
     },
 
     /// AppendEntryResponse is used by replicas to respond to AppendEntryRequest
@@ -42,6 +78,42 @@ where
         success: bool,
         last_index: usize,
         mismatch_index: Option<usize>,
+        /// Hash of the responder's log up to last_index, if the Replicas
+        /// verify content hashes.
+        content_hash: Option<u64>,
+        /// Timestamp of the AppendEntryRequest this responds to, which tells
+        /// the Leader when it sent that request.
+        request_timestamp: Option<SystemTime>,
+    },
+
+    /// InstallSnapshotRequest is used by the Leader to send a chunk of its
+    /// snapshot to a follower that needs entries the Leader compacted away.
+    /// Chunks are sent in order, offset being the position of data within the
+    /// snapshot, and done is set on the last one.
+    InstallSnapshotRequest {
+        from_id: ReplicaID,
+        term: usize,
+        last_included_index: usize,
+        last_included_term: usize,
+        data: Vec<u8>,
+        offset: usize,
+        done: bool,
+        /// Hash of the Leader's log up to last_included_index, if the
+        /// Replicas verify content hashes.
+        content_hash: Option<u64>,
+        /// Snapshot::applied_sequences of the snapshot, sent with every chunk.
+        applied_sequences: BTreeMap<ClientId, u64>,
+    },
+
+    /// InstallSnapshotResponse is used by replicas to acknowledge a snapshot
+    /// sent with InstallSnapshotRequest messages.
+    InstallSnapshotResponse {
+        from_id: ReplicaID,
+        term: usize,
+        last_included_index: usize,
+        /// Index of the last entry of the Replica's log once the snapshot is
+        /// installed, which tells the Leader where to send entries from.
+        last_index: usize,
     },
 
     /// VoteRequest is used by Candidates to solicit votes for themselves.
@@ -58,4 +130,58 @@ where
         term: usize,
         vote_granted: bool,
     },
+
+    /// PreVoteRequest is used by Replicas that run the Pre-Vote phase to find
+    /// out whether they could win an election in term before starting it.
+    /// Neither the sender nor the receiver changes its term or vote.
+    PreVoteRequest {
+        from_id: ReplicaID,
+        term: usize,
+        last_log_index: usize,
+        last_log_term: usize,
+    },
+
+    /// PreVoteResponse is used by replicas to respond to PreVoteRequest
+    /// messages.
+    PreVoteResponse {
+        from_id: ReplicaID,
+        term: usize,
+        vote_granted: bool,
+    },
+
+    /// TimeoutNow is used by a Leader that steps down to make the follower it
+    /// hands leadership to start an election right away.
+    TimeoutNow { from_id: ReplicaID, term: usize },
+}
+
+impl<T> Message<T>
+where
+    T: StateMachineTransition,
+{
+    /// Returns the ID of the Replica that sent the message.
+    pub fn from_id(&self) -> ReplicaID {
+        match self {
+            Message::AppendEntryRequest { from_id, .. }
+            | Message::AppendEntryResponse { from_id, .. }
+            | Message::InstallSnapshotRequest { from_id, .. }
+            | Message::InstallSnapshotResponse { from_id, .. }
+            | Message::VoteRequest { from_id, .. }
+            | Message::VoteResponse { from_id, .. }
+            | Message::PreVoteRequest { from_id, .. }
+            | Message::PreVoteResponse { from_id, .. }
+            | Message::TimeoutNow { from_id, .. } => *from_id,
+        }
+    }
+
+    /// Returns the priority of the message. Heartbeats, i.e. AppendEntryRequests
+    /// without entries, are high priority so that a Leader busy catching up a
+    /// follower keeps its leadership. All other messages are normal priority.
+    pub fn priority(&self) -> MessagePriority {
+        match self {
+            Message::AppendEntryRequest { entries, .. } if entries.is_empty() => {
+                MessagePriority::High
+            }
+            _ => MessagePriority::Normal,
+        }
+    }
 }
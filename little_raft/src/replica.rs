@@ -1,29 +1,198 @@
 use crate::{
+    clock::{Clock, SystemClock},
     cluster::Cluster,
-    message::{LogEntry, Message},
-    state_machine::{StateMachine, StateMachineTransition, TransitionState, TransitionAbandonedReason},
+    handle::{Metrics, Proposal, ProposeError, ProposeHandle, ReplicaHandle},
+    log_stream::{LogStream, LogStreamError},
+    message::{Configuration, LogEntry, Message},
+    state_machine::{
+        ApplyError, ClientId, Snapshot, SnapshotMeta, StateMachine, StateMachineTransition,
+        TransitionAbandonedReason, TransitionState, TransitionTimeline,
+    },
     timer::Timer,
 };
-use crossbeam_channel::{Receiver, Select};
-use rand::Rng;
+use crossbeam_channel::{unbounded, Receiver, Select, Sender};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::cell::RefCell;
 use std::cmp::Ordering;
+#[cfg(feature = "testing")]
+use std::collections::HashSet;
 use std::sync::{Arc, Mutex};
 use std::{
     cmp,
-    collections::{BTreeMap, BTreeSet},
-    time::{Duration, Instant},
+    collections::{hash_map::DefaultHasher, BTreeMap, BTreeSet, VecDeque},
+    hash::Hasher,
+    time::{Duration, Instant, SystemTime},
 };
 
-#[derive(Clone, PartialEq, Debug)]
-enum State {
+/// State is the role a Replica plays in the cluster.
+#[derive(Clone, Copy, PartialEq, Debug, Default)]
+pub enum State {
+    /// Followers replicate the Leader's log and vote in elections.
+    #[default]
     Follower,
+
+    /// Candidates solicit votes to become the Leader.
     Candidate,
+
+    /// The Leader accepts transitions and replicates them to the followers.
     Leader,
 }
 
+/// RoleChange describes a Replica becoming the Leader, a follower or a
+/// Candidate, see Replica::set_on_state_change.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RoleChange {
+    /// Role the Replica had before the change.
+    pub old_state: State,
+
+    /// Role the Replica has after the change.
+    pub new_state: State,
+
+    /// Term of the Replica after the change.
+    pub term: usize,
+}
+
 /// ReplicaID is a type alias used to identify Raft nodes.
 pub type ReplicaID = usize;
 
+/// RejectReason describes why the Replica refused to vote for a Candidate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RejectReason {
+    /// The Candidate's term is lower than the Replica's term.
+    StaleTerm,
+
+    /// The Candidate's log is not as up-to-date as the Replica's log.
+    LogBehind,
+
+    /// The Replica has already voted for another Candidate in this term.
+    AlreadyVoted,
+}
+
+/// RaftError describes why Replica::start returned before the Cluster halted
+/// the Replica. The Replica can be dropped and created again from its
+/// persisted state.
+#[derive(Clone, Debug, PartialEq)]
+pub enum RaftError {
+    /// The sending side of recv_msg or recv_transition was dropped, so the
+    /// Replica can no longer be notified of messages or transitions.
+    ChannelDisconnected,
+
+    /// A thread panicked while holding the lock of the Cluster or of the
+    /// state machine, which may have been left in an inconsistent state.
+    MutexPoisoned,
+
+    /// The state machine failed to load a snapshot sent by the Leader.
+    SnapshotLoad(ApplyError),
+
+    /// The state machine failed to apply a committed transition.
+    Apply(ApplyError),
+}
+
+/// ConfigError describes why Replica::try_new refused a configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ConfigError {
+    /// The minimum of the election timeout range is above its maximum.
+    EmptyElectionTimeoutRange { min: Duration, max: Duration },
+
+    /// The heartbeat timeout is not below the minimum election timeout, so
+    /// followers would start elections while the Leader is healthy.
+    HeartbeatTimeoutTooLong {
+        heartbeat_timeout: Duration,
+        min_election_timeout: Duration,
+    },
+}
+
+/// ConflictResolution describes how the Leader looks for the point where a
+/// follower's log diverges from its own after the follower rejects an
+/// AppendEntryRequest.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConflictResolution {
+    /// Retry right before the rejected index, or right after the end of the
+    /// follower's log if it is shorter. A follower that is simply behind is
+    /// found in a single round-trip, but one with a long conflicting suffix
+    /// is walked back one entry per round-trip.
+    FollowerHint,
+
+    /// Retry halfway between the highest index known to match and the
+    /// rejected index. Any divergence point is found in a logarithmic number
+    /// of round-trips, but the probe can land well before it, in which case
+    /// the Leader resends entries the follower already had.
+    BinarySearch,
+}
+
+/// FaultToleranceCheck describes what the Leader does with a membership change
+/// that leaves the cluster able to tolerate fewer failures than before, and
+/// fewer than the floor set with Replica::set_fault_tolerance_floor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaultToleranceCheck {
+    /// Report the change to Cluster::register_reduced_fault_tolerance, then
+    /// go on with it.
+    Warn,
+
+    /// Report the change to Cluster::register_reduced_fault_tolerance, and
+    /// drop it.
+    Reject,
+}
+
+/// PeerProgress describes how far the Leader replicated its log to a peer,
+/// see Replica::replication_status.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeerProgress {
+    /// Index of the next log entry the Leader sends to the peer. With
+    /// pipelining, entries before it may still be on their way.
+    pub next_index: usize,
+
+    /// Index of the highest log entry known to be replicated on the peer.
+    pub match_index: usize,
+
+    /// Number of committed entries the peer is not known to hold yet.
+    pub lag: usize,
+}
+
+/// TermEntries describes the committed log entries of a term, see
+/// Replica::entries_in_term.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TermEntries<T>
+where
+    T: StateMachineTransition,
+{
+    /// Committed entries of the term that are still in the log.
+    pub entries: Vec<LogEntry<T>>,
+
+    /// Whether some entries of the term may have been compacted into a
+    /// snapshot, in which case entries is not the whole term.
+    pub truncated: bool,
+}
+
+/// Replication state the Replica keeps about each of its peers.
+#[derive(Clone, Copy, Debug, Default)]
+struct PeerState {
+    /// Index of the next log entry to send to the peer. Only meaningful on
+    /// leaders.
+    next_index: usize,
+
+    /// Index of highest log entry known to be replicated on the peer. Only
+    /// meaningful on leaders.
+    match_index: usize,
+
+    /// Highest match_index this Replica learned while it was the Leader, if
+    /// any. Kept across terms.
+    known_match_index: Option<usize>,
+
+    /// When the peer last acknowledged this Replica as the Leader of the
+    /// current term. Only meaningful on leaders.
+    last_ack: Option<Instant>,
+
+    /// When the Leader sent the latest AppendEntryRequest of the current term
+    /// that the peer acknowledged. The peer heard from the Leader no earlier
+    /// than that. Only meaningful on leaders.
+    lease_ack: Option<Instant>,
+
+    /// Number of batches of entries sent to the peer ahead of its
+    /// acknowledgements. Only meaningful on leaders that pipeline entries.
+    inflight: usize,
+}
+
 /// Replica describes the local instance running the Raft algorithm. Its goal is
 /// to maintain the consistency of the user-defined StateMachine across the
 /// cluster. It uses the user-defined Cluster implementation to talk to other
@@ -37,12 +206,26 @@ where
     /// ID of this Replica.
     id: ReplicaID,
 
-    /// IDs of other Replicas in the cluster.
+    /// IDs of other Replicas in the cluster, as of the latest applied
+    /// configuration entry.
     peer_ids: Vec<ReplicaID>,
 
+    /// Whether this Replica belongs to the cluster as of the latest applied
+    /// configuration entry. A Replica removed from the cluster does not start
+    /// elections.
+    member: bool,
+
+    /// Old and new members of the cluster while it moves between them with
+    /// joint consensus.
+    joint_members: Option<(Vec<ReplicaID>, Vec<ReplicaID>)>,
+
     /// User-defined state machine that the cluster Replicates.
     state_machine: Arc<Mutex<S>>,
 
+    /// Optional state machine that is applied the same transitions as
+    /// state_machine but is not otherwise used by the Replica.
+    shadow_state_machine: Option<Arc<Mutex<dyn StateMachine<T> + Send>>>,
+
     /// Interface a Replica uses to communicate with the rest of the cluster.
     cluster: Arc<Mutex<C>>,
 
@@ -50,7 +233,7 @@ where
     current_term: usize,
 
     /// ID of peers with votes for self.
-    current_votes: Option<Box<BTreeSet<usize>>>,
+    current_votes: Option<BTreeSet<usize>>,
 
     /// State of this Replica.
     state: State,
@@ -58,28 +241,80 @@ where
     /// Who the last vote was cast for.
     voted_for: Option<usize>,
 
-    /// entries this Replica is aware of.
+    /// entries this Replica is aware of. Its first entry stands for the last
+    /// entry compacted into the snapshot, if any.
     log: Vec<LogEntry<T>>,
 
+    /// Index of the first entry of the log.
+    index_offset: usize,
+
+    /// Number of applied entries past the last snapshot after which the log
+    /// is compacted. None disables compaction.
+    snapshot_delta: Option<usize>,
+
+    /// Whether the log is compacted when the Replica is shut down.
+    snapshot_on_shutdown: bool,
+
+    /// Age, on the Replica's clock, past which the snapshot is replaced with
+    /// a new one if entries were applied since. None disables the limit.
+    max_snapshot_age: Option<Duration>,
+
+    /// When the Replica last took or installed a snapshot, or first checked
+    /// its age if it has not done either yet.
+    snapshot_taken_at: Option<Instant>,
+
+    /// Size in bytes of the transitions in the log at which the log is
+    /// compacted right away and the Leader stops accepting transitions. None
+    /// disables the limit.
+    hard_log_memory_limit: Option<usize>,
+
+    /// Number of times the log was compacted because of
+    /// hard_log_memory_limit.
+    emergency_compactions: usize,
+
+    /// Latest snapshot of the state machine, which replaces the log up to
+    /// index_offset.
+    snapshot: Option<Snapshot>,
+
+    /// Maximum number of bytes of snapshot data sent in a single
+    /// InstallSnapshotRequest. None sends the whole snapshot at once.
+    max_snapshot_chunk_size: Option<usize>,
+
+    /// Chunks of the snapshot the Leader is sending, received so far, along
+    /// with the last_included_index and last_included_term of the snapshot.
+    snapshot_buffer: Option<(usize, usize, Vec<u8>)>,
+
     /// Index of the highest transition known to be committed.
     commit_index: usize,
 
     /// Index of the highest transition applied to the local state machine.
     last_applied: usize,
 
-    /// For each server, index of the next log entry to send to that server.
-    /// Only present on leaders.
-    next_index: BTreeMap<usize, usize>,
+    /// Replication state of each peer, by ID. Holds an entry for every
+    /// current peer.
+    peers: BTreeMap<ReplicaID, PeerState>,
+
+    /// Maximum number of entries sent in a single AppendEntryRequest. None
+    /// sends all the entries a peer is missing at once.
+    max_entries_per_append: Option<usize>,
 
-    /// For each server, index of highest log entry known to be replicated on
-    /// that server. Only present on leaders.
-    match_index: BTreeMap<usize, usize>,
+    /// Maximum number of batches of entries the Leader sends to a peer ahead
+    /// of its acknowledgements. None sends the next batch only once the peer
+    /// acknowledged the previous one or on the next heartbeat.
+    max_inflight_appends: Option<usize>,
+
+    /// Whether a new Leader starts replicating to each peer right after its
+    /// known_match_index instead of at the end of its log.
+    seed_next_index: bool,
 
     /// No-op transition used to force a faster Replica update when a cluster
     /// Leader changes. Applied this transition multiple times must have no
     /// affect on the state machine.
     noop_transition: T,
 
+    /// Source of time of the Replica.
+    clock: Arc<dyn Clock>,
+
     /// Timer used for heartbeat messages.
     heartbeat_timer: Timer,
 
@@ -90,11 +325,176 @@ where
     /// If no heartbeat message is received by the deadline, the Replica will
     /// start an election.
     next_election_deadline: Instant,
+
+    /// Election timeout picked for the current election deadline.
+    last_election_timeout: Duration,
+
+    /// A heartbeat is skipped if AppendEntryRequests were broadcast within this
+    /// window before it.
+    heartbeat_coalescing_window: Duration,
+
+    /// When AppendEntryRequests were last broadcast.
+    last_broadcast: Instant,
+
+    /// AppendEntryRequests sent within the last minimum election timeout,
+    /// oldest first, as the peer they were sent to, their timestamp and when
+    /// they were sent. Acknowledgements are dated by the request they answer.
+    sent_requests: VecDeque<(ReplicaID, SystemTime, Instant)>,
+
+    /// How long the Leader may hold new transitions back before broadcasting
+    /// them, to replicate more of them at once. Zero disables group commit.
+    max_batch_delay: Duration,
+
+    /// Number of held back transitions that makes the Leader broadcast right
+    /// away. Zero puts no bound on the batch.
+    max_batch_size: usize,
+
+    /// When the held back transitions must be broadcast, and how many there
+    /// are.
+    batch: Option<(Instant, usize)>,
+
+    /// Whether AppendEntry messages are processed before Vote messages
+    /// received in the same batch.
+    prioritize_append_entries: bool,
+
+    /// Whether a new Leader waits for a majority of peers to acknowledge it
+    /// before accepting transitions.
+    confirm_leadership_before_writes: bool,
+
+    /// Whether the Leader may accept transitions.
+    leadership_confirmed: bool,
+
+    /// Peers that acknowledged this Replica as the Leader of the current term.
+    leadership_acks: BTreeSet<ReplicaID>,
+
+    /// How the Leader looks for the point where a follower's log diverges.
+    conflict_resolution: ConflictResolution,
+
+    /// Number of failures below which a membership change that reduces the
+    /// fault tolerance of the cluster is checked, and how.
+    fault_tolerance_floor: (usize, FaultToleranceCheck),
+
+    /// Whether timeouts are derived from the Replica's ID instead of being
+    /// randomized.
+    deterministic: bool,
+
+    /// Source of randomness for timeouts.
+    rng: StdRng,
+
+    /// Range within which a randomized back-off is picked for a Candidate that
+    /// competed with another Candidate in its term. None disables the back-off.
+    candidacy_backoff: Option<(Duration, Duration)>,
+
+    /// Latest term in which another Candidate solicited votes from this
+    /// Candidate.
+    competing_candidacy_term: Option<usize>,
+
+    /// Whether elections are preceded by a Pre-Vote phase.
+    pre_vote: bool,
+
+    /// Whether the Leader steps down when a majority of the cluster did not
+    /// acknowledge it within the minimum election timeout.
+    check_quorum: bool,
+
+    /// ID of peers that would vote for self in the next term, while a Pre-Vote
+    /// phase is in progress.
+    pre_votes: Option<BTreeSet<ReplicaID>>,
+
+    /// When this Replica last heard from a Leader of its term.
+    last_leader_contact: Option<Instant>,
+
+    /// Minimum time between two consecutive VoteRequest broadcasts.
+    min_vote_request_interval: Duration,
+
+    /// When VoteRequests were last broadcast.
+    last_vote_request: Option<Instant>,
+
+    /// Highest commit index ever received from a Leader. Used to check that
+    /// this Replica holds all committed entries when it becomes the Leader.
+    highest_seen_commit_index: usize,
+
+    /// Highest sequence number applied for each client that tags its
+    /// transitions, see StateMachineTransition::dedup_key.
+    applied_sequences: BTreeMap<ClientId, u64>,
+
+    /// Number of VoteRequests this Replica rejected, by reason.
+    vote_rejections: BTreeMap<RejectReason, usize>,
+
+    /// Whether AppendEntryResponses carry a hash of the log that the Leader
+    /// checks before counting them toward a commit.
+    verify_content_hashes: bool,
+
+    /// Cached hashes of the log, see content_hash_at.
+    content_hashes: Vec<u64>,
+
+    /// Drift between the Leader's clock and ours beyond which it is reported
+    /// to the Cluster. None disables the check.
+    max_clock_drift: Option<Duration>,
+
+    /// How much earlier than election_timeout.0 the Leader's lease ends, to
+    /// make up for followers whose clocks run fast.
+    clock_skew_bound: Duration,
+
+    /// Peers this Replica behaves as if it were partitioned from.
+    #[cfg(feature = "testing")]
+    partition: HashSet<ReplicaID>,
+
+    /// Handle shared with other threads to observe the Replica.
+    handle: ReplicaHandle,
+
+    /// Leader last reported to the Cluster.
+    leader_id: Option<ReplicaID>,
+
+    /// Number of unapplied entries in the log at which the Leader stops
+    /// accepting transitions. None disables the limit.
+    apply_backlog_limit: Option<usize>,
+
+    /// Whether the Leader left pending transitions in the state machine
+    /// because of apply_backlog_limit or hard_log_memory_limit.
+    transitions_deferred: bool,
+
+    /// Callback invoked each time the Replica becomes the Leader, a follower
+    /// or a Candidate.
+    on_state_change: Option<Box<dyn Fn(RoleChange) + Send>>,
+
+    /// Peer the Leader is handing leadership over to, and until when it
+    /// waits for the peer to catch up before giving up.
+    leadership_transfer: Option<(ReplicaID, Instant)>,
+
+    /// Error that halted the Replica, if any.
+    fatal_error: Option<RaftError>,
+
+    /// When the Replica last changed its role.
+    role_changed_at: Instant,
+
+    /// Indexes of the uncommitted entries this Replica appended before it lost
+    /// leadership. They are reported abandoned if a new Leader overwrites
+    /// them.
+    doomed_entries: BTreeSet<usize>,
+
+    /// Messages to hand to the caller of handle_message or handle_timeout
+    /// instead of sending them through the Cluster.
+    outbox: Option<Vec<(ReplicaID, Message<T>)>>,
+
+    /// Timelines of the transitions this Replica appended as the Leader that
+    /// are not applied yet, by log index.
+    in_flight_timelines: BTreeMap<usize, TransitionTimeline>,
+
+    /// Channel of the transitions proposed through ProposeHandles.
+    proposals: (Sender<Proposal<T>>, Receiver<Proposal<T>>),
+
+    /// Proposals received while waiting for something else, that are not
+    /// loaded yet.
+    received_proposals: Vec<Proposal<T>>,
+
+    /// Where to report the outcome of the proposals this Replica appended as
+    /// the Leader that are not committed yet, by log index.
+    proposal_waiters: BTreeMap<usize, Sender<Result<(), ProposeError>>>,
 }
 
 impl<S, T, C> Replica<S, T, C>
 where
-    T: StateMachineTransition,
+    T: StateMachineTransition + 'static,
     S: StateMachine<T>,
     C: Cluster<T>,
 {
@@ -102,7 +502,10 @@ where
     ///
     /// id is the ID of this Replica within the cluster.
     ///
-    /// peer_ids is a vector of IDs of all other Replicas in the cluster.
+    /// peer_ids is a vector of IDs of all other Replicas in the cluster. A
+    /// configuration entry in the applied part of the log, see add_server,
+    /// overrides it on restart. Configuration entries compacted into a
+    /// snapshot are not kept, so pass the latest membership.
     ///
     /// cluster represents the abstraction the Replica uses to talk with other
     /// Replicas.
@@ -110,7 +513,9 @@ where
     /// state_machine is the state machine that Raft maintains.
     ///
     /// noop_transition is a transition that can be applied to the state machine
-    /// multiple times with no effect.
+    /// multiple times with no effect. Its ID is reserved by the Replica: no-op
+    /// entries are never reported through register_transition_state, and user
+    /// transitions that share the no-op's ID are abandoned.
     ///
     /// heartbeat_timeout defines how often the Leader Replica sends out
     /// heartbeat messages.
@@ -124,6 +529,8 @@ where
     /// and responsiveness needs. An election_timeout_range / heartbeat_timeout
     /// ratio that's too low might cause unwarranted re-elections in the
     /// cluster.
+    ///
+    /// new does not check the timeouts, see try_new.
     pub fn new(
         id: ReplicaID,
         peer_ids: Vec<ReplicaID>,
@@ -133,275 +540,2318 @@ where
         heartbeat_timeout: Duration,
         election_timeout_range: (Duration, Duration),
     ) -> Replica<S, T, C> {
-        Replica {
+        // Entries that the state machine has already applied before a restart
+        // must not be applied again.
+        let mut last_applied = state_machine.lock().unwrap().applied_index();
+        // Pick up the term, vote, snapshot and log the Replica had before a
+        // restart. The log follows the snapshot, if any.
+        let (current_term, voted_for) = state_machine.lock().unwrap().load_hard_state();
+        let snapshot = state_machine.lock().unwrap().read_snapshot();
+        let (index_offset, offset_term) = snapshot.as_ref().map_or((0, 0), |snapshot| {
+            (snapshot.last_included_index, snapshot.last_included_term)
+        });
+        let mut log = vec![LogEntry {
+            term: offset_term,
+            index: index_offset,
+            transition: noop_transition.clone(),
+            config: None,
+        }];
+        log.extend(state_machine.lock().unwrap().read_log());
+        // A state machine that lost what it applied starts over from the
+        // snapshot.
+        let mut fatal_error = None;
+        if let Some(snapshot) = snapshot.as_ref().filter(|_| last_applied < index_offset) {
+            match state_machine.lock().unwrap().load_snapshot(snapshot) {
+                Ok(()) => last_applied = index_offset,
+                Err(error) => fatal_error = Some(RaftError::SnapshotLoad(error)),
+            }
+        }
+        // Pick up the membership of the latest configuration entry applied
+        // before a restart.
+        let config = log
+            .iter()
+            .rev()
+            .filter(|entry| entry.index <= last_applied)
+            .find_map(|entry| entry.config.clone());
+        // Remember which client sequence numbers were applied before a
+        // restart, so that resubmitted transitions are still skipped: the
+        // snapshot knows those of the entries it replaces, and the log the
+        // rest.
+        let applied_sequences = snapshot
+            .as_ref()
+            .map(|snapshot| snapshot.applied_sequences.clone())
+            .unwrap_or_default();
+        let clock: Arc<dyn Clock> = Arc::new(SystemClock);
+        let now = clock.now();
+        let handle = ReplicaHandle::default();
+        handle.update(index_offset, last_applied);
+        handle.update_leadership(current_term, false, None);
+        let peers = peer_ids
+            .iter()
+            .map(|peer_id| (*peer_id, PeerState::default()))
+            .collect();
+        let mut replica = Replica {
             state_machine,
+            shadow_state_machine: None,
             cluster,
             peer_ids,
+            member: true,
+            joint_members: None,
             id,
-            current_term: 0,
+            current_term,
             current_votes: None,
             state: State::Follower,
-            voted_for: None,
-            log: vec![LogEntry {
-                term: 0,
-                index: 0,
-                transition: noop_transition.clone(),
-            }],
+            voted_for,
+            log,
+            index_offset,
+            snapshot_delta: None,
+            max_snapshot_age: None,
+            snapshot_taken_at: None,
+            hard_log_memory_limit: None,
+            emergency_compactions: 0,
+            snapshot_on_shutdown: false,
+            snapshot,
+            max_snapshot_chunk_size: None,
+            snapshot_buffer: None,
             noop_transition,
-            commit_index: 0,
-            last_applied: 0,
-            next_index: BTreeMap::new(),
-            match_index: BTreeMap::new(),
+            commit_index: index_offset,
+            last_applied,
+            peers,
+            max_entries_per_append: None,
+            max_inflight_appends: None,
+            seed_next_index: false,
             election_timeout: election_timeout_range,
-            heartbeat_timer: Timer::new(heartbeat_timeout),
-            next_election_deadline: Instant::now(),
+            heartbeat_timer: Timer::new(heartbeat_timeout, clock.clone()),
+            clock,
+            next_election_deadline: now,
+            last_election_timeout: Duration::from_secs(0),
+            heartbeat_coalescing_window: Duration::from_secs(0),
+            last_broadcast: now,
+            sent_requests: VecDeque::new(),
+            max_batch_delay: Duration::from_secs(0),
+            max_batch_size: 0,
+            batch: None,
+            prioritize_append_entries: false,
+            confirm_leadership_before_writes: false,
+            leadership_confirmed: false,
+            leadership_acks: BTreeSet::new(),
+            conflict_resolution: ConflictResolution::FollowerHint,
+            fault_tolerance_floor: (1, FaultToleranceCheck::Warn),
+            deterministic: false,
+            rng: StdRng::from_entropy(),
+            candidacy_backoff: None,
+            competing_candidacy_term: None,
+            pre_vote: false,
+            check_quorum: false,
+            pre_votes: None,
+            last_leader_contact: None,
+            min_vote_request_interval: Duration::from_secs(0),
+            last_vote_request: None,
+            highest_seen_commit_index: 0,
+            vote_rejections: BTreeMap::new(),
+            applied_sequences,
+            in_flight_timelines: BTreeMap::new(),
+            proposals: unbounded(),
+            received_proposals: Vec::new(),
+            proposal_waiters: BTreeMap::new(),
+            verify_content_hashes: false,
+            content_hashes: Vec::new(),
+            max_clock_drift: None,
+            clock_skew_bound: Duration::ZERO,
+            #[cfg(feature = "testing")]
+            partition: HashSet::new(),
+            handle,
+            leader_id: None,
+            apply_backlog_limit: None,
+            transitions_deferred: false,
+            on_state_change: None,
+            leadership_transfer: None,
+            fatal_error,
+            role_changed_at: now,
+            doomed_entries: BTreeSet::new(),
+            outbox: None,
+        };
+        if let Some(config) = config {
+            replica.apply_configuration(&config);
         }
-    }
-
-    /// This function starts the Replica and blocks forever.
-    ///
-    /// recv_msg is a channel on which the user must notify the Replica whenever
-    /// new messages from the Cluster are available. The Replica will not poll
-    /// for messages from the Cluster unless notified through recv_msg.
-    ///
-    /// recv_transition is a channel on which the user must notify the Replica
-    /// whenever new transitions to be processed for the StateMachine are
-    /// available. The Replica will not poll for pending transitions for the
-    /// StateMachine unless notified through recv_transition.
-    pub fn start(&mut self, recv_msg: Receiver<()>, recv_transition: Receiver<()>) {
-        loop {
-            if self.cluster.lock().unwrap().halt() {
-                return;
-            }
-
-            match self.state {
-                State::Leader => self.poll_as_leader(&recv_msg, &recv_transition),
-                State::Follower => self.poll_as_follower(&recv_msg),
-                State::Candidate => self.poll_as_candidate(&recv_msg),
+        for index in index_offset + 1..=last_applied.min(replica.last_log_index()) {
+            if let Some((client_id, sequence)) = replica.entry(index).transition.dedup_key() {
+                record_sequence(&mut replica.applied_sequences, client_id, sequence);
             }
+        }
+        replica.publish_metrics();
+        replica
+    }
 
-            self.apply_ready_entries();
+    /// Create a new Replica like new does, after checking that
+    /// election_timeout_range is not empty and that heartbeat_timeout is below
+    /// its minimum. Returns the first problem found otherwise.
+    pub fn try_new(
+        id: ReplicaID,
+        peer_ids: Vec<ReplicaID>,
+        cluster: Arc<Mutex<C>>,
+        state_machine: Arc<Mutex<S>>,
+        noop_transition: T,
+        heartbeat_timeout: Duration,
+        election_timeout_range: (Duration, Duration),
+    ) -> Result<Replica<S, T, C>, ConfigError> {
+        let (min, max) = election_timeout_range;
+        if min > max {
+            return Err(ConfigError::EmptyElectionTimeoutRange { min, max });
+        }
+        if heartbeat_timeout >= min {
+            return Err(ConfigError::HeartbeatTimeoutTooLong {
+                heartbeat_timeout,
+                min_election_timeout: min,
+            });
         }
+        Ok(Replica::new(
+            id,
+            peer_ids,
+            cluster,
+            state_machine,
+            noop_transition,
+            heartbeat_timeout,
+            election_timeout_range,
+        ))
     }
 
-    fn poll_as_leader(&mut self, recv_msg: &Receiver<()>, recv_transition: &Receiver<()>) {
-        let mut select = Select::new();
-        let recv_heartbeat = self.heartbeat_timer.get_rx();
-        let (msg, transition, heartbeat) = (
-            select.recv(recv_msg),
-            select.recv(recv_transition),
-            select.recv(recv_heartbeat),
-        );
+    /// Run a shadow state machine alongside the primary one, for example to
+    /// try out a new implementation before switching to it. The shadow state
+    /// machine is applied every transition the primary state machine is
+    /// applied, in the same order, but nothing else: its transition states and
+    /// pending transitions are ignored. Compare the two state machines to find
+    /// out whether they diverged.
+    pub fn set_shadow_state_machine(
+        &mut self,
+        shadow_state_machine: Arc<Mutex<dyn StateMachine<T> + Send>>,
+    ) {
+        self.shadow_state_machine = Some(shadow_state_machine);
+    }
 
-        let oper = select.select();
-        match oper.index() {
-            // Process pending messages.
-            i if i == msg => {
-                oper.recv(recv_msg)
-                    .expect("could not react to a new message");
-                let messages = self.cluster.lock().unwrap().receive_messages();
-                for message in messages {
-                    self.process_message(message);
-                }
-            }
-            // Process pending transitions.
-            i if i == transition => {
-                oper.recv(recv_transition)
-                    .expect("could not react to a new transition");
-                self.load_new_transitions();
-                self.broadcast_append_entry_request();
-            }
-            // Broadcast heartbeat messages.
-            i if i == heartbeat => {
-                oper.recv(recv_heartbeat)
-                    .expect("could not react to the heartbeat");
-                self.broadcast_append_entry_request();
-                self.heartbeat_timer.renew();
-            }
-            _ => unreachable!(),
-        }
+    /// Call on_state_change each time the Replica becomes the Leader, a
+    /// follower or a Candidate, for example to update a dashboard or to run
+    /// background jobs on the Leader only. It is also called when the Replica
+    /// enters a new term in the same role. The Replica holds none of its locks
+    /// while calling it, but it does wait for it to return, so keep it short.
+    pub fn set_on_state_change<F>(&mut self, on_state_change: F)
+    where
+        F: Fn(RoleChange) + Send + 'static,
+    {
+        self.on_state_change = Some(Box::new(on_state_change));
     }
 
-    fn broadcast_append_entry_request(&mut self) {
-        self.broadcast_message(|peer_id: ReplicaID| Message::AppendEntryRequest {
-            term: self.current_term,
-            from_id: self.id,
-            prev_log_index: self.next_index[&peer_id] - 1,
-            prev_log_term: self.log[self.next_index[&peer_id] - 1].term,
-            entries: self.get_entries_for_peer(peer_id),
-            commit_index: self.commit_index,
-        });
+    /// Send snapshots to followers in chunks of at most max_chunk_size bytes,
+    /// so that large snapshots do not produce messages too large for the
+    /// transport. Every chunk of the snapshot is sent each time the follower
+    /// needs it, in order, and the follower puts them back together. Panics
+    /// if max_chunk_size is 0.
+    pub fn set_max_snapshot_chunk_size(&mut self, max_chunk_size: usize) {
+        assert!(max_chunk_size > 0, "snapshot chunks must hold some data");
+        self.max_snapshot_chunk_size = Some(max_chunk_size);
     }
 
-    fn poll_as_follower(&mut self, recv_msg: &Receiver<()>) {
-        match recv_msg.recv_deadline(self.next_election_deadline) {
-            // Process pending messages.
-            Ok(_) => {
-                let messages = self.cluster.lock().unwrap().receive_messages();
-                // Update the election deadline if more than zero messages were
-                // actually received.
-                if !messages.is_empty() {
-                    self.update_election_deadline();
-                }
+    /// Send at most max_entries AppendEntryRequest entries at a time, so that a
+    /// follower far behind the Leader does not receive a message too large for
+    /// the transport. The Leader sends the next entries as soon as the
+    /// follower acknowledges the previous ones, or on the next heartbeat.
+    /// Panics if max_entries is 0.
+    pub fn set_max_entries_per_append(&mut self, max_entries: usize) {
+        assert!(max_entries > 0, "appends must carry some entries");
+        self.max_entries_per_append = Some(max_entries);
+    }
 
-                for message in messages {
-                    self.process_message(message);
-                }
-            }
-            // Become candidate and update elction deadline.
-            _ => {
-                self.become_candidate();
-                self.update_election_deadline();
-            }
-        }
+    /// Pipeline entries to the followers: send up to window batches of entries
+    /// to a follower without waiting for it to acknowledge them, assuming it
+    /// will accept them. This keeps replication going at more than one batch
+    /// per round-trip. If the follower rejects a batch, the Leader goes back to
+    /// the point its log diverges as usual. Batches are split according to
+    /// set_max_entries_per_append. Panics if window is 0.
+    pub fn set_max_inflight_appends(&mut self, window: usize) {
+        assert!(window > 0, "at least one batch must be in flight");
+        self.max_inflight_appends = Some(window);
+    }
 
-        // Load new transitions. The follower will ignore these transitions, but
-        // they are still polled for periodically to ensure there are no stale
-        // transitions in case the Replica's state changes.
-        self.load_new_transitions();
+    /// Group commit: hold new transitions back for up to max_batch_delay, or
+    /// until max_batch_size of them accumulate, before broadcasting them, so
+    /// that a high rate of transitions is replicated in fewer rounds. This
+    /// trades latency for throughput. A max_batch_size of 0 puts no bound on
+    /// the batch. Only applies to Replicas run with start. Disabled by
+    /// default, with a max_batch_delay of 0.
+    pub fn set_group_commit(&mut self, max_batch_delay: Duration, max_batch_size: usize) {
+        self.max_batch_delay = max_batch_delay;
+        self.max_batch_size = max_batch_size;
     }
 
-    fn process_message(&mut self, message: Message<T>) {
-        match self.state {
-            State::Leader => self.process_message_as_leader(message),
-            State::Candidate => self.process_message_as_candidate(message),
-            State::Follower => self.process_message_as_follower(message),
-        }
+    /// Skip heartbeats that would follow AppendEntryRequests broadcast less
+    /// than window ago, for example because a new transition arrived just
+    /// before the heartbeat. Peers then get one message instead of two
+    /// nearly identical ones. The window should not exceed heartbeat_timeout.
+    /// Disabled by default.
+    pub fn set_heartbeat_coalescing_window(&mut self, window: Duration) {
+        self.heartbeat_coalescing_window = window;
     }
 
-    fn update_election_deadline(&mut self) {
-        // Randomize each election deadline within the allowed range.
-        self.next_election_deadline = Instant::now()
-            + rand::thread_rng().gen_range(self.election_timeout.0..=self.election_timeout.1);
+    /// Set the minimum time between two consecutive elections started by this
+    /// Replica. Elections that would start sooner are postponed, so the rate
+    /// of VoteRequests each peer receives stays bounded even if elections keep
+    /// timing out. Disabled by default.
+    pub fn set_min_vote_request_interval(&mut self, interval: Duration) {
+        self.min_vote_request_interval = interval;
     }
 
-    fn poll_as_candidate(&mut self, recv_msg: &Receiver<()>) {
-        match recv_msg.recv_deadline(self.next_election_deadline) {
-            Ok(_) => {
-                // Process pending messages.
-                let messages = self.cluster.lock().unwrap().receive_messages();
-                // Update the election deadline if more than zero messages were
-                // actually received.
-                if !messages.is_empty() {
-                    self.update_election_deadline();
-                }
-                for message in messages {
-                    self.process_message(message);
-                }
-            }
-            // Become candidate and update elction deadline.
-            _ => {
-                self.become_candidate();
-                self.update_election_deadline();
-            }
-        }
+    /// Make the Replica find out whether it could win an election before
+    /// starting it, as in the Pre-Vote extension of Raft. A Replica that times
+    /// out first asks its peers for pre-votes in the next term, without
+    /// changing its own term or vote, and only starts the election once a
+    /// majority granted them. Peers grant pre-votes as they would grant votes,
+    /// unless they heard from a Leader within the minimum election timeout. A
+    /// Replica cut off from the cluster then no longer inflates its term and
+    /// does not depose the Leader when it comes back. It must be enabled on
+    /// every Replica of the cluster. Disabled by default.
+    pub fn set_pre_vote(&mut self, pre_vote: bool) {
+        self.pre_vote = pre_vote;
+    }
 
-        // Load new transitions. The candidate will ignore these transitions,
-        // but they are still polled for periodically to ensure there are no
-        // stale transitions in case the Replica's state changes.
-        self.load_new_transitions();
+    /// Make the Leader step down once a majority of the cluster has not
+    /// acknowledged it for the minimum election timeout, as in the
+    /// CheckQuorum extension of Raft. A Leader cut off from the cluster then
+    /// stops accepting transitions that cannot commit, and stops claiming to
+    /// be the Leader while another one may have been elected. Disabled by
+    /// default.
+    pub fn set_check_quorum(&mut self, check_quorum: bool) {
+        self.check_quorum = check_quorum;
     }
 
-    fn broadcast_message<F>(&self, message_generator: F)
-    where
-        F: Fn(usize) -> Message<T>,
-    {
-        self.peer_ids.iter().for_each(|peer_id| {
-            self.cluster
-                .lock()
-                .unwrap()
-                .send_message(*peer_id, message_generator(*peer_id))
-        });
+    /// Process AppendEntry messages before Vote messages received in the same
+    /// batch. Under load this lets the Replica hear from the current Leader
+    /// before a flood of VoteRequests from a flapping peer, which makes the
+    /// cluster more stable. Messages are processed strictly in the order they
+    /// were received by default.
+    pub fn set_prioritize_append_entries(&mut self, prioritize: bool) {
+        self.prioritize_append_entries = prioritize;
     }
 
-    // Get log entries that have not been acknowledged by the peer.
-    fn get_entries_for_peer(&self, peer_id: ReplicaID) -> Vec<LogEntry<T>> {
-        self.log[self.next_index[&peer_id]..self.log.len()].to_vec()
+    /// Make a new Leader wait until a majority of the cluster has responded to
+    /// its AppendEntryRequests before it accepts transitions. This closes the
+    /// window in which a Leader that has already been replaced in a higher
+    /// term accepts writes that can never commit. Transitions submitted in the
+    /// meantime stay pending in the state machine. Disabled by default.
+    pub fn set_confirm_leadership_before_writes(&mut self, confirm: bool) {
+        self.confirm_leadership_before_writes = confirm;
     }
 
-    // Apply entries that are ready to be applied.
-    fn apply_ready_entries(&mut self) {
-        // Move the commit index to the latest log index that has been
-        // replicated on the majority of the replicas.
-        if self.state == State::Leader && self.commit_index < self.log.len() - 1 {
-            let mut n = self.log.len() - 1;
-            let old_commit_index = self.commit_index;
-            while n > self.commit_index {
-                let num_replications =
-                    self.match_index.iter().fold(
-                        0,
-                        |acc, mtch_idx| if mtch_idx.1 >= &n { acc + 1 } else { acc },
-                    );
-
-                if num_replications * 2 >= self.peer_ids.len()
-                    && self.log[n].term == self.current_term
-                {
-                    self.commit_index = n;
-                }
-                n -= 1;
-            }
+    /// Make a new Leader start replicating to each peer right after the last
+    /// index that peer acknowledged when this Replica was last the Leader. A
+    /// peer that fell far behind then catches up without first rejecting an
+    /// AppendEntryRequest sent from the end of the log. If the peer's log
+    /// changed since, it rejects the request as usual. Disabled by default.
+    pub fn set_seed_next_index(&mut self, seed: bool) {
+        self.seed_next_index = seed;
+    }
 
-            for i in old_commit_index + 1..=self.commit_index {
-                let mut state_machine = self.state_machine.lock().unwrap();
-                state_machine.register_transition_state(
-                    self.log[i].transition.get_id(),
-                    TransitionState::Committed,
-                );
-            }
-        }
+    /// Set how the Leader looks for the point where a follower's log diverges
+    /// from its own. Defaults to ConflictResolution::FollowerHint.
+    pub fn set_conflict_resolution(&mut self, conflict_resolution: ConflictResolution) {
+        self.conflict_resolution = conflict_resolution;
+    }
 
-        // Apply entries that are behind the currently committed index.
-        while self.commit_index > self.last_applied {
-            self.last_applied += 1;
-            let mut state_machine = self.state_machine.lock().unwrap();
-            state_machine.apply_transition(self.log[self.last_applied].transition.clone());
-            state_machine.register_transition_state(
-                self.log[self.last_applied].transition.get_id(),
-                TransitionState::Applied,
-            );
-        }
+    /// Check membership changes that leave the cluster able to tolerate fewer
+    /// than floor failures, and fewer than it tolerates now: a cluster of n
+    /// members tolerates (n - 1) / 2 failures, so going from 3 members to 2
+    /// leaves none, and going from 5 to 4 loses one. The Leader handles such
+    /// changes as check says. Changes that do not reduce the fault tolerance,
+    /// such as growing a cluster one member at a time, are never checked.
+    /// Defaults to a floor of 1 with FaultToleranceCheck::Warn.
+    pub fn set_fault_tolerance_floor(&mut self, floor: usize, check: FaultToleranceCheck) {
+        self.fault_tolerance_floor = (floor, check);
     }
 
-    fn load_new_transitions(&mut self) {
-        // Load new transitions. Ignore the transitions if the replica is not
-        // the Leader.
-        let mut state_machine = self.state_machine.lock().unwrap();
-        let transitions = state_machine.get_pending_transitions();
-        for transition in transitions {
-            if self.state == State::Leader {
-                self.log.push(LogEntry {
-                    index: self.log.len(),
-                    transition: transition.clone(),
-                    term: self.current_term,
-                });
+    /// Enable a randomized back-off to resolve split votes faster. When the
+    /// election of a Candidate times out after another Candidate asked it for a
+    /// vote in the same term, the Candidate waits for an extra timeout picked
+    /// from backoff_range before starting a new election. Disabled by default.
+    pub fn set_candidacy_backoff(&mut self, backoff_range: (Duration, Duration)) {
+        self.candidacy_backoff = Some(backoff_range);
+    }
 
+    /// Make followers send a hash of their log with each acknowledgement, and
+    /// make the Leader ignore acknowledgements whose hash does not match its
+    /// own log. This catches transports that deliver different payloads to
+    /// different followers, at the cost of hashing every entry. It must be
+    /// enabled on every Replica of the cluster, and every Replica must hash
+    /// transitions the same way (see StateMachineTransition::content_hash).
+    /// Disabled by default.
+    pub fn set_verify_content_hashes(&mut self, verify: bool) {
+        self.verify_content_hashes = verify;
+    }
+
+    /// Make followers report to Cluster::register_clock_drift when the
+    /// timestamp of the Leader's AppendEntryRequests is further than
+    /// max_clock_drift from their own clock. This is for observability only.
+    /// Leave room for network latency, which counts toward the drift.
+    /// Disabled by default.
+    pub fn set_max_clock_drift(&mut self, max_clock_drift: Duration) {
+        self.max_clock_drift = Some(max_clock_drift);
+    }
+
+    /// Shorten the Leader's lease by clock_skew_bound, see
+    /// can_serve_lease_read. Set it to the most that a follower's clock may
+    /// gain on the Leader's over an election timeout, network delays
+    /// excluded. Defaults to zero, which assumes that clocks run at the same
+    /// rate. Panics if clock_skew_bound is not below the minimum election
+    /// timeout, which would leave no lease at all.
+    pub fn set_clock_skew_bound(&mut self, clock_skew_bound: Duration) {
+        assert!(
+            clock_skew_bound < self.election_timeout.0,
+            "clock skew bound {:?} must be below the minimum election timeout {:?}",
+            clock_skew_bound,
+            self.election_timeout.0
+        );
+        self.clock_skew_bound = clock_skew_bound;
+    }
+
+    /// Compact the log whenever delta entries were applied since the last
+    /// snapshot: the state machine serializes itself with
+    /// StateMachine::create_snapshot and the applied entries are dropped from
+    /// the log. The Leader sends its snapshot to followers that need entries
+    /// it dropped. Disabled by default. Panics if delta is 0.
+    pub fn set_snapshot_delta(&mut self, delta: usize) {
+        assert!(delta > 0, "snapshots must cover some entries");
+        self.snapshot_delta = Some(delta);
+    }
+
+    /// Compact the log once the snapshot is max_age old, as measured on the
+    /// Replica's clock, however few entries were applied since, so that the
+    /// snapshot stays fresh on a cluster that rarely writes. The age counts
+    /// from when the Replica last took or installed a snapshot, or from when
+    /// it started if it has not. A snapshot that covers every applied entry
+    /// is kept however old it is. Disabled by default. Panics if max_age is
+    /// zero.
+    pub fn set_max_snapshot_age(&mut self, max_age: Duration) {
+        assert!(max_age > Duration::ZERO, "snapshots must be allowed to age");
+        self.max_snapshot_age = Some(max_age);
+    }
+
+    /// Cap the memory the log takes as a last resort. Once the transitions in
+    /// the log take limit bytes or more, as reported by
+    /// StateMachineTransition::size, the Replica compacts the log right away,
+    /// whatever set_snapshot_delta says, and the Leader stops accepting
+    /// transitions until the log is below limit again. Only applied entries
+    /// are compacted, so the log stays above limit while applying is paused
+    /// or the entries are not committed yet. Disabled by default. Panics if
+    /// limit is 0.
+    pub fn set_hard_log_memory_limit(&mut self, limit: usize) {
+        assert!(limit > 0, "the log must be allowed some memory");
+        self.hard_log_memory_limit = Some(limit);
+    }
+
+    /// Compact the log with a final snapshot when the Replica is shut down
+    /// through ReplicaHandle::shutdown, so that a restarted Replica does not
+    /// need the entries applied so far. Disabled by default.
+    pub fn set_snapshot_on_shutdown(&mut self, snapshot: bool) {
+        self.snapshot_on_shutdown = snapshot;
+    }
+
+    /// Make the Leader stop accepting transitions while limit entries or more
+    /// of its log are not applied yet, for example because applying is
+    /// paused. Transitions submitted in the meantime stay pending in the state
+    /// machine and are accepted on a heartbeat once the backlog is below
+    /// limit. Disabled by default.
+    pub fn set_apply_backlog_limit(&mut self, limit: usize) {
+        self.apply_backlog_limit = Some(limit);
+    }
+
+    /// Stop applying committed entries to the state machine while replication
+    /// and commits go on. See ReplicaHandle::pause_apply to pause a running
+    /// Replica from another thread.
+    pub fn pause_apply(&mut self) {
+        self.handle.pause_apply();
+    }
+
+    /// Resume applying committed entries after pause_apply.
+    pub fn resume_apply(&mut self) {
+        self.handle.resume_apply();
+    }
+
+    /// Make the Replica behave as if it were partitioned from the given peers:
+    /// messages from them are dropped on receipt and messages to them are
+    /// never sent. Pass an empty set to heal the partition. Only available
+    /// with the testing feature.
+    #[cfg(feature = "testing")]
+    pub fn set_partition(&mut self, blocked: HashSet<ReplicaID>) {
+        self.partition = blocked;
+    }
+
+    /// Advance the commit index to index without checking that a quorum holds
+    /// the entries. THIS BREAKS RAFT'S SAFETY GUARANTEES: it is only meant to
+    /// set up scenarios in tests, such as committed entries that are not
+    /// applied yet. The entries are applied the next time the Replica
+    /// processes a message or a timeout. Panics if the log does not reach
+    /// index. Only available with the unsafe-testing feature.
+    #[cfg(feature = "unsafe-testing")]
+    pub fn force_commit_index(&mut self, index: usize) {
+        assert!(
+            index <= self.last_log_index(),
+            "cannot commit index {} past the end of the log at {}",
+            index,
+            self.last_log_index()
+        );
+        self.commit_index = cmp::max(self.commit_index, index);
+        self.handle.update(self.commit_index, self.last_applied);
+    }
+
+    /// Remove all randomness from the Replica. Election timeouts and candidacy
+    /// back-offs are then a fixed function of the Replica's ID, spread evenly
+    /// across their range, so that the Replica behaves the same way every time
+    /// it is fed the same messages in the same order. This is meant for
+    /// simulations that explore message interleavings. Disabled by default.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.deterministic = deterministic;
+    }
+
+    /// Seed the randomness of election timeouts and candidacy back-offs.
+    /// Unlike set_deterministic, timeouts stay random, but Replicas seeded the
+    /// same way pick the same sequence of timeouts on every run. This makes it
+    /// possible to replay a simulated cluster's elections, for example to
+    /// debug a rare livelock. Seeded from the operating system by default.
+    pub fn set_rng_seed(&mut self, seed: u64) {
+        self.rng = StdRng::seed_from_u64(seed);
+    }
+
+    /// Take the time from clock instead of the operating system, for example a
+    /// ManualClock that only moves when the test advances it. Election
+    /// deadlines, heartbeats, leases, leadership acknowledgements and the
+    /// times reported by the ReplicaHandle all follow the clock, while message
+    /// timestamps (see set_max_clock_drift) keep using the system clock. Set
+    /// the clock before starting the Replica.
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        let now = clock.now();
+        self.heartbeat_timer = Timer::new(self.heartbeat_timer.get_timeout(), clock.clone());
+        self.next_election_deadline = now;
+        self.last_broadcast = now;
+        self.role_changed_at = now;
+        self.handle.set_clock(clock.clone());
+        self.clock = clock;
+    }
+
+    /// Change how often the Leader sends out heartbeat messages, for example
+    /// to adapt to the latency observed on the network. It takes effect when
+    /// the heartbeat timer is next renewed. Keep the election timeout range
+    /// 2-3x the heartbeat timeout, as explained in new. Panics if
+    /// heartbeat_timeout is not below the minimum election timeout.
+    pub fn set_heartbeat_timeout(&mut self, heartbeat_timeout: Duration) {
+        assert!(
+            heartbeat_timeout < self.election_timeout.0,
+            "heartbeat timeout {:?} must be below the minimum election timeout {:?}",
+            heartbeat_timeout,
+            self.election_timeout.0
+        );
+        self.heartbeat_timer.set_timeout(heartbeat_timeout);
+    }
+
+    /// Change the range election timeouts are picked from. It takes effect
+    /// when the next election deadline is picked. Keep the range 2-3x the
+    /// heartbeat timeout, as explained in new. Panics if min is above max or
+    /// not above the heartbeat timeout.
+    pub fn set_election_timeout_range(&mut self, min: Duration, max: Duration) {
+        assert!(
+            min <= max,
+            "election timeout range {:?}..{:?} is empty",
+            min,
+            max
+        );
+        assert!(
+            min > self.heartbeat_timer.get_timeout(),
+            "minimum election timeout {:?} must be above the heartbeat timeout {:?}",
+            min,
+            self.heartbeat_timer.get_timeout()
+        );
+        self.election_timeout = (min, max);
+    }
+
+    /// Returns the randomized election timeout that was picked last, i.e. the
+    /// one that drives the current election deadline. It is zero until the
+    /// first deadline is picked.
+    pub fn last_election_timeout(&self) -> Duration {
+        self.last_election_timeout
+    }
+
+    /// Take the transitions this Replica appended as the Leader that were not
+    /// committed when it lost leadership, with their log indexes, so that
+    /// clients can submit them to the new Leader. They are reported abandoned
+    /// right away instead of when a new Leader overwrites them. Note that the
+    /// new Leader may still hold and commit them, so resubmitted transitions
+    /// should be idempotent.
+    pub fn take_inflight_proposals(&mut self) -> Vec<(T::TransitionID, usize)> {
+        let doomed_entries = std::mem::take(&mut self.doomed_entries);
+        let mut state_machine = self.state_machine.lock().unwrap();
+        let proposals: Vec<(T::TransitionID, usize)> = doomed_entries
+            .into_iter()
+            .map(|index| {
+                let transition_id = self.entry(index).transition.get_id();
+                state_machine.register_transition_state(
+                    self.entry(index).transition.get_id(),
+                    TransitionState::Abandoned(TransitionAbandonedReason::NotLeader),
+                );
+                (transition_id, index)
+            })
+            .collect();
+        drop(state_machine);
+        for (_, index) in &proposals {
+            if let Some(reply) = self.proposal_waiters.remove(index) {
+                let _ = reply.send(Err(ProposeError::Abandoned(
+                    TransitionAbandonedReason::NotLeader,
+                )));
+            }
+        }
+        proposals
+    }
+
+    /// Hand leadership over to the peer target, for example before taking
+    /// this Replica down for a restart. The Leader first brings target up to
+    /// date, then tells it to start an election right away and becomes a
+    /// follower. Transitions received in the meantime are abandoned. If target
+    /// does not catch up within an election timeout, the Leader gives up and
+    /// accepts transitions again. Does nothing unless the Replica is the
+    /// Leader and target is one of its peers, and stays one once the pending
+    /// membership change, if any, is applied.
+    pub fn transfer_leadership(&mut self, target: ReplicaID) {
+        let leaving = (self.last_applied + 1..=self.last_log_index())
+            .rev()
+            .find_map(|index| self.entry(index).config.as_ref())
+            .is_some_and(|config| !config.members.contains(&target));
+        if self.state != State::Leader || !self.peers.contains_key(&target) || leaving {
+            return;
+        }
+        self.leadership_transfer = Some((target, self.clock.now() + self.election_timeout.1));
+        self.broadcast_append_entry_request();
+        self.continue_leadership_transfer(self.clock.now());
+    }
+
+    /// Add the Replica with the given ID to the cluster. The Leader appends a
+    /// configuration entry to its log, and each Replica counts the new member
+    /// toward elections and commits once it applied that entry. Membership
+    /// changes one Replica at a time, so this does nothing unless the Replica
+    /// is the Leader, id is not a member yet and no other configuration entry
+    /// is in progress. Start the new Replica with the current members as its
+    /// peers.
+    pub fn add_server(&mut self, id: ReplicaID) {
+        if id == self.id || self.peer_ids.contains(&id) {
+            return;
+        }
+        let mut members = self.members();
+        members.push(id);
+        members.sort_unstable();
+        self.propose_configuration(Configuration {
+            members,
+            old_members: None,
+        });
+    }
+
+    /// Remove the Replica with the given ID from the cluster, the same way
+    /// add_server adds one. A Leader that removes itself keeps leading until
+    /// the configuration entry is applied, then steps down. A removed Replica
+    /// no longer starts elections and can be shut down.
+    pub fn remove_server(&mut self, id: ReplicaID) {
+        if id != self.id && !self.peer_ids.contains(&id) {
+            return;
+        }
+        let members = self
+            .members()
+            .into_iter()
+            .filter(|member_id| *member_id != id)
+            .collect();
+        self.change_membership(members);
+    }
+
+    /// Replace the members of the cluster with the given ones at once, for
+    /// example to move a cluster to a disjoint set of Replicas. The Leader
+    /// appends a joint configuration entry holding both the old and the new
+    /// members. From the time a Replica applies it, elections and commits
+    /// need a majority of both. Once the joint configuration is committed, the
+    /// Leader appends a configuration entry with the new members alone. This
+    /// does nothing unless the Replica is the Leader and no other membership
+    /// change is in progress. A Leader that is not a new member steps down
+    /// once the new configuration is applied.
+    pub fn change_membership(&mut self, mut members: Vec<ReplicaID>) {
+        members.sort_unstable();
+        members.dedup();
+        let old_members = self.members();
+        if members.is_empty() || members == old_members {
+            return;
+        }
+        // Changing a single member needs no joint configuration: any
+        // majority of the old members overlaps with any majority of the new
+        // ones.
+        let changed = members
+            .iter()
+            .chain(&old_members)
+            .filter(|id| members.contains(id) != old_members.contains(id))
+            .count();
+        self.propose_configuration(Configuration {
+            members,
+            old_members: if changed > 1 { Some(old_members) } else { None },
+        });
+    }
+
+    /// Returns whether the Leader can serve a read from its state machine
+    /// without contacting its peers, which is cheaper than any round-trip.
+    /// This holds while the Leader's lease lasts: election_timeout.0, minus the
+    /// clock skew bound set with set_clock_skew_bound, from the time the
+    /// Leader sent the AppendEntryRequests that a majority of the cluster last
+    /// acknowledged, since the followers cannot have heard from it any
+    /// earlier. This also requires that the state machine applied every
+    /// committed entry, including the Leader's no-op.
+    ///
+    /// The lease relies on followers refusing to elect another Leader for
+    /// election_timeout.0 after hearing from this one, so enable
+    /// set_pre_vote. Those election_timeout.0 are measured on the followers'
+    /// clocks. A follower whose clock runs fast may stop refusing before
+    /// election_timeout.0 have passed on the Leader's clock, but no earlier
+    /// than election_timeout.0 minus what its clock gained over that time. As
+    /// long as no clock gains more than the skew bound, any majority that
+    /// elects a new Leader includes a member of the acknowledging majority,
+    /// which cannot vote before the lease ended. Messages must also take much
+    /// less than election_timeout.0 to arrive. If these assumptions do not
+    /// hold, a read served under the lease may miss writes committed by a
+    /// newer Leader.
+    pub fn can_serve_lease_read(&self) -> bool {
+        if self.state != State::Leader
+            || self.entry(self.commit_index).term != self.current_term
+            || self.last_applied < self.commit_index
+        {
+            return false;
+        }
+
+        // The lease runs from the oldest request among those that the
+        // majority which acknowledged the Leader most recently answered.
+        let now = self.clock.now();
+        let lease_start = self.quorum_value(|id| match id == self.id {
+            true => Some(now),
+            false => self.peers[&id].lease_ack,
+        });
+        let lease = self
+            .election_timeout
+            .0
+            .saturating_sub(self.clock_skew_bound);
+        matches!(lease_start, Some(ack) if now.saturating_duration_since(ack) < lease)
+    }
+
+    /// Returns the Replica's core counters. Use ReplicaHandle::metrics to
+    /// scrape them from another thread while the Replica runs.
+    pub fn metrics(&self) -> Metrics {
+        Metrics {
+            current_term: self.current_term,
+            commit_index: self.commit_index,
+            last_applied: self.last_applied,
+            log_len: self.log.len(),
+            index_offset: self.index_offset,
+            state: self.state,
+            since_last_heartbeat: self
+                .last_heartbeat()
+                .map(|last_heartbeat| self.clock.now().saturating_duration_since(last_heartbeat)),
+            last_election_timeout: self.last_election_timeout,
+        }
+    }
+
+    /// Returns the current term of the Replica.
+    pub fn current_term(&self) -> usize {
+        self.current_term
+    }
+
+    /// Returns whether the Replica is the Leader.
+    pub fn is_leader(&self) -> bool {
+        self.state == State::Leader
+    }
+
+    /// Returns the Leader the Replica last reported to
+    /// Cluster::register_leader, if it knows of one. Clients can be redirected
+    /// there. See ReplicaHandle to query it from another thread.
+    pub fn leader_id(&self) -> Option<ReplicaID> {
+        self.leader_id
+    }
+
+    /// Returns the index of the highest log entry known to be committed. See
+    /// ReplicaHandle to query it from another thread.
+    pub fn commit_index(&self) -> usize {
+        self.commit_index
+    }
+
+    /// Returns whether the log entry at index is known to be committed, so
+    /// that clients holding the index of their transition can poll for it
+    /// instead of relying on register_transition_state. Entries compacted
+    /// into a snapshot are committed.
+    pub fn is_committed(&self, index: usize) -> bool {
+        index <= self.commit_index
+    }
+
+    /// Returns a handle to observe the Replica from other threads while it
+    /// runs.
+    pub fn handle(&self) -> ReplicaHandle {
+        self.handle.clone()
+    }
+
+    /// Returns a handle to propose transitions from other threads and wait
+    /// for them to commit while the Replica runs.
+    pub fn propose_handle(&self) -> ProposeHandle<T> {
+        ProposeHandle::new(self.handle.clone(), self.proposals.0.clone())
+    }
+
+    /// Returns the error that halted the Replica, if any. A Replica halts when
+    /// its state machine fails to apply a committed transition or to load a
+    /// snapshot with ApplyError::Fatal. It then stops applying entries and
+    /// start returns the error.
+    pub fn fatal_error(&self) -> Option<&ApplyError> {
+        match &self.fatal_error {
+            Some(RaftError::SnapshotLoad(error)) | Some(RaftError::Apply(error)) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Returns how long the Replica has been in its current role, be it
+    /// Follower, Candidate or Leader. A Candidate that starts a new election
+    /// or a Follower that moves to a new term keeps its role. This makes the
+    /// stability of leadership observable.
+    pub fn time_in_role(&self) -> Duration {
+        self.clock
+            .now()
+            .saturating_duration_since(self.role_changed_at)
+    }
+
+    /// Returns the committed log entries that were appended in the given term,
+    /// including the no-op entry of the term's Leader. This is useful to audit
+    /// what a particular Leader committed. Entries compacted into a snapshot
+    /// are not returned, and TermEntries::truncated tells whether some of the
+    /// term's entries may have been compacted.
+    pub fn entries_in_term(&self, term: usize) -> TermEntries<T> {
+        // The first entry of the log only stands for the compacted ones.
+        let truncated = self.index_offset > 0 && self.log[0].term >= term;
+        let entries = self.log[1..=self.log_position(self.commit_index)]
+            .iter()
+            .filter(|entry| entry.term == term)
+            .cloned()
+            .collect();
+        TermEntries { entries, truncated }
+    }
+
+    /// Returns the index of the first committed log entry appended in the
+    /// given term, or None if no entry of that term is committed yet. Systems
+    /// that layer leases or epochs on top of Raft can use it as a fence.
+    /// Returns None as well if the first entry of the term was compacted into
+    /// a snapshot, since its index is no longer known.
+    pub fn first_committed_index_in_term(&self, term: usize) -> Option<usize> {
+        // The first entry of the log only stands for the compacted ones, and
+        // the term may have started among them.
+        if self.log[0].term == term {
+            return None;
+        }
+        self.log[1..=self.log_position(self.commit_index)]
+            .iter()
+            .find(|entry| entry.term == term)
+            .map(|entry| entry.index)
+    }
+
+    /// Returns the committed log entries from from_index onwards as records
+    /// for systems outside of the cluster, with each transition serialized by
+    /// encode. To resume a stream, pass the index following the last record
+    /// received. If the entries at from_index were compacted into a snapshot,
+    /// LogStreamError::Compacted tells where to resume once the snapshot is
+    /// restored.
+    pub fn log_stream<F>(
+        &self,
+        from_index: usize,
+        encode: F,
+    ) -> Result<LogStream<'_, T, F>, LogStreamError>
+    where
+        F: Fn(&T) -> Vec<u8>,
+    {
+        // The first entry of the log only stands for the entries before it.
+        if from_index <= self.index_offset && self.snapshot.is_some() {
+            return Err(LogStreamError::Compacted {
+                snapshot_index: self.index_offset,
+            });
+        }
+        let end = self.log_position(self.commit_index) + 1;
+        let start = match self.local_index(from_index) {
+            Some(start) => cmp::min(cmp::max(start, 1), end),
+            None if from_index <= self.index_offset => 1,
+            None => end,
+        };
+        Ok(LogStream::new(&self.log[start..end], encode))
+    }
+
+    /// Returns the latest snapshot of the state machine, which replaces the
+    /// log entries up to its last_included_index, if the log was compacted.
+    pub fn snapshot(&self) -> Option<&Snapshot> {
+        self.snapshot.as_ref()
+    }
+
+    /// Returns the number of transitions in the log that are not committed
+    /// yet. Clients can use it to back off before the Leader is overloaded.
+    pub fn proposal_queue_depth(&self) -> usize {
+        self.uncommitted_transitions().count()
+    }
+
+    /// Returns the total size in bytes of the transitions in the log that are
+    /// not committed yet, as reported by StateMachineTransition::size.
+    pub fn proposal_queue_bytes(&self) -> usize {
+        self.uncommitted_transitions()
+            .map(|transition| transition.size())
+            .sum()
+    }
+
+    fn uncommitted_transitions(&self) -> impl Iterator<Item = &T> {
+        self.log[self.log_position(self.commit_index) + 1..]
+            .iter()
+            .map(|entry| &entry.transition)
+            .filter(move |transition| !self.is_noop(transition))
+    }
+
+    /// Returns how far the Leader replicated its log to each peer, by ID, for
+    /// example to export it as metrics and spot a follower that fell behind
+    /// before it needs a snapshot. Only the Leader tracks this, so the map is
+    /// empty on other Replicas.
+    pub fn replication_status(&self) -> BTreeMap<ReplicaID, PeerProgress> {
+        if self.state != State::Leader {
+            return BTreeMap::new();
+        }
+        self.peers
+            .iter()
+            .map(|(peer_id, peer)| {
+                let progress = PeerProgress {
+                    next_index: peer.next_index,
+                    match_index: peer.match_index,
+                    lag: self.commit_index.saturating_sub(peer.match_index),
+                };
+                (*peer_id, progress)
+            })
+            .collect()
+    }
+
+    /// Returns the number of bytes the Replica uses to track the replication
+    /// state of its peers. It grows linearly with the size of the cluster,
+    /// which matters in clusters with hundreds of Replicas.
+    pub fn peer_state_bytes(&self) -> usize {
+        self.peers.len() * std::mem::size_of::<(ReplicaID, PeerState)>()
+    }
+
+    /// Returns how many times the log was compacted because it reached the
+    /// limit set with set_hard_log_memory_limit. A count that keeps growing
+    /// means that the snapshot delta is too large for the memory available.
+    pub fn emergency_compactions(&self) -> usize {
+        self.emergency_compactions
+    }
+
+    /// Returns how many VoteRequests this Replica has rejected for the given
+    /// reason. Repeatedly failing elections can be diagnosed by looking at
+    /// which reason keeps growing across the cluster.
+    pub fn vote_rejections(&self, reason: RejectReason) -> usize {
+        self.vote_rejections.get(&reason).copied().unwrap_or(0)
+    }
+
+    /// This function starts the Replica and blocks until it stops.
+    ///
+    /// recv_msg is a channel on which the user must notify the Replica whenever
+    /// new messages from the Cluster are available. The Replica will not poll
+    /// for messages from the Cluster unless notified through recv_msg.
+    ///
+    /// recv_transition is a channel on which the user must notify the Replica
+    /// whenever new transitions to be processed for the StateMachine are
+    /// available. The Replica will not poll for pending transitions for the
+    /// StateMachine unless notified through recv_transition.
+    ///
+    /// start returns Ok once the Cluster halts the Replica or once
+    /// ReplicaHandle::shutdown is called. It returns an error if the Replica
+    /// cannot go on, in which case the caller may create a new Replica rather
+    /// than bring the whole process down.
+    pub fn start(
+        &mut self,
+        recv_msg: Receiver<()>,
+        recv_transition: Receiver<()>,
+    ) -> Result<(), RaftError> {
+        let recv_shutdown = self.handle.wake_receiver();
+        loop {
+            if self.cluster.is_poisoned() || self.state_machine.is_poisoned() {
+                return Err(RaftError::MutexPoisoned);
+            }
+
+            if self.cluster.lock().unwrap().halt() {
+                self.drain_committed_entries();
+                return Ok(());
+            }
+
+            if self.handle.take_shutdown_request() {
+                self.drain_committed_entries();
+                if self.snapshot_on_shutdown && self.last_applied > self.index_offset {
+                    self.compact_log();
+                }
+                return Ok(());
+            }
+
+            if let Some(error) = &self.fatal_error {
+                return Err(error.clone());
+            }
+
+            match self.state {
+                State::Leader => {
+                    self.poll_as_leader(&recv_msg, &recv_transition, &recv_shutdown)?
+                }
+                State::Follower => self.poll_as_follower(&recv_msg, &recv_shutdown)?,
+                State::Candidate => self.poll_as_candidate(&recv_msg, &recv_shutdown)?,
+            }
+
+            self.apply_ready_entries();
+            self.publish_metrics();
+        }
+    }
+
+    /// Process a single message and return the messages to send in response,
+    /// instead of sending them through the Cluster. Together with
+    /// handle_timeout, this lets users drive the Replica from their own event
+    /// loop rather than calling start. The message is processed as is: it is
+    /// up to the caller to verify its sender.
+    pub fn handle_message(&mut self, message: Message<T>) -> Vec<(ReplicaID, Message<T>)> {
+        self.outbox = Some(Vec::new());
+        if self.state != State::Leader {
+            self.update_election_deadline();
+        }
+        self.process_message(message);
+        self.apply_ready_entries();
+        self.publish_metrics();
+        self.outbox.take().unwrap_or_default()
+    }
+
+    /// Let the Replica act on the time being now and return the messages to
+    /// send as a result. A Follower or a Candidate whose election deadline has
+    /// passed starts an election, and a Leader that has not broadcast for a
+    /// heartbeat timeout sends heartbeats. Call it at least once per
+    /// heartbeat_timeout when driving the Replica with handle_message.
+    pub fn handle_timeout(&mut self, now: Instant) -> Vec<(ReplicaID, Message<T>)> {
+        self.outbox = Some(Vec::new());
+        match self.state {
+            State::Leader => {
+                if self.state_machine.lock().unwrap().should_step_down() {
+                    self.step_down();
+                } else {
+                    if now >= self.last_broadcast + self.heartbeat_timer.get_timeout() {
+                        if self.transitions_deferred && self.leadership_confirmed {
+                            self.load_new_transitions();
+                        }
+                        self.broadcast_append_entry_request();
+                    }
+                    self.continue_leadership_transfer(now);
+                    self.check_quorum(now);
+                }
+            }
+            State::Follower | State::Candidate => {
+                if now >= self.next_election_deadline {
+                    self.process_election_timeout();
+                }
+            }
+        }
+        self.apply_ready_entries();
+        self.publish_metrics();
+        self.outbox.take().unwrap_or_default()
+    }
+
+    fn poll_as_leader(
+        &mut self,
+        recv_msg: &Receiver<()>,
+        recv_transition: &Receiver<()>,
+        recv_shutdown: &Receiver<()>,
+    ) -> Result<(), RaftError> {
+        let recv_batch = match self.batch {
+            Some((deadline, _)) => self.clock.at(deadline),
+            None => crossbeam_channel::never(),
+        };
+        let recv_proposal = self.proposals.1.clone();
+        let mut select = Select::new();
+        let recv_heartbeat = self.heartbeat_timer.get_rx();
+        let (msg, transition, heartbeat, shutdown, batch, proposal) = (
+            select.recv(recv_msg),
+            select.recv(recv_transition),
+            select.recv(recv_heartbeat),
+            select.recv(recv_shutdown),
+            select.recv(&recv_batch),
+            select.recv(&recv_proposal),
+        );
+
+        let oper = select.select();
+        match oper.index() {
+            // Return to start, which stops the Replica.
+            i if i == shutdown => {
+                let _ = oper.recv(recv_shutdown);
+                return Ok(());
+            }
+            // Process pending messages.
+            i if i == msg => {
+                oper.recv(recv_msg)
+                    .map_err(|_| RaftError::ChannelDisconnected)?;
+                let mut messages = self.receive_messages();
+                while let Some(message) = self.next_message(&mut messages) {
+                    self.process_message(message);
+                }
+            }
+            // Process pending transitions, or the proposals that come with
+            // the first one received.
+            i if i == transition || i == proposal => {
+                if i == transition {
+                    oper.recv(recv_transition)
+                        .map_err(|_| RaftError::ChannelDisconnected)?;
+                } else if let Ok(proposal) = oper.recv(&recv_proposal) {
+                    self.received_proposals.push(proposal);
+                }
+                // Until the Leader is confirmed, the transitions wait in the
+                // state machine.
+                if self.leadership_confirmed {
+                    let last_log_index = self.last_log_index();
+                    self.load_new_transitions();
+                    if !self.hold_back(self.last_log_index() - last_log_index) {
+                        self.broadcast_append_entry_request();
+                    }
+                }
+            }
+            // Broadcast the transitions held back for group commit.
+            i if i == batch => {
+                let _ = oper.recv(&recv_batch);
+                self.broadcast_append_entry_request();
+            }
+            // Broadcast heartbeat messages, unless the peers have just heard
+            // from the Leader anyway.
+            i if i == heartbeat => {
+                // If the clock dropped the timer, the channel is disconnected
+                // instead of fired. Renewing the timer replaces the dead
+                // channel either way, so the Leader never spins on it.
+                let _ = oper.recv(recv_heartbeat);
+                if self.transitions_deferred && self.leadership_confirmed {
+                    self.load_new_transitions();
+                }
+                if self
+                    .clock
+                    .now()
+                    .saturating_duration_since(self.last_broadcast)
+                    >= self.heartbeat_coalescing_window
+                {
+                    self.broadcast_append_entry_request();
+                }
+                self.heartbeat_timer.renew();
+                self.continue_leadership_transfer(self.clock.now());
+                self.check_quorum(self.clock.now());
+            }
+            _ => unreachable!(),
+        }
+
+        if self.state == State::Leader && self.state_machine.lock().unwrap().should_step_down() {
+            self.step_down();
+        }
+        Ok(())
+    }
+
+    // Step down if a majority of the cluster did not acknowledge the Leader
+    // within the last minimum election timeout: the Leader is likely cut off
+    // from it, and another one may have been elected since.
+    fn check_quorum(&mut self, now: Instant) {
+        if !self.check_quorum
+            || self.state != State::Leader
+            || now < self.role_changed_at + self.election_timeout.0
+        {
+            return;
+        }
+        let last_quorum_ack = self.quorum_value(|id| match id == self.id {
+            true => Some(now),
+            false => self.peers[&id].last_ack,
+        });
+        if !matches!(last_quorum_ack,
+            Some(ack) if now.saturating_duration_since(ack) < self.election_timeout.0)
+        {
+            self.register_leader(None);
+            self.become_follower(self.current_term);
+            self.update_election_deadline();
+        }
+    }
+
+    // Hand leadership to the peer with the most of the log, which is the
+    // likeliest to win an election right away, and become a follower.
+    fn step_down(&mut self) {
+        let successor_id = self
+            .peers
+            .iter()
+            .max_by_key(|(_, peer)| peer.match_index)
+            .map(|(peer_id, _)| *peer_id);
+        // Bring the peers as close to the end of the log as possible first.
+        self.broadcast_append_entry_request();
+        match successor_id {
+            Some(successor_id) => self.hand_over_leadership(successor_id),
+            None => {
+                self.register_leader(None);
+                self.become_follower(self.current_term);
+                self.update_election_deadline();
+            }
+        }
+    }
+
+    // Tell the successor to start an election right away, and become a
+    // follower.
+    fn hand_over_leadership(&mut self, successor_id: ReplicaID) {
+        self.send_message(
+            successor_id,
+            Message::TimeoutNow {
+                from_id: self.id,
+                term: self.current_term,
+            },
+        );
+        self.register_leader(None);
+        self.become_follower(self.current_term);
+        self.update_election_deadline();
+    }
+
+    // Hand leadership over to the target of the transfer in progress once it
+    // holds the whole log, or give the transfer up once it timed out.
+    fn continue_leadership_transfer(&mut self, now: Instant) {
+        if let Some((target, deadline)) = self.leadership_transfer {
+            match self.peers.get(&target) {
+                Some(peer) if peer.match_index == self.last_log_index() => {
+                    self.hand_over_leadership(target)
+                }
+                Some(_) if now < deadline => {}
+                _ => self.leadership_transfer = None,
+            }
+        }
+    }
+
+    // Whether to hold back the broadcast of the given number of new entries,
+    // along with the ones held back already, for group commit.
+    fn hold_back(&mut self, appended: usize) -> bool {
+        if self.max_batch_delay == Duration::from_secs(0) {
+            return false;
+        }
+        let now = self.clock.now();
+        let (deadline, size) = self.batch.get_or_insert((now + self.max_batch_delay, 0));
+        *size += appended;
+        now < *deadline && (self.max_batch_size == 0 || *size < self.max_batch_size)
+    }
+
+    fn broadcast_append_entry_request(&mut self) {
+        self.last_broadcast = self.clock.now();
+        self.batch = None;
+        let mut peer_ids = Vec::new();
+        for peer_id in self.peer_ids.clone() {
+            if self.peers[&peer_id].next_index <= self.index_offset {
+                self.send_append_entry_request(peer_id, true);
+                continue;
+            }
+            match self.max_inflight_appends {
+                Some(window) => {
+                    // The window is full or the peer has every entry, so only
+                    // let it know that the Leader is alive.
+                    if !self.pipeline_entries(peer_id, window) {
+                        peer_ids.push(peer_id);
+                    }
+                }
+                None => peer_ids.push(peer_id),
+            }
+        }
+        let with_entries = self.max_inflight_appends.is_none();
+        self.broadcast_message(&peer_ids, |replica, peer_id| {
+            replica.append_entry_request(peer_id, with_entries)
+        });
+    }
+
+    // Send the peer the entries it is missing, if with_entries is set, or a
+    // heartbeat otherwise. Peers that need entries compacted away get the
+    // snapshot instead.
+    fn send_append_entry_request(&mut self, peer_id: ReplicaID, with_entries: bool) {
+        if self.peers[&peer_id].next_index <= self.index_offset {
+            for message in self.install_snapshot_requests() {
+                self.send_message(peer_id, message);
+            }
+            return;
+        }
+        let message = self.append_entry_request(peer_id, with_entries);
+        self.send_message(peer_id, message);
+    }
+
+    // Send the peer the entries it is missing, in batches, as long as fewer
+    // than window batches await its acknowledgement. next_index moves past
+    // each batch as if the peer had accepted it already. Returns whether any
+    // batch was sent.
+    fn pipeline_entries(&mut self, peer_id: ReplicaID, window: usize) -> bool {
+        let mut sent = false;
+        loop {
+            let peer = self.peers[&peer_id];
+            if peer.inflight >= window
+                || peer.next_index > self.last_log_index()
+                || peer.next_index <= self.index_offset
+            {
+                return sent;
+            }
+            let message = self.append_entry_request(peer_id, true);
+            if let Message::AppendEntryRequest {
+                prev_log_index,
+                entries,
+                ..
+            } = &message
+            {
+                let peer = self.peers.get_mut(&peer_id).unwrap();
+                peer.next_index = prev_log_index + entries.len() + 1;
+                peer.inflight += 1;
+            }
+            self.send_message(peer_id, message);
+            sent = true;
+        }
+    }
+
+    // AppendEntryRequest for a peer that does not need entries compacted away,
+    // with the entries it is missing if with_entries is set, or as a heartbeat
+    // otherwise.
+    fn append_entry_request(&self, peer_id: ReplicaID, with_entries: bool) -> Message<T> {
+        let next_index = self.peers[&peer_id].next_index;
+        Message::AppendEntryRequest {
+            term: self.current_term,
+            from_id: self.id,
+            prev_log_index: next_index - 1,
+            prev_log_term: self.entry(next_index - 1).term,
+            entries: match with_entries {
+                true => self.get_entries_for_peer(peer_id),
+                false => Vec::new(),
+            },
+            commit_index: self.commit_index,
+            timestamp: Some(SystemTime::now()),
+        }
+    }
+
+    // Chunks of the snapshot, in order.
+    fn install_snapshot_requests(&self) -> Vec<Message<T>> {
+        let snapshot = self
+            .snapshot
+            .as_ref()
+            .expect("a compacted log must have a snapshot");
+        let chunk_size = match self.max_snapshot_chunk_size {
+            Some(max_chunk_size) => max_chunk_size,
+            None => cmp::max(snapshot.data.len(), 1),
+        };
+        let content_hash = if self.verify_content_hashes {
+            self.content_hashes.first().copied()
+        } else {
+            None
+        };
+        // An empty snapshot still takes one chunk.
+        let offsets: Vec<usize> = match snapshot.data.len() {
+            0 => vec![0],
+            len => (0..len).step_by(chunk_size).collect(),
+        };
+        offsets
+            .into_iter()
+            .map(|offset| {
+                let end = cmp::min(offset + chunk_size, snapshot.data.len());
+                Message::InstallSnapshotRequest {
+                    from_id: self.id,
+                    term: self.current_term,
+                    last_included_index: snapshot.last_included_index,
+                    last_included_term: snapshot.last_included_term,
+                    data: snapshot.data[offset..end].to_vec(),
+                    offset,
+                    done: end == snapshot.data.len(),
+                    content_hash,
+                    applied_sequences: snapshot.applied_sequences.clone(),
+                }
+            })
+            .collect()
+    }
+
+    fn poll_as_follower(
+        &mut self,
+        recv_msg: &Receiver<()>,
+        recv_shutdown: &Receiver<()>,
+    ) -> Result<(), RaftError> {
+        let recv_election_timeout = self.clock.at(self.next_election_deadline);
+        let mut select = Select::new();
+        select.recv(recv_msg);
+        let shutdown = select.recv(recv_shutdown);
+        let election_timeout = select.recv(&recv_election_timeout);
+        let oper = select.select();
+        match oper.index() {
+            // Return to start, which stops the Replica.
+            i if i == shutdown => {
+                let _ = oper.recv(recv_shutdown);
+                return Ok(());
+            }
+            // Become candidate and update elction deadline.
+            i if i == election_timeout => {
+                let _ = oper.recv(&recv_election_timeout);
+                self.process_election_timeout();
+            }
+            // Process pending messages.
+            _ => {
+                oper.recv(recv_msg)
+                    .map_err(|_| RaftError::ChannelDisconnected)?;
+                let mut messages = self.receive_messages();
+                // Update the election deadline if more than zero messages were
+                // actually received.
+                let mut received = false;
+                while let Some(message) = self.next_message(&mut messages) {
+                    if !received {
+                        self.update_election_deadline();
+                        received = true;
+                    }
+                    self.process_message(message);
+                }
+            }
+        }
+
+        // Load new transitions. The follower will ignore these transitions, but
+        // they are still polled for periodically to ensure there are no stale
+        // transitions in case the Replica's state changes.
+        self.load_new_transitions();
+        Ok(())
+    }
+
+    // Receive pending messages. They are pulled from the Cluster as they are
+    // processed, unless the Leader's messages are processed first, which takes
+    // them all at once to sort them.
+    fn receive_messages(&self) -> Box<dyn Iterator<Item = Message<T>>> {
+        let messages = self.cluster.lock().unwrap().drain_messages();
+        if !self.prioritize_append_entries {
+            return messages;
+        }
+
+        // Process the Leader's messages before elections. The sort is stable,
+        // so messages of the same kind keep their order.
+        let mut messages: Vec<Message<T>> = messages.collect();
+        messages.sort_by_key(|message| match message {
+            Message::AppendEntryRequest { .. }
+            | Message::AppendEntryResponse { .. }
+            | Message::InstallSnapshotRequest { .. }
+            | Message::InstallSnapshotResponse { .. } => 0,
+            Message::VoteRequest { .. }
+            | Message::VoteResponse { .. }
+            | Message::PreVoteRequest { .. }
+            | Message::PreVoteResponse { .. }
+            | Message::TimeoutNow { .. } => 1,
+        });
+        Box::new(messages.into_iter())
+    }
+
+    // Next received message, dropping the ones whose sender the Cluster could
+    // not verify.
+    fn next_message(
+        &self,
+        messages: &mut Box<dyn Iterator<Item = Message<T>>>,
+    ) -> Option<Message<T>> {
+        messages.find(|message| {
+            #[cfg(feature = "testing")]
+            if self.partition.contains(&message.from_id()) {
+                return false;
+            }
+            self.cluster.lock().unwrap().verify_peer(message.from_id())
+        })
+    }
+
+    fn process_message(&mut self, message: Message<T>) {
+        match self.state {
+            State::Leader => self.process_message_as_leader(message),
+            State::Candidate => self.process_message_as_candidate(message),
+            State::Follower => self.process_message_as_follower(message),
+        }
+    }
+
+    fn update_election_deadline(&mut self) {
+        // Randomize each election deadline within the allowed range.
+        self.last_election_timeout = self.pick_timeout(self.election_timeout);
+        self.next_election_deadline = self.clock.now() + self.last_election_timeout;
+    }
+
+    // Pick a timeout within the given range, at random unless the Replica is
+    // deterministic. A deterministic Replica picks its ID's rank among the
+    // cluster's IDs so that no two Replicas pick the same timeout.
+    fn pick_timeout(&mut self, (min, max): (Duration, Duration)) -> Duration {
+        if self.deterministic {
+            let rank = self.peer_ids.iter().filter(|id| **id < self.id).count() as u32;
+            let cluster_size = self.peer_ids.len() as u32 + 1;
+            min + (max - min) * rank / cluster_size
+        } else {
+            self.rng.gen_range(min..=max)
+        }
+    }
+
+    fn process_election_timeout(&mut self) {
+        match self.state {
+            State::Follower if !self.member => self.update_election_deadline(),
+            // A Replica installing a snapshot does not campaign, since a
+            // Leader is sending it the snapshot. If the Leader went silent for
+            // a whole election timeout, the transfer is abandoned and the
+            // Replica campaigns once the next one elapses.
+            State::Follower if self.snapshot_buffer.is_some() => {
+                self.snapshot_buffer = None;
+                self.update_election_deadline();
+            }
+            State::Follower => self.start_election(),
+            State::Candidate => {
+                let split_vote = self.competing_candidacy_term.take() == Some(self.current_term);
+                match self.candidacy_backoff {
+                    // Another Candidate is likely to time out at the same time,
+                    // so back off before soliciting votes again.
+                    Some((min, max)) if split_vote => {
+                        self.next_election_deadline =
+                            self.clock.now() + self.pick_timeout((min, max));
+                    }
+                    _ => self.start_election(),
+                }
+            }
+            State::Leader => {}
+        }
+    }
+
+    fn start_election(&mut self) {
+        // Bound the rate of VoteRequests sent to the peers, however quickly
+        // elections time out.
+        if let Some(last_vote_request) = self.last_vote_request {
+            let earliest_vote_request = last_vote_request + self.min_vote_request_interval;
+            if self.clock.now() < earliest_vote_request {
+                self.next_election_deadline = earliest_vote_request;
+                return;
+            }
+        }
+
+        if self.pre_vote && !self.peer_ids.is_empty() {
+            self.start_pre_vote();
+        } else {
+            self.become_candidate();
+        }
+        self.update_election_deadline();
+    }
+
+    fn start_pre_vote(&mut self) {
+        let mut pre_votes = BTreeSet::new();
+        pre_votes.insert(self.id);
+        self.pre_votes = Some(pre_votes);
+        self.last_vote_request = Some(self.clock.now());
+        let peer_ids = self.peer_ids.clone();
+        self.broadcast_message(&peer_ids, |replica, _| Message::PreVoteRequest {
+            from_id: replica.id,
+            term: replica.current_term + 1,
+            last_log_index: replica.last_log_index(),
+            last_log_term: replica.last_log_term(),
+        });
+    }
+
+    // Grant a pre-vote if the sender could win the election it asks about.
+    // Unlike for votes, nothing is recorded.
+    fn process_pre_vote_request(
+        &mut self,
+        from_id: ReplicaID,
+        term: usize,
+        last_log_index: usize,
+        last_log_term: usize,
+    ) {
+        // A Replica that heard from the Leader lately has no reason to replace
+        // it.
+        let leader_alive = self.state == State::Leader
+            || matches!(self.last_leader_contact,
+                Some(contact) if self.clock.now().saturating_duration_since(contact) < self.election_timeout.0);
+        let vote_granted = term > self.current_term
+            && !leader_alive
+            && self.log_up_to_date(last_log_index, last_log_term);
+        self.send_message(
+            from_id,
+            Message::PreVoteResponse {
+                from_id: self.id,
+                term: if vote_granted {
+                    term
+                } else {
+                    self.current_term
+                },
+                vote_granted,
+            },
+        );
+    }
+
+    fn process_pre_vote_response(&mut self, from_id: ReplicaID, term: usize, vote_granted: bool) {
+        if !vote_granted || term != self.current_term + 1 {
+            return;
+        }
+        if !self.peers.contains_key(&from_id) {
+            return;
+        }
+        if let Some(mut pre_votes) = self.pre_votes.take() {
+            pre_votes.insert(from_id);
+            // If a majority would vote for the Replica, the actual election
+            // can start.
+            let won = self.is_quorum(&pre_votes);
+            self.pre_votes = Some(pre_votes);
+            if won {
+                self.become_candidate();
+                self.update_election_deadline();
+            }
+        }
+    }
+
+    // Whether a Candidate whose log ends at last_log_index in last_log_term
+    // is at least as up to date as this Replica. Logs are compared by the term
+    // of their last entry first, and by their length only if those are equal.
+    fn log_up_to_date(&self, last_log_index: usize, last_log_term: usize) -> bool {
+        (last_log_term, last_log_index) >= (self.last_log_term(), self.last_log_index())
+    }
+
+    fn poll_as_candidate(
+        &mut self,
+        recv_msg: &Receiver<()>,
+        recv_shutdown: &Receiver<()>,
+    ) -> Result<(), RaftError> {
+        let recv_election_timeout = self.clock.at(self.next_election_deadline);
+        let mut select = Select::new();
+        select.recv(recv_msg);
+        let shutdown = select.recv(recv_shutdown);
+        let election_timeout = select.recv(&recv_election_timeout);
+        let oper = select.select();
+        match oper.index() {
+            // Return to start, which stops the Replica.
+            i if i == shutdown => {
+                let _ = oper.recv(recv_shutdown);
+                return Ok(());
+            }
+            // Become candidate and update elction deadline.
+            i if i == election_timeout => {
+                let _ = oper.recv(&recv_election_timeout);
+                self.process_election_timeout();
+            }
+            _ => {
+                // Process pending messages.
+                oper.recv(recv_msg)
+                    .map_err(|_| RaftError::ChannelDisconnected)?;
+                let mut messages = self.receive_messages();
+                // Update the election deadline if more than zero messages were
+                // actually received.
+                let mut received = false;
+                while let Some(message) = self.next_message(&mut messages) {
+                    if !received {
+                        self.update_election_deadline();
+                        received = true;
+                    }
+                    self.process_message(message);
+                }
+            }
+        }
+
+        // Load new transitions. The candidate will ignore these transitions,
+        // but they are still polled for periodically to ensure there are no
+        // stale transitions in case the Replica's state changes.
+        self.load_new_transitions();
+        Ok(())
+    }
+
+    // Send a message to each of peer_ids, through a single Cluster::broadcast
+    // unless the messages go to the caller of handle_message or
+    // handle_timeout. The messages are generated before the Cluster is
+    // locked, since generating them may need it.
+    fn broadcast_message<F>(&mut self, peer_ids: &[ReplicaID], message_generator: F)
+    where
+        F: Fn(&Self, ReplicaID) -> Message<T>,
+    {
+        let mut messages: BTreeMap<ReplicaID, Message<T>> = peer_ids
+            .iter()
+            .map(|peer_id| (*peer_id, message_generator(self, *peer_id)))
+            .collect();
+        if self.outbox.is_some() {
+            for peer_id in peer_ids {
+                let message = messages.remove(peer_id).unwrap();
+                self.send_message(*peer_id, message);
+            }
+            return;
+        }
+
+        #[allow(unused_mut)]
+        let mut peer_ids = peer_ids.to_vec();
+        #[cfg(feature = "testing")]
+        peer_ids.retain(|peer_id| !self.partition.contains(peer_id));
+        for peer_id in &peer_ids {
+            self.record_sent_request(*peer_id, &messages[peer_id]);
+        }
+        let messages = RefCell::new(messages);
+        self.cluster
+            .lock()
+            .unwrap()
+            .broadcast(&peer_ids, |peer_id| {
+                messages
+                    .borrow_mut()
+                    .remove(&peer_id)
+                    .expect("a message is generated for each peer")
+            });
+    }
+
+    // Send a message through the Cluster, or hand it to the caller of
+    // handle_message or handle_timeout.
+    fn send_message(&mut self, to_id: ReplicaID, message: Message<T>) {
+        #[cfg(feature = "testing")]
+        if self.partition.contains(&to_id) {
+            return;
+        }
+        self.record_sent_request(to_id, &message);
+
+        match &mut self.outbox {
+            Some(outbox) => outbox.push((to_id, message)),
+            None => {
+                let priority = message.priority();
+                self.cluster
+                    .lock()
+                    .unwrap()
+                    .send_message_with_priority(to_id, message, priority)
+            }
+        }
+    }
+
+    // Remember when an AppendEntryRequest was sent, for as long as it may
+    // extend the Leader's lease.
+    fn record_sent_request(&mut self, to_id: ReplicaID, message: &Message<T>) {
+        if let Message::AppendEntryRequest {
+            timestamp: Some(timestamp),
+            ..
+        } = message
+        {
+            let now = self.clock.now();
+            while matches!(self.sent_requests.front(),
+                Some((_, _, sent_at)) if now.saturating_duration_since(*sent_at) >= self.election_timeout.0)
+            {
+                self.sent_requests.pop_front();
+            }
+            self.sent_requests.push_back((to_id, *timestamp, now));
+        }
+    }
+
+    // Get log entries that have not been acknowledged by the peer, in the
+    // format the peer understands, up to max_entries_per_append of them.
+    fn get_entries_for_peer(&self, peer_id: ReplicaID) -> Vec<LogEntry<T>> {
+        let start = self.log_position(self.peers[&peer_id].next_index - 1) + 1;
+        let end = match self.max_entries_per_append {
+            Some(max_entries) => cmp::min(start + max_entries, self.log.len()),
+            None => self.log.len(),
+        };
+        let entries = &self.log[start..end];
+        match self.cluster.lock().unwrap().peer_format(peer_id) {
+            Some(format) => entries
+                .iter()
+                .map(|entry| LogEntry {
+                    transition: entry.transition.for_format(format),
+                    index: entry.index,
+                    term: entry.term,
+                    config: entry.config.clone(),
+                })
+                .collect(),
+            None => entries.to_vec(),
+        }
+    }
+
+    // Index of the last entry of the log.
+    fn last_log_index(&self) -> usize {
+        self.index_offset + self.log.len() - 1
+    }
+
+    // Term of the last entry of the log.
+    fn last_log_term(&self) -> usize {
+        self.entry(self.last_log_index()).term
+    }
+
+    // Position in the log of the entry at the given absolute index, or None if
+    // the entry was compacted into the snapshot or is past the end of the log.
+    // Indexes received from other Replicas must go through it before they are
+    // used to access the log.
+    fn local_index(&self, absolute: usize) -> Option<usize> {
+        absolute
+            .checked_sub(self.index_offset)
+            .filter(|local| *local < self.log.len())
+    }
+
+    // Position in the log of the entry at the given index, which must be in
+    // the log.
+    fn log_position(&self, index: usize) -> usize {
+        match self.local_index(index) {
+            Some(local) => local,
+            None => panic!(
+                "entry {} is outside of the log, which holds {} to {}",
+                index,
+                self.index_offset,
+                self.last_log_index()
+            ),
+        }
+    }
+
+    // Entry at the given index, which must be in the log.
+    fn entry(&self, index: usize) -> &LogEntry<T> {
+        &self.log[self.log_position(index)]
+    }
+
+    // Apply entries that are ready to be applied.
+    fn apply_ready_entries(&mut self) {
+        // Move the commit index to the latest log index that has been
+        // replicated on the majority of the replicas.
+        if self.state == State::Leader && self.commit_index < self.last_log_index() {
+            let old_commit_index = self.commit_index;
+            let last_log_index = self.last_log_index();
+            let n = cmp::min(
+                self.quorum_value(|id| match id == self.id {
+                    true => last_log_index,
+                    false => self.peers[&id].match_index,
+                }),
+                last_log_index,
+            );
+
+            // Only entries of the current term are committed by counting
+            // replicas. Terms never decrease along the log, so if the entry at
+            // n is from an earlier term, so are all entries below it.
+            if n > self.commit_index && self.entry(n).term == self.current_term {
+                self.commit_index = n;
+            }
+
+            for i in old_commit_index + 1..=self.commit_index {
+                if self.is_noop(&self.entry(i).transition) {
+                    continue;
+                }
+
+                if let Some(timeline) = self.in_flight_timelines.get_mut(&i) {
+                    timeline.committed = Some(self.clock.now());
+                }
+
+                let mut state_machine = self.state_machine.lock().unwrap();
+                state_machine.register_transition_state(
+                    self.entry(i).transition.get_id(),
+                    TransitionState::Committed,
+                );
+            }
+        }
+
+        // Entries that made it to the new Leader's log are no longer doomed.
+        self.doomed_entries = self.doomed_entries.split_off(&(self.commit_index + 1));
+
+        // Proposals still waiting on a committed entry were not overwritten,
+        // so the entry is theirs.
+        let uncommitted = self.proposal_waiters.split_off(&(self.commit_index + 1));
+        for (_, reply) in std::mem::replace(&mut self.proposal_waiters, uncommitted) {
+            let _ = reply.send(Ok(()));
+        }
+
+        // Apply entries that are behind the currently committed index, unless
+        // applying is paused.
+        if !self.handle.apply_paused() {
+            self.apply_committed_entries();
+        }
+
+        // Once the joint configuration is committed, the Leader completes
+        // the change by moving on to the new members alone.
+        if let Some((_, new_members)) = &self.joint_members {
+            if self.state == State::Leader {
+                let members = new_members.clone();
+                self.propose_configuration(Configuration {
+                    members,
+                    old_members: None,
+                });
+            }
+        }
+
+        if let Some(snapshot_delta) = self.snapshot_delta {
+            if self.log_position(self.last_applied) >= snapshot_delta {
+                self.compact_log();
+            }
+        }
+
+        if let Some(max_snapshot_age) = self.max_snapshot_age {
+            let now = self.clock.now();
+            let taken_at = *self.snapshot_taken_at.get_or_insert(now);
+            if now.saturating_duration_since(taken_at) >= max_snapshot_age
+                && self.last_applied > self.index_offset
+            {
+                self.compact_log();
+            }
+        }
+
+        // Reclaim the memory of the applied entries as soon as the log is too
+        // large, however few they are.
+        if self.log_over_memory_limit() && self.last_applied > self.index_offset {
+            let index_offset = self.index_offset;
+            self.compact_log();
+            if self.index_offset > index_offset {
+                self.emergency_compactions += 1;
+            }
+        }
+
+        self.handle.update(self.commit_index, self.last_applied);
+    }
+
+    // IDs of the members of the cluster, this Replica included if it is one.
+    // In a joint configuration, the members of either configuration.
+    fn members(&self) -> Vec<ReplicaID> {
+        let mut members = self.peer_ids.clone();
+        if self.member {
+            members.push(self.id);
+        }
+        members.sort_unstable();
+        members
+    }
+
+    // Append a configuration entry, unless the Replica cannot change the
+    // membership right now.
+    fn propose_configuration(&mut self, config: Configuration) {
+        // Until the previous change is applied, a majority of the old
+        // membership and one of the new one need not overlap. A joint
+        // configuration may only be followed by its new members.
+        let change_pending = (self.last_applied + 1..=self.last_log_index())
+            .any(|index| self.entry(index).config.is_some());
+        let joint_pending = match &self.joint_members {
+            Some((_, new_members)) => {
+                config.old_members.is_some() || &config.members != new_members
+            }
+            None => false,
+        };
+        if self.state != State::Leader
+            || self.leadership_transfer.is_some()
+            || change_pending
+            || joint_pending
+        {
+            return;
+        }
+
+        // Completing a joint configuration only carries out a change that
+        // was checked when it started.
+        if self.joint_members.is_none() && !self.check_fault_tolerance(&config.members) {
+            return;
+        }
+
+        self.log.push(LogEntry {
+            index: self.last_log_index() + 1,
+            transition: self.noop_transition.clone(),
+            term: self.current_term,
+            config: Some(config),
+        });
+        self.state_machine
+            .lock()
+            .unwrap()
+            .append_log_entries(&self.log[self.log.len() - 1..]);
+        self.broadcast_append_entry_request();
+    }
+
+    // Whether a change to the given members may go on as far as the fault
+    // tolerance of the cluster is concerned, reporting it if it falls below
+    // the floor.
+    fn check_fault_tolerance(&mut self, members: &[ReplicaID]) -> bool {
+        let tolerated_failures = |members: usize| members.saturating_sub(1) / 2;
+        let (floor, check) = self.fault_tolerance_floor;
+        let before = tolerated_failures(self.members().len());
+        let after = tolerated_failures(members.len());
+        if after >= before || after >= floor {
+            return true;
+        }
+        let rejected = check == FaultToleranceCheck::Reject;
+        self.cluster
+            .lock()
+            .unwrap()
+            .register_reduced_fault_tolerance(members, after, rejected);
+        !rejected
+    }
+
+    // Adopt the membership of an applied configuration entry.
+    fn apply_configuration(&mut self, config: &Configuration) {
+        let mut members = config.members.clone();
+        if let Some(old_members) = &config.old_members {
+            members.extend(old_members);
+            members.sort_unstable();
+            members.dedup();
+        }
+        self.joint_members = config
+            .old_members
+            .clone()
+            .map(|old_members| (old_members, config.members.clone()));
+        self.peer_ids = members
+            .iter()
+            .copied()
+            .filter(|peer_id| *peer_id != self.id)
+            .collect();
+        self.member = members.contains(&self.id);
+        self.peers.retain(|peer_id, _| members.contains(peer_id));
+        // A transfer to a Replica that left the cluster cannot complete.
+        if let Some((target, _)) = self.leadership_transfer {
+            if !members.contains(&target) {
+                self.leadership_transfer = None;
+            }
+        }
+        self.leadership_acks
+            .retain(|peer_id| members.contains(peer_id));
+        let next_index = match self.state {
+            State::Leader => self.last_log_index() + 1,
+            _ => 0,
+        };
+        for peer_id in &self.peer_ids {
+            self.peers.entry(*peer_id).or_insert(PeerState {
+                next_index,
+                ..PeerState::default()
+            });
+        }
+
+        if !self.member && self.state == State::Leader {
+            self.register_leader(None);
+            self.become_follower(self.current_term);
+        }
+    }
+
+    // Highest value that a majority of the cluster reached, given the value
+    // that each member reached. In a joint configuration, a majority of both
+    // the old and the new members must have reached it.
+    fn quorum_value<V, F>(&self, value_of: F) -> V
+    where
+        V: Ord,
+        F: Fn(ReplicaID) -> V,
+    {
+        let configurations = match &self.joint_members {
+            Some((old_members, new_members)) => vec![old_members.clone(), new_members.clone()],
+            None => vec![self.members()],
+        };
+        configurations
+            .into_iter()
+            .map(|members| {
+                let mut values: Vec<V> = members.iter().map(|id| value_of(*id)).collect();
+                values.sort_unstable_by(|a, b| b.cmp(a));
+                // The members with the highest values, down to this one, form
+                // a majority.
+                values.swap_remove(members.len() / 2)
+            })
+            .min()
+            .unwrap()
+    }
+
+    // Whether the given Replicas form a majority of the cluster.
+    fn is_quorum(&self, ids: &BTreeSet<ReplicaID>) -> bool {
+        self.quorum_value(|id| ids.contains(&id))
+    }
+
+    // Apply the committed entries that are not applied yet, in order.
+    fn apply_committed_entries(&mut self) {
+        let mut config = None;
+        while self.commit_index > self.last_applied && self.fatal_error.is_none() {
+            let index = self.last_applied + 1;
+            let mut state_machine = self.state_machine.lock().unwrap();
+
+            // Skip transitions that repeat one already applied for their
+            // client.
+            let dedup_key = self.entry(index).transition.dedup_key();
+            let duplicate = matches!(dedup_key, Some((client_id, sequence))
+                if self.applied_sequences.get(&client_id).is_some_and(|applied| sequence <= *applied));
+            if !duplicate {
+                if let Err(error) =
+                    state_machine.apply_and_record(self.entry(index).transition.clone(), index)
+                {
+                    // Every Replica fails on this committed entry, so stop
+                    // here rather than skip it and diverge.
+                    self.fatal_error = Some(RaftError::Apply(error));
+                    break;
+                }
+
+                // The shadow state machine is only evaluated, so its errors
+                // do not affect the Replica.
+                if let Some(shadow_state_machine) = &self.shadow_state_machine {
+                    let _ = shadow_state_machine
+                        .lock()
+                        .unwrap()
+                        .apply_and_record(self.entry(index).transition.clone(), index);
+                }
+            }
+            self.last_applied = index;
+            if let Some((client_id, sequence)) = dedup_key {
+                record_sequence(&mut self.applied_sequences, client_id, sequence);
+            }
+            if let Some(entry_config) = &self.entry(index).config {
+                config = Some(entry_config.clone());
+            }
+
+            if !self.is_noop(&self.entry(index).transition) {
+                state_machine.register_transition_state(
+                    self.entry(index).transition.get_id(),
+                    TransitionState::Applied,
+                );
+            }
+            if let Some(mut timeline) = self.in_flight_timelines.remove(&index) {
+                timeline.applied = Some(self.clock.now());
                 state_machine
-                    .register_transition_state(transition.get_id(), TransitionState::Queued);
-            } else {
-                state_machine
-                    .register_transition_state(
-                        transition.get_id(), TransitionState::Abandoned(TransitionAbandonedReason::NotLeader));
+                    .register_transition_timeline(self.entry(index).transition.get_id(), timeline);
+            }
+        }
+
+        if let Some(config) = config {
+            self.apply_configuration(&config);
+        }
+    }
+
+    // Before shutting down, hand the committed entries that are not applied
+    // yet to the state machine, or apply them if it does not take them, so
+    // that a replacement process does not have to fetch them from the
+    // cluster again.
+    fn drain_committed_entries(&mut self) {
+        if self.commit_index <= self.last_applied || self.fatal_error.is_some() {
+            return;
+        }
+        let entries = &self.log
+            [self.log_position(self.last_applied) + 1..=self.log_position(self.commit_index)];
+        if !self
+            .state_machine
+            .lock()
+            .unwrap()
+            .drain_committed_entries(entries)
+        {
+            self.apply_committed_entries();
+            self.handle.update(self.commit_index, self.last_applied);
+        }
+    }
+
+    // Replace the applied entries of the log with a snapshot of the state
+    // machine. The last applied entry stays as the first entry of the log so
+    // that its term remains known.
+    fn compact_log(&mut self) {
+        let data = match self.state_machine.lock().unwrap().create_snapshot() {
+            Some(data) => data,
+            None => return,
+        };
+
+        let last_included_index = self.last_applied;
+        if self.verify_content_hashes {
+            self.content_hash_at(last_included_index);
+        }
+        let compacted = self.log_position(last_included_index);
+        self.log.drain(..compacted);
+        self.content_hashes
+            .drain(..cmp::min(compacted, self.content_hashes.len()));
+        self.index_offset = last_included_index;
+        self.snapshot = Some(Snapshot {
+            last_included_index,
+            last_included_term: self.log[0].term,
+            data,
+            applied_sequences: self.applied_sequences.clone(),
+        });
+        self.snapshot_taken_at = Some(self.clock.now());
+    }
+
+    // Whether the transitions in the log take hard_log_memory_limit bytes or
+    // more.
+    fn log_over_memory_limit(&self) -> bool {
+        match self.hard_log_memory_limit {
+            Some(limit) => {
+                let log_bytes: usize = self.log.iter().map(|entry| entry.transition.size()).sum();
+                log_bytes >= limit
+            }
+            None => false,
+        }
+    }
+
+    // No-op entries are internal to Raft, so they are never reported to the
+    // user. Their ID is reserved for that reason.
+    fn is_noop(&self, transition: &T) -> bool {
+        transition.get_id() == self.noop_transition.get_id()
+    }
+
+    fn load_new_transitions(&mut self) {
+        // Leave the transitions pending while the Leader is too far behind
+        // applying its log, or while its log takes too much memory.
+        if self.state == State::Leader {
+            // The state machine may have applied entries that the log lost
+            // before a restart.
+            let backlog = self.last_log_index().saturating_sub(self.last_applied);
+            self.transitions_deferred = matches!(self.apply_backlog_limit, Some(limit) if backlog >= limit)
+                || self.log_over_memory_limit();
+            if self.transitions_deferred {
+                return;
             }
         }
+
+        // Load new transitions. Ignore the transitions if the replica is not
+        // the Leader. The Leader has the state machine validate them while it
+        // holds the lock anyway.
+        // Proposals come after the state machine's transitions, along with
+        // where to report their outcome.
+        let validate = self.state == State::Leader && self.leadership_transfer.is_none();
+        let mut proposals = std::mem::take(&mut self.received_proposals);
+        proposals.extend(self.proposals.1.try_iter());
+        let transitions: Vec<_> = {
+            let mut state_machine = self.state_machine.lock().unwrap();
+            state_machine
+                .get_pending_transitions()
+                .into_iter()
+                .map(|transition| (transition, None))
+                .chain(
+                    proposals
+                        .into_iter()
+                        .map(|(transition, reply)| (transition, Some(reply))),
+                )
+                .map(|(transition, reply)| {
+                    let verdict = if validate && !self.is_noop(&transition) {
+                        state_machine.validate_transition(&transition)
+                    } else {
+                        Ok(())
+                    };
+                    (transition, reply, verdict)
+                })
+                .collect()
+        };
+
+        // Sort the transitions out without holding the state machine's lock,
+        // appending the accepted ones to the log as a contiguous block.
+        let first_appended = self.log.len();
+        let appended = self.clock.now();
+        let mut states = Vec::with_capacity(transitions.len());
+        for (transition, reply, verdict) in transitions {
+            let state = if self.is_noop(&transition) {
+                // A user transition sharing the no-op's ID could never be
+                // tracked, so refuse it right away.
+                TransitionState::Abandoned(TransitionAbandonedReason::ReservedID)
+            } else if self.leadership_transfer.is_some() {
+                TransitionState::Abandoned(TransitionAbandonedReason::LeadershipTransfer)
+            } else if let Err(reason) = verdict {
+                TransitionState::Rejected(reason)
+            } else if self.state == State::Leader {
+                self.in_flight_timelines.insert(
+                    self.last_log_index() + 1,
+                    TransitionTimeline {
+                        appended,
+                        replicated: None,
+                        committed: None,
+                        applied: None,
+                    },
+                );
+                if let Some(reply) = reply {
+                    self.proposal_waiters
+                        .insert(self.last_log_index() + 1, reply);
+                }
+                states.push((transition.get_id(), TransitionState::Queued));
+                self.log.push(LogEntry {
+                    index: self.last_log_index() + 1,
+                    transition,
+                    term: self.current_term,
+                    config: None,
+                });
+                continue;
+            } else {
+                TransitionState::Abandoned(TransitionAbandonedReason::NotLeader)
+            };
+            if let Some(reply) = reply {
+                let _ = reply.send(Err(match &state {
+                    TransitionState::Abandoned(TransitionAbandonedReason::NotLeader) => {
+                        ProposeError::NotLeader {
+                            leader_id: self.leader_id,
+                        }
+                    }
+                    TransitionState::Abandoned(reason) => ProposeError::Abandoned(reason.clone()),
+                    TransitionState::Rejected(reason) => ProposeError::Rejected(reason.clone()),
+                    state => unreachable!("transition refused as {:?}", state),
+                }));
+            }
+            states.push((transition.get_id(), state));
+        }
+
+        // Persist the block and report every transition under a single lock.
+        let mut state_machine = self.state_machine.lock().unwrap();
+        if self.log.len() > first_appended {
+            state_machine.append_log_entries(&self.log[first_appended..]);
+        }
+        for (transition_id, state) in states {
+            state_machine.register_transition_state(transition_id, state);
+        }
     }
 
     fn process_message_as_leader(&mut self, message: Message<T>) {
-        if let Message::AppendEntryResponse {
+        if let Message::InstallSnapshotResponse {
+            from_id,
+            term,
+            last_included_index,
+            last_index,
+        } = message
+        {
+            self.process_install_snapshot_response_as_leader(
+                from_id,
+                term,
+                last_included_index,
+                last_index,
+            );
+        } else if let Message::PreVoteRequest {
+            from_id,
+            term,
+            last_log_index,
+            last_log_term,
+        } = message
+        {
+            self.process_pre_vote_request(from_id, term, last_log_index, last_log_term);
+        } else if let Message::AppendEntryResponse {
             from_id,
             term,
             success,
             last_index,
             mismatch_index,
+            content_hash,
+            request_timestamp,
         } = message
         {
             if term > self.current_term {
                 // Become follower if another node's term is higher.
-                self.cluster.lock().unwrap().register_leader(None);
+                self.register_leader(None);
                 self.become_follower(term);
-            } else if success {
+                return;
+            }
+
+            if term == self.current_term {
+                self.record_request_ack(from_id, request_timestamp);
+                self.record_leadership_ack(from_id);
+                self.record_caught_up_as_leader();
+            }
+
+            if success {
+                // A response to a request of an older term may acknowledge
+                // entries this Leader does not have.
+                let last_index = cmp::min(last_index, self.last_log_index());
+
+                // A peer whose copy of the log differs from ours must not count
+                // toward committing it.
+                if self.verify_content_hashes
+                    && (last_index > self.last_log_index()
+                        || last_index < self.index_offset
+                        || content_hash != Some(self.content_hash_at(last_index)))
+                {
+                    return;
+                }
+
                 // Update information about the peer's logs.
-                self.next_index.insert(from_id, last_index + 1);
-                self.match_index.insert(from_id, last_index);
+                let peer = match self.peers.get_mut(&from_id) {
+                    Some(peer) => peer,
+                    None => return,
+                };
+                match self.max_inflight_appends {
+                    // Acknowledgements of pipelined batches may arrive out of
+                    // order, and must neither move the peer's progress back
+                    // nor count twice.
+                    Some(window) => {
+                        peer.match_index = cmp::max(peer.match_index, last_index);
+                        peer.next_index = cmp::max(peer.next_index, last_index + 1);
+                        peer.inflight = match last_index + 1 >= peer.next_index {
+                            // The peer holds everything sent so far, even if
+                            // some acknowledgements were lost.
+                            true => 0,
+                            false => peer.inflight.saturating_sub(1),
+                        };
+                        peer.known_match_index = Some(peer.match_index);
+                        self.pipeline_entries(from_id, window);
+                    }
+                    None => {
+                        peer.next_index = last_index + 1;
+                        peer.match_index = last_index;
+                        peer.known_match_index = Some(last_index);
+                        // The previous batch was capped, so carry on with the
+                        // next one rather than wait for a heartbeat.
+                        if self.max_entries_per_append.is_some()
+                            && last_index < self.last_log_index()
+                        {
+                            self.send_append_entry_request(from_id, true);
+                        }
+                    }
+                }
+                let now = self.clock.now();
+                for (_, timeline) in self.in_flight_timelines.range_mut(..=last_index) {
+                    timeline.replicated.get_or_insert(now);
+                }
+                self.continue_leadership_transfer(now);
             } else {
                 // Update information about the peer's logs.
                 //
@@ -419,16 +2869,152 @@ where
                 // to the Raft paper's guidance on decreasing next_index by
                 // one at a time, but is more performant in cases when we
                 // can cut straight to the follower's last_index+1.
-                if let Some(mismatch_index) = mismatch_index {
-                    if mismatch_index < self.next_index[&from_id] {
-                        let next_index = cmp::min(mismatch_index, last_index + 1);
-                        self.next_index.insert(from_id, next_index);
+                //
+                // With binary search, the next probe is halfway between the
+                // highest index known to match and mismatch_index instead.
+                if let (Some(mismatch_index), Some(peer)) =
+                    (mismatch_index, self.peers.get_mut(&from_id))
+                {
+                    if mismatch_index < peer.next_index {
+                        // Batches sent past the mismatch are rejected as well.
+                        peer.inflight = 0;
+                        // The placeholder at index 0 matches on every peer.
+                        peer.next_index = match self.conflict_resolution {
+                            ConflictResolution::FollowerHint => {
+                                cmp::max(cmp::min(mismatch_index, last_index + 1), 1)
+                            }
+                            ConflictResolution::BinarySearch => {
+                                let probe_index = (peer.match_index + mismatch_index) / 2;
+                                cmp::min(probe_index, last_index) + 1
+                            }
+                        };
+                        self.cluster.lock().unwrap().on_peer_reset(from_id);
                     }
                 }
             }
         }
     }
 
+    fn process_install_snapshot_response_as_leader(
+        &mut self,
+        from_id: ReplicaID,
+        term: usize,
+        last_included_index: usize,
+        last_index: usize,
+    ) {
+        if term > self.current_term {
+            // Become follower if another node's term is higher.
+            self.register_leader(None);
+            self.become_follower(term);
+            return;
+        }
+
+        if term == self.current_term {
+            self.record_leadership_ack(from_id);
+        }
+
+        // The peer now holds everything up to the snapshot. Responses to
+        // older snapshots must not move its progress back, and none can move
+        // it past the end of our log.
+        let last_log_index = self.last_log_index();
+        let last_included_index = cmp::min(last_included_index, last_log_index);
+        let peer = match self.peers.get_mut(&from_id) {
+            Some(peer) => peer,
+            None => return,
+        };
+        if peer.match_index < last_included_index {
+            peer.match_index = last_included_index;
+            peer.known_match_index = Some(last_included_index);
+        }
+        if peer.next_index > last_included_index {
+            return;
+        }
+        // The peer may have kept entries past the snapshot. Probe from its last
+        // one, as for a rejected AppendEntryRequest: if the peer does not
+        // hold our entry there, it rejects the request and tells where to
+        // resume.
+        peer.next_index = cmp::max(cmp::min(last_index, last_log_index), last_included_index) + 1;
+        peer.inflight = 0;
+
+        // Send the entries that follow the snapshot right away rather than
+        // with the next heartbeat, so the peer catches up in one round trip.
+        if peer.next_index <= last_log_index {
+            match self.max_inflight_appends {
+                Some(window) => {
+                    self.pipeline_entries(from_id, window);
+                }
+                None => self.send_append_entry_request(from_id, true),
+            }
+        }
+    }
+
+    // Hash of the log up to and including index, chaining the content hashes of
+    // the entries. Hashes are computed lazily and cached, starting from the
+    // first entry of the log.
+    fn content_hash_at(&mut self, index: usize) -> u64 {
+        let position = self.log_position(index);
+        while self.content_hashes.len() <= position {
+            let entry = &self.log[self.content_hashes.len()];
+            let mut hasher = DefaultHasher::new();
+            hasher.write_u64(self.content_hashes.last().copied().unwrap_or_default());
+            hasher.write_usize(entry.term);
+            hasher.write_u64(entry.transition.content_hash());
+            self.content_hashes.push(hasher.finish());
+        }
+        self.content_hashes[position]
+    }
+
+    // Record that a peer answered the AppendEntryRequest sent with timestamp,
+    // which dates the acknowledgement for the lease. Requests too old to be
+    // remembered cannot extend the lease anyway.
+    fn record_request_ack(&mut self, peer_id: ReplicaID, timestamp: Option<SystemTime>) {
+        let sent_at = self
+            .sent_requests
+            .iter()
+            .find(|(to_id, sent, _)| *to_id == peer_id && Some(*sent) == timestamp)
+            .map(|(_, _, sent_at)| *sent_at);
+        if let (Some(sent_at), Some(peer)) = (sent_at, self.peers.get_mut(&peer_id)) {
+            peer.lease_ack = cmp::max(peer.lease_ack, Some(sent_at));
+        }
+    }
+
+    // Record how fresh the Leader is: as of the time by which a majority of the
+    // cluster last acknowledged it.
+    fn record_caught_up_as_leader(&self) {
+        let now = self.clock.now();
+        let caught_up_at = self.quorum_value(|id| match id == self.id {
+            true => Some(now),
+            false => self.peers[&id].last_ack,
+        });
+        if let Some(caught_up_at) = caught_up_at {
+            self.handle.record_caught_up(caught_up_at);
+        }
+    }
+
+    // Record that a peer acknowledged this Replica as the Leader of the current
+    // term. Once a majority did, the Leader is confirmed and starts accepting
+    // transitions.
+    fn record_leadership_ack(&mut self, peer_id: ReplicaID) {
+        if let Some(peer) = self.peers.get_mut(&peer_id) {
+            peer.last_ack = Some(self.clock.now());
+        }
+        if self.leadership_confirmed {
+            return;
+        }
+
+        if !self.peers.contains_key(&peer_id) {
+            return;
+        }
+        self.leadership_acks.insert(peer_id);
+        let mut acks = self.leadership_acks.clone();
+        acks.insert(self.id);
+        if self.is_quorum(&acks) {
+            self.leadership_confirmed = true;
+            self.load_new_transitions();
+            self.broadcast_append_entry_request();
+        }
+    }
+
     fn process_vote_request_as_follower(
         &mut self,
         from_id: ReplicaID,
@@ -439,30 +3025,28 @@ where
         match self.current_term.cmp(&term) {
             Ordering::Greater => {
                 // Do not vote for Replicas that are behind.
-                self.cluster.lock().unwrap().send_message(
-                    from_id,
-                    Message::VoteResponse {
-                        from_id: self.id,
-                        term: self.current_term,
-                        vote_granted: false,
-                    },
-                );
+                self.reject_vote(from_id, RejectReason::StaleTerm);
+                return;
             }
             Ordering::Less => {
                 // Become follower if the other replica's term is higher.
-                self.cluster.lock().unwrap().register_leader(None);
+                self.register_leader(None);
                 self.become_follower(term);
             }
             _ => {}
         }
 
-        if self.voted_for == None || self.voted_for == Some(from_id) {
-            if self.log[self.log.len() - 1].index <= last_log_index
-                && self.log[self.log.len() - 1].term <= last_log_term
-            {
-                // If the criteria are met, grant the vote.
-                self.cluster.lock().unwrap().register_leader(None);
-                self.cluster.lock().unwrap().send_message(
+        if self.voted_for.is_none() || self.voted_for == Some(from_id) {
+            if self.log_up_to_date(last_log_index, last_log_term) {
+                // If the criteria are met, grant the vote. The vote must be
+                // durable before the Candidate hears of it. Give the Candidate
+                // a full election timeout to win before campaigning against
+                // it.
+                self.register_leader(None);
+                self.voted_for = Some(from_id);
+                self.save_hard_state();
+                self.update_election_deadline();
+                self.send_message(
                     from_id,
                     Message::VoteResponse {
                         from_id: self.id,
@@ -470,31 +3054,29 @@ where
                         vote_granted: true,
                     },
                 );
-                self.voted_for = Some(from_id);
             } else {
                 // If the criteria are not met, do not grant the vote.
-                self.cluster.lock().unwrap().send_message(
-                    from_id,
-                    Message::VoteResponse {
-                        from_id: self.id,
-                        term: self.current_term,
-                        vote_granted: false,
-                    },
-                );
+                self.reject_vote(from_id, RejectReason::LogBehind);
             }
         } else {
             // If voted for someone else, don't grant the vote.
-            self.cluster.lock().unwrap().send_message(
-                from_id,
-                Message::VoteResponse {
-                    from_id: self.id,
-                    term: self.current_term,
-                    vote_granted: false,
-                },
-            )
+            self.reject_vote(from_id, RejectReason::AlreadyVoted);
         }
     }
 
+    fn reject_vote(&mut self, to_id: ReplicaID, reason: RejectReason) {
+        *self.vote_rejections.entry(reason).or_insert(0) += 1;
+        self.send_message(
+            to_id,
+            Message::VoteResponse {
+                from_id: self.id,
+                term: self.current_term,
+                vote_granted: false,
+            },
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn process_append_entry_request_as_follower(
         &mut self,
         from_id: ReplicaID,
@@ -503,71 +3085,346 @@ where
         prev_log_term: usize,
         entries: Vec<LogEntry<T>>,
         commit_index: usize,
+        timestamp: Option<SystemTime>,
     ) {
         // Check that the leader's term is at least as large as ours.
         if self.current_term > term {
-            self.cluster.lock().unwrap().send_message(
+            self.send_message(
                 from_id,
                 Message::AppendEntryResponse {
                     from_id: self.id,
                     term: self.current_term,
                     success: false,
-                    last_index: self.log.len() - 1,
+                    last_index: self.last_log_index(),
                     mismatch_index: None,
+                    content_hash: None,
+                    request_timestamp: timestamp,
                 },
             );
             return;
+        }
+        // A Leader of a later term took over.
+        if term > self.current_term {
+            self.become_follower(term);
+        }
+        // The Leader is alive, so late pre-votes must not start an election.
+        self.pre_votes = None;
+
+        self.last_leader_contact = Some(self.clock.now());
+
+        // Whatever the state of our log, entries up to the Leader's commit
+        // index are committed and must be present on every future Leader.
+        self.highest_seen_commit_index = cmp::max(self.highest_seen_commit_index, commit_index);
+
+        // The snapshot being installed replaces our log up to its last entry.
+        // Entries up to there would be dropped along with it, and
+        // acknowledging them would make the Leader start the transfer over.
+        if let Some((installing_index, _, _)) = self.snapshot_buffer {
+            if prev_log_index < installing_index {
+                self.send_message(
+                    from_id,
+                    Message::AppendEntryResponse {
+                        from_id: self.id,
+                        term: self.current_term,
+                        success: false,
+                        last_index: self.last_log_index(),
+                        mismatch_index: None,
+                        content_hash: None,
+                        request_timestamp: timestamp,
+                    },
+                );
+                return;
+            }
+        }
+
+        // Entries up to index_offset were compacted into our snapshot, so they
+        // are committed and match the Leader's. Skip over them instead of
+        // looking below the start of our log.
+        let (prev_log_index, prev_log_term, entries) = if prev_log_index < self.index_offset {
+            let index_offset = self.index_offset;
+            (
+                index_offset,
+                self.entry(index_offset).term,
+                entries
+                    .into_iter()
+                    .filter(|entry| entry.index > index_offset)
+                    .collect(),
+            )
+        } else {
+            (prev_log_index, prev_log_term, entries)
+        };
+
         // If our log doesn't contain an entry at prev_log_index with the
-        // prev_log_term term, reply false.
-        } else if prev_log_index >= self.log.len() || self.log[prev_log_index].term != prev_log_term
-        {
-            self.cluster.lock().unwrap().send_message(
+        // prev_log_term term, reply false. So do we if the entries do not
+        // follow it, or would overwrite committed ones, which no Leader sends.
+        let matches_prev_log = match self.local_index(prev_log_index) {
+            Some(local) => self.log[local].term == prev_log_term,
+            None => false,
+        };
+        let well_formed = entries.iter().enumerate().all(|(i, entry)| {
+            entry.index == prev_log_index + 1 + i
+                && (entry.index > self.commit_index || self.entry(entry.index).term == entry.term)
+        });
+        if !matches_prev_log || !well_formed {
+            self.send_message(
                 from_id,
                 Message::AppendEntryResponse {
                     from_id: self.id,
                     term: self.current_term,
                     success: false,
-                    last_index: self.log.len() - 1,
+                    last_index: self.last_log_index(),
                     mismatch_index: Some(prev_log_index),
+                    content_hash: None,
+                    request_timestamp: timestamp,
                 },
             );
             return;
         }
 
+        // Only the entries up to the last one the Leader sent are known to
+        // match its log. Anything past them may be a stale suffix that the
+        // Leader has yet to overwrite.
+        let last_new_index = prev_log_index + entries.len();
+        let mut first_appended = None;
         for entry in entries {
             // Drop local inconsistent logs.
-            if entry.index < self.log.len() && entry.term != self.log[entry.index].term {
-                self.log.truncate(entry.index);
+            if let Some(local) = self.local_index(entry.index) {
+                if entry.term != self.log[local].term {
+                    self.abandon_doomed_entries(entry.index);
+                    self.log.truncate(local);
+                    self.content_hashes.truncate(local);
+                }
             }
 
             // Push received logs.
-            if entry.index == self.log.len() {
+            if entry.index == self.last_log_index() + 1 {
+                first_appended.get_or_insert(entry.index);
                 self.log.push(entry);
             }
         }
+        if let Some(first_appended) = first_appended {
+            self.state_machine
+                .lock()
+                .unwrap()
+                .append_log_entries(&self.log[self.log_position(first_appended)..]);
+        }
 
         // Update local commit index to either the received commit index or the
-        // latest local log position, whichever is smaller.
+        // last entry known to match the Leader's log, whichever is smaller.
         if commit_index > self.commit_index && !self.log.is_empty() {
-            self.commit_index = if commit_index < self.log[self.log.len() - 1].index {
-                commit_index
-            } else {
-                self.log[self.log.len() - 1].index
-            }
+            self.commit_index = cmp::max(self.commit_index, cmp::min(commit_index, last_new_index));
         }
-        self.cluster.lock().unwrap().register_leader(Some(from_id));
-        self.cluster.lock().unwrap().send_message(
+        if self.commit_index >= commit_index {
+            self.handle.record_caught_up(self.clock.now());
+        }
+        self.register_leader(Some(from_id));
+        let last_index = last_new_index;
+        let content_hash = if self.verify_content_hashes {
+            Some(self.content_hash_at(last_index))
+        } else {
+            None
+        };
+        self.send_message(
             from_id,
             Message::AppendEntryResponse {
                 from_id: self.id,
                 term: self.current_term,
                 success: true,
-                last_index: self.log.len() - 1,
+                last_index,
                 mismatch_index: None,
+                content_hash,
+                request_timestamp: timestamp,
+            },
+        );
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn process_install_snapshot_request_as_follower(
+        &mut self,
+        from_id: ReplicaID,
+        term: usize,
+        last_included_index: usize,
+        last_included_term: usize,
+        data: Vec<u8>,
+        offset: usize,
+        done: bool,
+        content_hash: Option<u64>,
+        applied_sequences: BTreeMap<ClientId, u64>,
+    ) {
+        // Check that the leader's term is at least as large as ours.
+        if self.current_term > term {
+            self.send_message(
+                from_id,
+                Message::InstallSnapshotResponse {
+                    from_id: self.id,
+                    term: self.current_term,
+                    last_included_index,
+                    last_index: self.last_log_index(),
+                },
+            );
+            return;
+        }
+        // A Leader of a later term took over.
+        if term > self.current_term {
+            self.become_follower(term);
+        }
+        // The Leader is alive, so late pre-votes must not start an election.
+        self.pre_votes = None;
+
+        self.register_leader(Some(from_id));
+        self.last_leader_contact = Some(self.clock.now());
+        self.highest_seen_commit_index =
+            cmp::max(self.highest_seen_commit_index, last_included_index);
+
+        // A transfer starts over at offset 0. Chunks of another snapshot, or
+        // that do not follow the ones received so far, are dropped.
+        if offset == 0 {
+            self.snapshot_buffer = Some((last_included_index, last_included_term, Vec::new()));
+        }
+        match &mut self.snapshot_buffer {
+            Some((index, term, buffer))
+                if *index == last_included_index
+                    && *term == last_included_term
+                    && offset == buffer.len() =>
+            {
+                buffer.extend(data)
+            }
+            _ => return,
+        }
+        if !done {
+            return;
+        }
+
+        let (_, _, data) = self.snapshot_buffer.take().unwrap();
+        // A Replica that already applied the snapshot's entries has nothing
+        // to install.
+        if last_included_index > self.last_applied {
+            let snapshot = Snapshot {
+                last_included_index,
+                last_included_term,
+                data,
+                applied_sequences,
+            };
+            // A snapshot that cannot be loaded halts the Replica before its
+            // log is touched.
+            if let Err(error) = self.state_machine.lock().unwrap().load_snapshot(&snapshot) {
+                self.fatal_error = Some(RaftError::SnapshotLoad(error));
+                return;
+            }
+            if let Some(shadow_state_machine) = &self.shadow_state_machine {
+                let _ = shadow_state_machine
+                    .lock()
+                    .unwrap()
+                    .load_snapshot(&snapshot);
+            }
+
+            let kept = self
+                .local_index(last_included_index)
+                .filter(|local| self.log[*local].term == last_included_term);
+            if let Some(kept) = kept {
+                // Entries past the snapshot are consistent with the Leader's
+                // log, so keep them.
+                self.log.drain(..kept);
+            } else {
+                // Of the entries the snapshot replaces, only those of its last
+                // term are known to be part of it.
+                let overwritten: Vec<usize> = self
+                    .doomed_entries
+                    .iter()
+                    .copied()
+                    .filter(|index| {
+                        *index > last_included_index
+                            || self.entry(*index).term != last_included_term
+                    })
+                    .collect();
+                for index in &overwritten {
+                    self.doomed_entries.remove(index);
+                }
+                self.abandon_entries(overwritten);
+                self.log = vec![LogEntry {
+                    index: last_included_index,
+                    term: last_included_term,
+                    transition: self.noop_transition.clone(),
+                    config: None,
+                }];
+            }
+            {
+                let mut state_machine = self.state_machine.lock().unwrap();
+                state_machine.save_snapshot(&snapshot);
+                state_machine.append_log_entries(&self.log[1..]);
+            }
+            self.content_hashes = content_hash.into_iter().collect();
+            self.index_offset = last_included_index;
+            // Only the snapshot knows which sequence numbers its entries
+            // applied.
+            self.applied_sequences = snapshot.applied_sequences.clone();
+            self.snapshot = Some(snapshot);
+            self.snapshot_taken_at = Some(self.clock.now());
+            self.commit_index = cmp::max(self.commit_index, last_included_index);
+            self.last_applied = last_included_index;
+            self.handle.update(self.commit_index, self.last_applied);
+            self.state_machine
+                .lock()
+                .unwrap()
+                .after_snapshot_install(SnapshotMeta {
+                    last_included_index,
+                    last_included_term,
+                });
+        }
+
+        self.send_message(
+            from_id,
+            Message::InstallSnapshotResponse {
+                from_id: self.id,
+                term: self.current_term,
+                last_included_index,
+                last_index: self.last_log_index(),
             },
         );
     }
 
+    // Report the entries this Replica appended as the Leader that are about to
+    // be dropped from index onwards as abandoned.
+    fn abandon_doomed_entries(&mut self, index: usize) {
+        let doomed_entries = self.doomed_entries.split_off(&index);
+        self.abandon_entries(doomed_entries);
+    }
+
+    // Report the entries at the given indices overwritten, to the state
+    // machine and to the proposals waiting on them. They must still be in the
+    // log.
+    fn abandon_entries(&mut self, indices: impl IntoIterator<Item = usize>) {
+        let mut state_machine = self.state_machine.lock().unwrap();
+        for doomed_index in indices {
+            state_machine.register_transition_state(
+                self.entry(doomed_index).transition.get_id(),
+                TransitionState::Abandoned(TransitionAbandonedReason::Overwritten),
+            );
+            if let Some(reply) = self.proposal_waiters.remove(&doomed_index) {
+                let _ = reply.send(Err(ProposeError::Abandoned(
+                    TransitionAbandonedReason::Overwritten,
+                )));
+            }
+        }
+    }
+
+    // Report the Leader's clock to the Cluster if it drifted too far from ours.
+    fn check_clock_drift(&mut self, leader_id: ReplicaID, timestamp: Option<SystemTime>) {
+        if let (Some(max_clock_drift), Some(timestamp)) = (self.max_clock_drift, timestamp) {
+            let now = SystemTime::now();
+            let drift = now
+                .duration_since(timestamp)
+                .or_else(|_| timestamp.duration_since(now))
+                .unwrap_or_default();
+            if drift > max_clock_drift {
+                self.cluster
+                    .lock()
+                    .unwrap()
+                    .register_clock_drift(leader_id, drift);
+            }
+        }
+    }
+
     fn process_message_as_follower(&mut self, message: Message<T>) {
         match message {
             Message::VoteRequest {
@@ -585,22 +3442,74 @@ where
                 prev_log_term,
                 entries,
                 commit_index,
-            } => self.process_append_entry_request_as_follower(
+                timestamp,
+            } => {
+                self.check_clock_drift(from_id, timestamp);
+                self.process_append_entry_request_as_follower(
+                    from_id,
+                    term,
+                    prev_log_index,
+                    prev_log_term,
+                    entries,
+                    commit_index,
+                    timestamp,
+                )
+            }
+            Message::InstallSnapshotRequest {
                 from_id,
                 term,
-                prev_log_index,
-                prev_log_term,
-                entries,
-                commit_index,
+                last_included_index,
+                last_included_term,
+                data,
+                offset,
+                done,
+                content_hash,
+                applied_sequences,
+            } => self.process_install_snapshot_request_as_follower(
+                from_id,
+                term,
+                last_included_index,
+                last_included_term,
+                data,
+                offset,
+                done,
+                content_hash,
+                applied_sequences,
             ),
+            Message::PreVoteRequest {
+                from_id,
+                term,
+                last_log_index,
+                last_log_term,
+            } => self.process_pre_vote_request(from_id, term, last_log_index, last_log_term),
+            Message::PreVoteResponse {
+                from_id,
+                term,
+                vote_granted,
+            } => self.process_pre_vote_response(from_id, term, vote_granted),
+            Message::TimeoutNow { from_id, term } => {
+                // The Leader hands leadership over, so skip the election
+                // timeout and the Pre-Vote phase. Only the Leader of the
+                // current term may do so, and not while it sends us a
+                // snapshot.
+                if term == self.current_term
+                    && self.leader_id == Some(from_id)
+                    && self.snapshot_buffer.is_none()
+                {
+                    self.become_candidate();
+                    self.update_election_deadline();
+                }
+            }
             Message::AppendEntryResponse { .. } => { /* ignore */ }
+            Message::InstallSnapshotResponse { .. } => { /* ignore */ }
             Message::VoteResponse { .. } => { /* ignore */ }
         }
     }
 
     fn process_message_as_candidate(&mut self, message: Message<T>) {
         match message {
-            Message::AppendEntryRequest { term, from_id, .. } => {
+            Message::AppendEntryRequest { term, from_id, .. }
+            | Message::InstallSnapshotRequest { term, from_id, .. } => {
                 self.process_append_entry_request_as_candidate(term, from_id, message)
             }
             Message::VoteRequest { term, from_id, .. } => {
@@ -611,7 +3520,20 @@ where
                 term,
                 vote_granted,
             } => self.process_vote_response_as_candidate(from_id, term, vote_granted),
-            Message::AppendEntryResponse { .. } => { /* ignore */ }
+            Message::PreVoteRequest {
+                from_id,
+                term,
+                last_log_index,
+                last_log_term,
+            } => self.process_pre_vote_request(from_id, term, last_log_index, last_log_term),
+            Message::PreVoteResponse {
+                from_id,
+                term,
+                vote_granted,
+            } => self.process_pre_vote_response(from_id, term, vote_granted),
+            Message::AppendEntryResponse { .. }
+            | Message::InstallSnapshotResponse { .. }
+            | Message::TimeoutNow { .. } => { /* ignore */ }
         }
     }
 
@@ -622,16 +3544,19 @@ where
         vote_granted: bool,
     ) {
         if term > self.current_term {
-            self.cluster.lock().unwrap().register_leader(None);
+            self.register_leader(None);
             self.become_follower(term);
-        } else if vote_granted {
-            // Record that the vote has been granted.
-            if let Some(cur_votes) = &mut self.current_votes {
+        } else if vote_granted && term == self.current_term && self.peers.contains_key(&from_id) {
+            // Record that the vote has been granted. Votes granted in earlier
+            // terms are stale and must not count toward this election.
+            if let Some(mut cur_votes) = self.current_votes.take() {
                 cur_votes.insert(from_id);
                 // If more than half of the cluster has voted for the Replica
                 // (the Replica itself included), it's time to become the
                 // Leader.
-                if cur_votes.len() * 2 > self.peer_ids.len() {
+                let won = self.is_quorum(&cur_votes);
+                self.current_votes = Some(cur_votes);
+                if won {
                     self.become_leader();
                 }
             }
@@ -645,18 +3570,15 @@ where
         message: Message<T>,
     ) {
         if term > self.current_term {
-            self.cluster.lock().unwrap().register_leader(None);
+            self.register_leader(None);
             self.become_follower(term);
             self.process_message(message);
+        } else if term < self.current_term {
+            self.reject_vote(from_id, RejectReason::StaleTerm);
         } else {
-            self.cluster.lock().unwrap().send_message(
-                from_id,
-                Message::VoteResponse {
-                    from_id: self.id,
-                    term: self.current_term,
-                    vote_granted: false,
-                },
-            );
+            // Candidates always vote for themselves.
+            self.competing_candidacy_term = Some(term);
+            self.reject_vote(from_id, RejectReason::AlreadyVoted);
         }
     }
 
@@ -667,33 +3589,61 @@ where
         message: Message<T>,
     ) {
         if term >= self.current_term {
-            self.cluster.lock().unwrap().register_leader(None);
+            self.register_leader(None);
             self.become_follower(term);
             self.process_message(message);
         } else {
-            self.cluster.lock().unwrap().send_message(
+            self.send_message(
                 from_id,
                 Message::AppendEntryResponse {
                     from_id: self.id,
                     term: self.current_term,
                     success: false,
-                    last_index: self.log.len() - 1,
+                    last_index: self.last_log_index(),
                     mismatch_index: None,
+                    content_hash: None,
+                    request_timestamp: None,
                 },
             );
         }
     }
 
     fn become_leader(&mut self) {
-        self.cluster.lock().unwrap().register_leader(Some(self.id));
-        self.state = State::Leader;
+        // Raft's vote rules guarantee that a Leader holds every committed
+        // entry. If it does not, the vote check is broken. Release builds
+        // turn the leadership down rather than lose committed entries.
+        let complete = self.last_log_index() >= self.highest_seen_commit_index;
+        debug_assert!(
+            complete,
+            "Leader completeness violated: log ends at {} but index {} is committed",
+            self.last_log_index(),
+            self.highest_seen_commit_index
+        );
+        if !complete {
+            self.become_follower(self.current_term);
+            return;
+        }
+
+        self.register_leader(Some(self.id));
+        self.set_state(State::Leader);
+        self.leadership_acks.clear();
+        self.leadership_confirmed =
+            !self.confirm_leadership_before_writes || self.peer_ids.is_empty();
         self.current_votes = None;
-        self.voted_for = None;
-        self.next_index = BTreeMap::new();
-        self.match_index = BTreeMap::new();
-        for peer_id in &self.peer_ids {
-            self.next_index.insert(*peer_id, self.log.len());
-            self.match_index.insert(*peer_id, 0);
+        self.pre_votes = None;
+        let last_log_index = self.last_log_index();
+        for (peer_id, peer) in self.peers.iter_mut() {
+            peer.next_index = match peer.known_match_index {
+                Some(match_index) if self.seed_next_index => {
+                    cmp::min(match_index + 1, last_log_index + 1)
+                }
+                _ => last_log_index + 1,
+            };
+            peer.match_index = 0;
+            peer.last_ack = None;
+            peer.lease_ack = None;
+            peer.inflight = 0;
+            self.cluster.lock().unwrap().on_peer_reset(*peer_id);
         }
 
         // If the previous Leader had some uncommitted entries that were
@@ -703,35 +3653,138 @@ where
         // emerges, append a no-op entry. This is a neat optimization described
         // in the part 8 of the paper.
         self.log.push(LogEntry {
-            index: self.log.len(),
+            index: self.last_log_index() + 1,
             transition: self.noop_transition.clone(),
             term: self.current_term,
+            config: None,
         });
+        self.state_machine
+            .lock()
+            .unwrap()
+            .append_log_entries(&self.log[self.log.len() - 1..]);
+
+        // Let the followers know about the new Leader right away rather than
+        // after a heartbeat timeout, before any of them starts an election.
+        self.broadcast_append_entry_request();
+        self.heartbeat_timer.renew();
     }
 
     fn become_follower(&mut self, term: usize) {
-        self.current_term = term;
-        self.state = State::Follower;
+        if self.state == State::Leader {
+            // Entries this Replica appended in its term will only commit if
+            // the new Leader has them.
+            for entry in &self.log[self.log_position(self.commit_index) + 1..] {
+                if entry.term == self.current_term && !self.is_noop(&entry.transition) {
+                    self.doomed_entries.insert(entry.index);
+                }
+            }
+        }
+
+        // The vote cast in the current term stands: forgetting it would let
+        // this Replica vote twice in the term.
+        if term > self.current_term {
+            self.current_term = term;
+            self.voted_for = None;
+            self.save_hard_state();
+            // The Leader of the new term starts any snapshot transfer over.
+            self.snapshot_buffer = None;
+        }
+        self.set_state(State::Follower);
+        self.leadership_transfer = None;
         self.current_votes = None;
-        self.voted_for = None;
+        self.pre_votes = None;
+        // Replication progress is only meaningful to the Leader that tracked
+        // it.
+        for peer in self.peers.values_mut() {
+            peer.next_index = 0;
+            peer.match_index = 0;
+            peer.last_ack = None;
+            peer.lease_ack = None;
+            peer.inflight = 0;
+        }
+        self.in_flight_timelines.clear();
+        self.sent_requests.clear();
+    }
+
+    fn save_hard_state(&mut self) {
+        self.state_machine
+            .lock()
+            .unwrap()
+            .save_hard_state(self.current_term, self.voted_for);
+    }
+
+    fn set_state(&mut self, state: State) {
+        let old_state = self.state;
+        if self.state != state {
+            self.state = state;
+            self.role_changed_at = self.clock.now();
+        }
+        // The term changes along with the state, if at all.
+        self.publish_leadership();
+        if let Some(on_state_change) = &self.on_state_change {
+            on_state_change(RoleChange {
+                old_state,
+                new_state: state,
+                term: self.current_term,
+            });
+        }
+    }
+
+    fn register_leader(&mut self, leader_id: Option<ReplicaID>) {
+        self.leader_id = leader_id;
+        self.cluster.lock().unwrap().register_leader(leader_id);
+        self.publish_leadership();
+    }
+
+    // When the Replica last heard from the Leader of its term or, on the
+    // Leader, last broadcast AppendEntryRequests.
+    fn last_heartbeat(&self) -> Option<Instant> {
+        match self.state {
+            State::Leader => Some(self.last_broadcast),
+            _ => self.last_leader_contact,
+        }
+    }
+
+    // Share the counters that are not kept up to date on the handle as they
+    // change.
+    fn publish_metrics(&self) {
+        self.handle.update_metrics(
+            self.log.len(),
+            self.index_offset,
+            self.state,
+            self.last_heartbeat(),
+            self.last_election_timeout,
+        );
+    }
+
+    fn publish_leadership(&self) {
+        self.handle.update_leadership(
+            self.current_term,
+            self.state == State::Leader,
+            self.leader_id,
+        );
     }
 
     fn become_candidate(&mut self) {
         // Increase current term.
         self.current_term += 1;
         // Claim yourself a candidate.
-        self.state = State::Candidate;
+        self.set_state(State::Candidate);
         // Initialize votes. Vote for yourself.
         let mut votes = BTreeSet::new();
         votes.insert(self.id);
-        self.current_votes = Some(Box::new(votes));
+        self.current_votes = Some(votes);
+        self.pre_votes = None;
         self.voted_for = Some(self.id);
+        self.save_hard_state();
         // Fan out vote requests.
-        self.broadcast_message(|_: usize| Message::VoteRequest {
-            from_id: self.id,
-            term: self.current_term,
-            last_log_index: self.log.len() - 1,
-            last_log_term: self.log[self.log.len() - 1].term,
+        self.last_vote_request = Some(self.clock.now());
+        let peer_ids = self.peer_ids.clone();
+        self.broadcast_message(&peer_ids, |replica, _| Message::VoteRequest {
+            from_id: replica.id,
+            term: replica.current_term,
+            last_log_index: replica.last_log_index(),
+            last_log_term: replica.last_log_term(),
         });
 
         if self.peer_ids.is_empty() {
@@ -739,3 +3792,14 @@ where
         }
     }
 }
+
+// Remember that the client's transition with the given sequence number was
+// applied.
+fn record_sequence(
+    applied_sequences: &mut BTreeMap<ClientId, u64>,
+    client_id: ClientId,
+    sequence: u64,
+) {
+    let applied = applied_sequences.entry(client_id).or_insert(sequence);
+    *applied = cmp::max(*applied, sequence);
+}
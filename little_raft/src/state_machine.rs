@@ -1,4 +1,10 @@
-use std::fmt::Debug;
+use crate::{message::LogEntry, replica::ReplicaID};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    fmt::Debug,
+    hash::{Hash, Hasher},
+    time::Instant,
+};
 
 /// TransitionState describes the state of a particular transition.
 #[derive(Clone, Debug, PartialEq)]
@@ -19,6 +25,28 @@ pub enum TransitionState {
 
     /// Abandoned transitions have been ignored by the replica.
     Abandoned(TransitionAbandonedReason),
+
+    /// Rejected transitions have been refused by StateMachine::validate_transition
+    /// and never entered the log.
+    Rejected(TransitionRejectedReason),
+}
+
+/// TransitionTimeline records when a transition proposed to the Leader reached
+/// each step of replication. It is useful to find where write latency is spent.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransitionTimeline {
+    /// When the Leader appended the transition to its log.
+    pub appended: Instant,
+
+    /// When the first follower acknowledged the transition. None if the
+    /// cluster has no other Replicas.
+    pub replicated: Option<Instant>,
+
+    /// When the transition was replicated on a majority of the cluster.
+    pub committed: Option<Instant>,
+
+    /// When the transition was applied to the Leader's state machine.
+    pub applied: Option<Instant>,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -26,8 +54,72 @@ pub enum TransitionAbandonedReason {
     // NotLeader transitions have been abandoned because the replica is not
     // the cluster leader.
     NotLeader,
+
+    // ReservedID transitions have been abandoned because their ID is the same
+    // as the ID of the no-op transition, which is reserved by the replica.
+    ReservedID,
+
+    // Overwritten transitions have been abandoned because the replica appended
+    // them as the leader but lost leadership before they were committed, and
+    // the new leader replaced them in the log.
+    Overwritten,
+
+    // LeadershipTransfer transitions have been abandoned because the replica
+    // was handing leadership over to another replica when it received them.
+    LeadershipTransfer,
+}
+
+/// TransitionRejectedReason describes why StateMachine::validate_transition
+/// refused a transition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TransitionRejectedReason {
+    /// Invalid transitions could never be applied, for example because they
+    /// are malformed.
+    Invalid(String),
+}
+
+/// ApplyError describes why the state machine could not apply a transition.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ApplyError {
+    /// Fatal errors can never be recovered from. The transition is committed,
+    /// so every Replica fails to apply it the same way, and skipping it would
+    /// make this Replica diverge from the cluster. The Replica halts instead.
+    Fatal(String),
+}
+
+/// Snapshot is a serialized copy of the state machine that replaces the log
+/// entries up to and including last_included_index.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// Index of the last entry the snapshot replaces.
+    pub last_included_index: usize,
+
+    /// Term of the last entry the snapshot replaces.
+    pub last_included_term: usize,
+
+    /// State of the state machine, as returned by StateMachine::create_snapshot.
+    pub data: Vec<u8>,
+
+    /// Highest sequence number applied for each client by the entries the
+    /// snapshot replaces, see StateMachineTransition::dedup_key.
+    pub applied_sequences: BTreeMap<ClientId, u64>,
+}
+
+/// SnapshotMeta describes a snapshot installed from the Leader, see
+/// StateMachine::after_snapshot_install.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SnapshotMeta {
+    /// Index of the last entry the snapshot replaced.
+    pub last_included_index: usize,
+
+    /// Term of the last entry the snapshot replaced.
+    pub last_included_term: usize,
 }
 
+/// ClientId identifies a client that tags its transitions with sequence
+/// numbers, see StateMachineTransition::dedup_key.
+pub type ClientId = u64;
+
 /// StateMachineTransition describes a user-defined transition that can be
 /// applied to the state machine replicated by Raft.
 pub trait StateMachineTransition: Clone + Debug {
@@ -37,6 +129,46 @@ pub trait StateMachineTransition: Clone + Debug {
     /// get_id is used by the Replica to identify the transition to be able to
     /// call register_transition_state.
     fn get_id(&self) -> Self::TransitionID;
+
+    /// size is the approximate size of the transition in bytes. Override it if
+    /// the transition owns heap memory, such as a Vec or a String.
+    fn size(&self) -> usize {
+        std::mem::size_of_val(self)
+    }
+
+    /// for_format is used by the Leader to convert the transition for a peer
+    /// that only understands the given format, as told by
+    /// Cluster::peer_format. The converted transition must have the same
+    /// effect on the state machine. Replicas that verify content hashes must
+    /// hash both versions the same way. The default returns the transition
+    /// as is.
+    fn for_format(&self, _format: u32) -> Self {
+        self.clone()
+    }
+
+    /// dedup_key tags the transition with the ID of the client that submitted
+    /// it and a sequence number that the client increases with every new
+    /// transition. A Replica does not apply a transition whose sequence number
+    /// is not above the highest one it applied for the client, so a client can
+    /// safely resubmit a transition after a leader change without knowing
+    /// whether the first attempt committed. The skipped transition is
+    /// reported Applied, like the one it repeats. Snapshots carry the
+    /// sequence numbers applied by the entries they replace. The default
+    /// returns None, which never skips.
+    fn dedup_key(&self) -> Option<(ClientId, u64)> {
+        None
+    }
+
+    /// content_hash is used by Replicas that verify content hashes to check
+    /// that they hold the same transitions. The default hashes the Debug
+    /// representation of the transition with the standard library's hasher,
+    /// which is only stable within a given Rust release. Override it if the
+    /// Replicas may be built differently or for a cheaper hash.
+    fn content_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        format!("{:?}", self).hash(&mut hasher);
+        hasher.finish()
+    }
 }
 
 /// StateMachine describes a user-defined state machine that is replicated
@@ -53,9 +185,153 @@ where
 
     /// When a particular transition is ready to be applied, the Replica will
     /// call apply_transition to apply said transition to the local state
-    /// machine.
+    /// machine. Transitions are applied at least once: after a restart, the
+    /// Replica applies again every transition above applied_index.
     fn apply_transition(&mut self, transition: T);
 
+    /// try_apply_transition is what the Replica calls to apply a transition
+    /// that may fail. Override it instead of apply_transition if a transition
+    /// can never be applied, and return ApplyError::Fatal to halt the Replica.
+    /// The default calls apply_transition, which never fails.
+    fn try_apply_transition(&mut self, transition: T) -> Result<(), ApplyError> {
+        self.apply_transition(transition);
+        Ok(())
+    }
+
+    /// apply_and_record is what the Replica actually calls to apply the
+    /// transition at the given log index. State machines that persist their
+    /// state must override it to apply the transition and record index as
+    /// their applied_index in a single atomic step: if the state could be
+    /// persisted without the index, a crash in between would make the Replica
+    /// apply the transition again after the restart. The default simply calls
+    /// try_apply_transition.
+    fn apply_and_record(&mut self, transition: T, _index: usize) -> Result<(), ApplyError> {
+        self.try_apply_transition(transition)
+    }
+
+    /// applied_index is consulted by the Replica when it is created. It must
+    /// return the index of the last transition that the state machine has
+    /// durably applied, so that the Replica does not apply it again. State
+    /// machines that do not persist their state can keep the default of 0.
+    fn applied_index(&self) -> usize {
+        0
+    }
+
+    /// This is a hook that the Leader calls right after applying a transition
+    /// it appended to its log, with the timeline of that transition. Only
+    /// transitions that are still in flight are tracked, so none are reported
+    /// for a transition appended by an earlier Leader or when leadership is
+    /// lost before the transition is applied.
+    fn register_transition_timeline(
+        &mut self,
+        _transition_id: T::TransitionID,
+        _timeline: TransitionTimeline,
+    ) {
+    }
+
+    /// save_hard_state is called whenever the Replica's term or vote changes,
+    /// before the Replica acts on the change. State machines that persist
+    /// their state must durably store both values, so that a restarted
+    /// Replica never votes twice in the same term.
+    fn save_hard_state(&mut self, _term: usize, _voted_for: Option<ReplicaID>) {}
+
+    /// load_hard_state is consulted by the Replica when it is created. It must
+    /// return the term and vote last passed to save_hard_state. The default is
+    /// a fresh Replica's term 0 and no vote.
+    fn load_hard_state(&self) -> (usize, Option<ReplicaID>) {
+        (0, None)
+    }
+
+    /// append_log_entries is called whenever the Replica adds entries to its
+    /// log, before it acknowledges them. The entries follow the ones persisted
+    /// so far, or the snapshot last passed to save_snapshot if none were
+    /// persisted since, unless an entry with the index of the first one is
+    /// persisted already: that entry and the ones after it must then be
+    /// replaced. Compaction (see create_snapshot) does not drop persisted
+    /// entries.
+    fn append_log_entries(&mut self, _entries: &[LogEntry<T>]) {}
+
+    /// read_log is consulted by the Replica when it is created. It must
+    /// return the entries persisted with append_log_entries, in order,
+    /// starting right after the snapshot returned by read_snapshot, or at
+    /// index 1 if there is none. The default is an empty log.
+    fn read_log(&self) -> Vec<LogEntry<T>> {
+        Vec::new()
+    }
+
+    /// save_snapshot is called whenever the Replica installs a snapshot sent
+    /// by the Leader, before it acknowledges it. State machines that persist
+    /// their log must durably store the snapshot and drop every persisted
+    /// entry: the entries up to last_included_index are part of the snapshot,
+    /// and the Replica appends the ones it keeps past it again.
+    fn save_snapshot(&mut self, _snapshot: &Snapshot) {}
+
+    /// read_snapshot is consulted by the Replica when it is created. It must
+    /// return the snapshot last passed to save_snapshot, which the log read
+    /// with read_log follows. If applied_index is below the snapshot's
+    /// last_included_index, the Replica loads the snapshot with load_snapshot
+    /// first. The default is no snapshot.
+    fn read_snapshot(&self) -> Option<Snapshot> {
+        None
+    }
+
+    /// should_step_down is checked by the Leader every time it wakes up, at
+    /// least once per heartbeat. Return true to make it hand leadership to
+    /// another Replica, for example because this node is being drained. Keep
+    /// returning true for as long as this Replica should not lead, since it
+    /// may win a later election. The default never steps down.
+    fn should_step_down(&self) -> bool {
+        false
+    }
+
+    /// create_snapshot is called by Replicas that compact their log (see
+    /// Replica::set_snapshot_delta) to serialize the state machine with every
+    /// transition applied so far. Returning None postpones the compaction.
+    /// The default never creates snapshots.
+    fn create_snapshot(&mut self) -> Option<Vec<u8>> {
+        None
+    }
+
+    /// load_snapshot replaces the state of the state machine with a snapshot
+    /// that the Leader sent because this Replica fell behind its compacted
+    /// log. Afterwards, applied_index must return the snapshot's
+    /// last_included_index. If the snapshot cannot be loaded, return
+    /// ApplyError::Fatal to halt the Replica. State machines that create
+    /// snapshots must implement it.
+    fn load_snapshot(&mut self, _snapshot: &Snapshot) -> Result<(), ApplyError> {
+        Err(ApplyError::Fatal(
+            "the state machine cannot load snapshots".to_string(),
+        ))
+    }
+
+    /// after_snapshot_install is called once the Replica finished installing a
+    /// snapshot sent by the Leader: the snapshot is loaded and saved, and the
+    /// log follows it. Unlike transitions, which reach the state machine one
+    /// at a time through apply_transition, a snapshot replaces the whole state
+    /// at once, so state derived from it outside of the snapshot, such as
+    /// secondary indexes, is stale until rebuilt here. It is not called for
+    /// the snapshot loaded when the Replica is created. The default does
+    /// nothing.
+    fn after_snapshot_install(&mut self, _meta: SnapshotMeta) {}
+
+    /// drain_committed_entries is called when Replica::start returns because
+    /// the Cluster halted or the Replica was shut down while committed entries
+    /// were not applied yet, for example because applying was paused. Return
+    /// true once the entries are handed off, say to a durable sink from which
+    /// a replacement process applies them. Return false to have the Replica
+    /// apply them before start returns, which is the default.
+    fn drain_committed_entries(&mut self, _entries: &[LogEntry<T>]) -> bool {
+        false
+    }
+
+    /// validate_transition is called by the Leader on every pending transition
+    /// before appending it to its log. Return an error to reject the
+    /// transition instead: it is reported as TransitionState::Rejected and
+    /// takes no log slot. The default accepts every transition.
+    fn validate_transition(&self, _transition: &T) -> Result<(), TransitionRejectedReason> {
+        Ok(())
+    }
+
     /// This function is used to receive transitions from the user that need to
     /// be applied to the replicated state machine. Note that only the Leader
     /// Replica processes transitions and only when notified via the
@@ -1,35 +1,41 @@
-use crossbeam::channel::{bounded, Receiver};
-use std::{thread, time::Duration};
+use crate::clock::Clock;
+use crossbeam::channel::Receiver;
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 pub struct Timer {
-    rx: Receiver<()>,
+    rx: Receiver<Instant>,
     timeout: Duration,
+    clock: Arc<dyn Clock>,
 }
 
-// Timer fires after the specified duration. The timer can be renewed.
+// Timer fires after the specified duration of the clock. The timer can be
+// renewed.
 impl Timer {
-    pub fn new(timeout: Duration) -> Timer {
+    pub fn new(timeout: Duration, clock: Arc<dyn Clock>) -> Timer {
         Timer {
-            timeout: timeout,
-            rx: Timer::get_timeout_channel(timeout),
+            timeout,
+            rx: clock.at(clock.now() + timeout),
+            clock,
         }
     }
 
     pub fn renew(&mut self) {
-        self.rx = Timer::get_timeout_channel(self.timeout);
+        self.rx = self.clock.at(self.clock.now() + self.timeout);
     }
 
-    pub fn get_rx(&self) -> &Receiver<()> {
+    pub fn get_rx(&self) -> &Receiver<Instant> {
         &self.rx
     }
 
-    fn get_timeout_channel(timeout: Duration) -> Receiver<()> {
-        let (tx, rx) = bounded(1);
-        thread::spawn(move || {
-            thread::sleep(timeout);
-            let _ = tx.send(());
-        });
+    // The new timeout applies from the next renewal on.
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
 
-        rx
+    pub fn get_timeout(&self) -> Duration {
+        self.timeout
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,89 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, NO_ELECTION_TIMEOUT};
+use little_raft::message::{LogEntry, Message};
+use std::time::{Duration, Instant};
+
+#[test]
+fn lagging_follower_catches_up_in_capped_batches() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_max_entries_per_append(4);
+
+    // Replica 0 holds ten entries from the previous Leader, then wins the
+    // next term and appends its no-op at index 11.
+    replica.handle_message(Message::VoteRequest {
+        from_id: 1,
+        term: 1,
+        last_log_index: 0,
+        last_log_term: 0,
+    });
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: (1..=10)
+            .map(|index| LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: 1,
+                },
+                index,
+                term: 1,
+                config: None,
+            })
+            .collect(),
+        commit_index: 0,
+        timestamp: None,
+    });
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 2,
+        vote_granted: true,
+    });
+    assert!(replica.is_leader());
+
+    // Replica 2 has nothing, so it needs every entry from index 1.
+    replica.handle_message(Message::AppendEntryResponse {
+        from_id: 2,
+        term: 2,
+        success: false,
+        last_index: 0,
+        mismatch_index: Some(10),
+        content_hash: None,
+        request_timestamp: None,
+    });
+    let mut outbound = replica.handle_timeout(Instant::now() + Duration::from_secs(1));
+
+    // Each acknowledgement brings the next batch right away.
+    let mut batches = Vec::new();
+    while let Some((_, message)) = outbound.into_iter().find(|(to_id, _)| *to_id == 2) {
+        let (prev_log_index, entries) = match message {
+            Message::AppendEntryRequest {
+                prev_log_index,
+                entries,
+                ..
+            } => (prev_log_index, entries),
+            message => panic!("unexpected message {:?}", message),
+        };
+        let indexes: Vec<usize> = entries.iter().map(|entry| entry.index).collect();
+        batches.push(indexes);
+        outbound = replica.handle_message(Message::AppendEntryResponse {
+            from_id: 2,
+            term: 2,
+            success: true,
+            last_index: prev_log_index + entries.len(),
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        });
+    }
+    assert_eq!(
+        batches,
+        vec![vec![1, 2, 3, 4], vec![5, 6, 7, 8], vec![9, 10, 11]]
+    );
+
+    // Replica 2's acknowledgement of the last batch commits the no-op.
+    assert_eq!(replica.handle().commit_index(), 11);
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::{new_replica, settle, Harness, ELECTION_TIMEOUT};
+use little_raft::message::Message;
+
+#[test]
+fn vote_requests_go_out_in_one_broadcast() {
+    let harness = Harness::new(0, vec![1, 2], ELECTION_TIMEOUT);
+    harness.elect(&[1, 2]);
+    assert_eq!(
+        harness.cluster.lock().unwrap().broadcasts.first(),
+        Some(&vec![1, 2])
+    );
+    harness.stop();
+}
+
+#[test]
+fn heartbeats_go_out_in_one_broadcast() {
+    let harness = Harness::new(0, vec![1, 2], ELECTION_TIMEOUT);
+    harness.elect(&[1, 2]);
+    {
+        let mut cluster = harness.cluster.lock().unwrap();
+        cluster.broadcasts.clear();
+        cluster.sent_messages.clear();
+    }
+    settle();
+
+    // Each round of AppendEntryRequests is a single broadcast.
+    let cluster = harness.cluster.lock().unwrap();
+    let appends = cluster
+        .sent_messages
+        .iter()
+        .filter(|(_, message)| matches!(message, Message::AppendEntryRequest { .. }))
+        .count();
+    let broadcasts = cluster.broadcasts.clone();
+    drop(cluster);
+    assert!(appends > 0);
+    assert_eq!(broadcasts, vec![vec![1, 2]; appends / 2]);
+    harness.stop();
+}
+
+#[test]
+fn broadcasts_skip_partitioned_peers() {
+    let (mut replica, cluster, state_machine) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    replica.set_partition(vec![2].into_iter().collect());
+    let harness = Harness::start(replica, cluster, state_machine);
+    while harness.cluster.lock().unwrap().broadcasts.is_empty() {
+        settle();
+    }
+    assert_eq!(harness.cluster.lock().unwrap().broadcasts[0], vec![1]);
+    harness.stop();
+}
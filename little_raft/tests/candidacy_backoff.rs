@@ -0,0 +1,58 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, ELECTION_TIMEOUT};
+use little_raft::message::Message;
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+const BACKOFF: (Duration, Duration) = (Duration::from_millis(400), Duration::from_millis(500));
+
+// Wait for the next VoteRequest and return its term.
+fn next_vote_request(harness: &Harness) -> usize {
+    loop {
+        for (_, message) in harness.take_sent() {
+            if let Message::VoteRequest { term, .. } = message {
+                return term;
+            }
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+fn competing_vote_request(term: usize) -> Message<ArithmeticOperation> {
+    Message::VoteRequest {
+        from_id: 1,
+        term,
+        last_log_index: 0,
+        last_log_term: 0,
+    }
+}
+
+fn time_to_next_candidacy(competing: bool) -> Duration {
+    let (mut replica, cluster, state_machine) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    replica.set_candidacy_backoff(BACKOFF);
+    let harness = Harness::start(replica, cluster, state_machine);
+
+    let term = next_vote_request(&harness);
+    let started = Instant::now();
+    if competing {
+        harness.deliver(vec![competing_vote_request(term)]);
+    }
+    assert_eq!(next_vote_request(&harness), term + 1);
+    let elapsed = started.elapsed();
+
+    harness.stop();
+    elapsed
+}
+
+#[test]
+fn split_vote_backs_off() {
+    assert!(time_to_next_candidacy(true) >= ELECTION_TIMEOUT.0 + BACKOFF.0);
+}
+
+#[test]
+fn uncontested_candidate_does_not_back_off() {
+    assert!(time_to_next_candidacy(false) < BACKOFF.0);
+}
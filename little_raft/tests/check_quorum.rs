@@ -0,0 +1,95 @@
+mod common;
+
+use common::new_replica;
+use little_raft::message::Message;
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+const ELECTION_TIMEOUT: (Duration, Duration) =
+    (Duration::from_millis(500), Duration::from_millis(1000));
+
+#[test]
+fn partitioned_leader_steps_down() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    replica.set_check_quorum(true);
+    replica.handle_timeout(Instant::now() + ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    for peer_id in 1..=2 {
+        replica.handle_message(Message::AppendEntryResponse {
+            from_id: peer_id,
+            term: 1,
+            success: true,
+            last_index: 1,
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        });
+    }
+    let acknowledged = Instant::now();
+    assert!(replica.is_leader());
+
+    // Nothing reaches the followers anymore, and they stop answering. The
+    // Leader holds on while their acknowledgements are recent enough.
+    replica.set_partition(vec![1, 2].into_iter().collect::<HashSet<_>>());
+    replica.handle_timeout(acknowledged + ELECTION_TIMEOUT.0 / 2);
+    assert!(replica.is_leader());
+
+    replica.handle_timeout(acknowledged + ELECTION_TIMEOUT.0);
+    assert!(!replica.is_leader());
+    assert_eq!(replica.leader_id(), None);
+    assert_eq!(replica.current_term(), 1);
+}
+
+#[test]
+fn leader_that_steps_down_keeps_its_vote() {
+    let (mut replica, _, state_machine) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    replica.set_check_quorum(true);
+    replica.handle_timeout(Instant::now() + ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    assert!(replica.is_leader());
+    replica.handle_timeout(Instant::now() + ELECTION_TIMEOUT.1 * 2);
+    assert!(!replica.is_leader());
+    assert_eq!(state_machine.lock().unwrap().hard_state, (1, Some(0)));
+
+    // The Replica voted for itself in term 1, so another Candidate of the
+    // same term does not get its vote.
+    let outbound = replica.handle_message(Message::VoteRequest {
+        from_id: 2,
+        term: 1,
+        last_log_index: 1,
+        last_log_term: 1,
+    });
+    assert!(matches!(
+        outbound.as_slice(),
+        [(
+            2,
+            Message::VoteResponse {
+                vote_granted: false,
+                ..
+            }
+        )]
+    ));
+}
+
+#[test]
+fn leader_without_check_quorum_holds_on() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    replica.handle_timeout(Instant::now() + ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    replica.handle_timeout(Instant::now() + ELECTION_TIMEOUT.1 * 10);
+    assert!(replica.is_leader());
+}
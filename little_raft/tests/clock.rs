@@ -0,0 +1,57 @@
+mod common;
+
+use common::{
+    new_replica, settle, ArithmeticOperation, Harness, ELECTION_TIMEOUT, HEARTBEAT_TIMEOUT,
+};
+use little_raft::{clock::ManualClock, message::Message};
+use std::sync::Arc;
+
+fn count_sent<F>(harness: &Harness, predicate: F) -> usize
+where
+    F: Fn(&Message<ArithmeticOperation>) -> bool,
+{
+    harness
+        .take_sent()
+        .iter()
+        .filter(|(_, message)| predicate(message))
+        .count()
+}
+
+#[test]
+fn replica_follows_a_manual_clock() {
+    let clock = ManualClock::new();
+    let (mut replica, cluster, state_machine) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    replica.set_clock(Arc::new(clock.clone()));
+    let harness = Harness::start(replica, cluster, state_machine);
+    let is_vote_request = |message: &Message<_>| matches!(message, Message::VoteRequest { .. });
+    let is_heartbeat = |message: &Message<_>| matches!(message, Message::AppendEntryRequest { .. });
+
+    // The election timeout passes on the system clock, but not on the
+    // Replica's.
+    settle();
+    settle();
+    assert_eq!(count_sent(&harness, is_vote_request), 0);
+
+    clock.advance(ELECTION_TIMEOUT.1);
+    settle();
+    assert_eq!(count_sent(&harness, is_vote_request), 2);
+
+    // The same goes for the Leader's heartbeats.
+    harness.deliver(
+        (1..=2)
+            .map(|peer_id| Message::VoteResponse {
+                from_id: peer_id,
+                term: 1,
+                vote_granted: true,
+            })
+            .collect(),
+    );
+    assert_eq!(count_sent(&harness, is_heartbeat), 2);
+    settle();
+    assert_eq!(count_sent(&harness, is_heartbeat), 0);
+
+    clock.advance(HEARTBEAT_TIMEOUT);
+    settle();
+    assert_eq!(count_sent(&harness, is_heartbeat), 2);
+    harness.stop();
+}
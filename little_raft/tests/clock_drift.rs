@@ -0,0 +1,37 @@
+mod common;
+
+use common::{new_replica, Harness, NO_ELECTION_TIMEOUT};
+use little_raft::message::Message;
+use std::time::{Duration, SystemTime};
+
+fn heartbeat(timestamp: SystemTime) -> Message<common::ArithmeticOperation> {
+    Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: Vec::new(),
+        commit_index: 0,
+        timestamp: Some(timestamp),
+    }
+}
+
+#[test]
+fn skewed_leader_clock_is_reported() {
+    let (mut replica, cluster, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_max_clock_drift(Duration::from_secs(1));
+    let harness = Harness::start(replica, cluster, state_machine);
+
+    // The Leader's clock is in sync, give or take network latency.
+    harness.deliver(vec![heartbeat(SystemTime::now())]);
+    assert!(harness.cluster.lock().unwrap().clock_drifts.is_empty());
+
+    // The Leader's clock runs ten seconds late.
+    harness.deliver(vec![heartbeat(SystemTime::now() - Duration::from_secs(10))]);
+    let clock_drifts = harness.cluster.lock().unwrap().clock_drifts.clone();
+    assert_eq!(clock_drifts.len(), 1);
+    assert_eq!(clock_drifts[0].0, 1);
+    assert!(clock_drifts[0].1 >= Duration::from_secs(10));
+
+    harness.stop();
+}
@@ -0,0 +1,213 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Calculator, TestReplica, NO_ELECTION_TIMEOUT};
+use little_raft::message::{LogEntry, Message};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+const OLD_TERM: usize = 5;
+const OLD_ENTRIES: usize = 10;
+const NEW_ENTRIES: usize = 30;
+
+// Make the Replica the Leader of the term after OLD_TERM, holding OLD_ENTRIES
+// entries of OLD_TERM followed by its no-op and NEW_ENTRIES entries of its own
+// term, none of them replicated. Returns the Leader's term.
+fn elect_with_log(
+    replica: &mut TestReplica,
+    state_machine: &Arc<Mutex<Calculator>>,
+    peer_ids: &[usize],
+) -> usize {
+    replica.set_confirm_leadership_before_writes(true);
+    replica.handle_message(Message::VoteRequest {
+        from_id: peer_ids[0],
+        term: OLD_TERM,
+        last_log_index: 0,
+        last_log_term: 0,
+    });
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: peer_ids[0],
+        term: OLD_TERM,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: (1..=OLD_ENTRIES)
+            .map(|index| LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: 1,
+                },
+                index,
+                term: OLD_TERM,
+                config: None,
+            })
+            .collect(),
+        commit_index: 0,
+        timestamp: None,
+    });
+
+    let term = OLD_TERM + 1;
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    for peer_id in peer_ids {
+        replica.handle_message(Message::VoteResponse {
+            from_id: *peer_id,
+            term,
+            vote_granted: true,
+        });
+    }
+
+    // The transitions are appended once the peers acknowledge the Leader,
+    // which they do without acknowledging any entry.
+    state_machine.lock().unwrap().pending_transitions = (1..=NEW_ENTRIES)
+        .map(|i| ArithmeticOperation {
+            id: OLD_ENTRIES + i,
+            delta: 1,
+        })
+        .collect();
+    for peer_id in peer_ids {
+        replica.handle_message(Message::AppendEntryResponse {
+            from_id: *peer_id,
+            term,
+            success: false,
+            last_index: 0,
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        });
+    }
+    term
+}
+
+// The commit index as the Raft paper defines it: the highest index of the
+// current term that a majority of the cluster holds.
+fn naive_commit_index(match_index: &BTreeMap<usize, usize>, commit_index: usize) -> usize {
+    let last_index = OLD_ENTRIES + 1 + NEW_ENTRIES;
+    let mut n = last_index;
+    while n > commit_index {
+        let replications = match_index.values().filter(|index| **index >= n).count();
+        if (replications + 1) * 2 > match_index.len() + 1 && n > OLD_ENTRIES {
+            return n;
+        }
+        n -= 1;
+    }
+    commit_index
+}
+
+#[test]
+fn commit_index_matches_the_naive_scan() {
+    for cluster_size in 1..=7 {
+        for seed in 0..10 {
+            let peer_ids: Vec<usize> = (1..cluster_size).collect();
+            let (mut replica, _, state_machine) =
+                new_replica(0, peer_ids.clone(), NO_ELECTION_TIMEOUT);
+            let handle = replica.handle();
+            if peer_ids.is_empty() {
+                // A single Replica commits everything on its own.
+                state_machine.lock().unwrap().pending_transitions =
+                    vec![ArithmeticOperation { id: 1, delta: 1 }];
+                replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+                assert_eq!(handle.commit_index(), 1);
+                continue;
+            }
+            let term = elect_with_log(&mut replica, &state_machine, &peer_ids);
+
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mut match_index: BTreeMap<usize, usize> =
+                peer_ids.iter().map(|peer_id| (*peer_id, 0)).collect();
+            let mut commit_index = 0;
+            for _ in 0..50 {
+                let peer_id = peer_ids[rng.gen_range(0..peer_ids.len())];
+                let last_index =
+                    rng.gen_range(match_index[&peer_id]..=OLD_ENTRIES + 1 + NEW_ENTRIES);
+                match_index.insert(peer_id, last_index);
+                replica.handle_message(Message::AppendEntryResponse {
+                    from_id: peer_id,
+                    term,
+                    success: true,
+                    last_index,
+                    mismatch_index: None,
+                    content_hash: None,
+                    request_timestamp: None,
+                });
+
+                commit_index = naive_commit_index(&match_index, commit_index);
+                assert_eq!(
+                    handle.commit_index(),
+                    commit_index,
+                    "cluster of {} with seed {}",
+                    cluster_size,
+                    seed
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn even_clusters_need_a_strict_majority_to_commit() {
+    let last_index = OLD_ENTRIES + 1 + NEW_ENTRIES;
+    for (total_nodes, needed_peers) in [(2, 1), (4, 2), (6, 3)] {
+        let peer_ids: Vec<usize> = (1..total_nodes).collect();
+        let (mut replica, _, state_machine) = new_replica(0, peer_ids.clone(), NO_ELECTION_TIMEOUT);
+        let term = elect_with_log(&mut replica, &state_machine, &peer_ids);
+
+        // With half of the cluster holding the log, the other half, which may
+        // have elected another Leader, can still overwrite it.
+        for (i, peer_id) in peer_ids.iter().enumerate() {
+            replica.handle_message(Message::AppendEntryResponse {
+                from_id: *peer_id,
+                term,
+                success: true,
+                last_index,
+                mismatch_index: None,
+                content_hash: None,
+                request_timestamp: None,
+            });
+            let acks = i + 1;
+            let expected = if acks < needed_peers { 0 } else { last_index };
+            assert_eq!(
+                replica.handle().commit_index(),
+                expected,
+                "cluster of {} after {} acknowledgements",
+                total_nodes,
+                acks
+            );
+        }
+    }
+}
+
+#[test]
+fn is_committed_covers_compacted_entries() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_snapshot_delta(2);
+    let append = |prev_log_index, commit_index| Message::AppendEntryRequest {
+        from_id: 1,
+        term: 0,
+        prev_log_index,
+        prev_log_term: 0,
+        entries: (prev_log_index + 1..=4)
+            .map(|index| LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: 1,
+                },
+                index,
+                term: 0,
+                config: None,
+            })
+            .collect(),
+        commit_index,
+        timestamp: None,
+    };
+    replica.handle_message(append(0, 3));
+    assert_eq!(replica.commit_index(), 3);
+    assert_eq!(replica.snapshot().unwrap().last_included_index, 3);
+    assert!((0..=3).all(|index| replica.is_committed(index)));
+    assert!(!replica.is_committed(4));
+    assert!(!replica.is_committed(5));
+
+    replica.handle_message(append(3, 4));
+    assert!(replica.is_committed(4));
+}
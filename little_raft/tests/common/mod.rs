@@ -0,0 +1,560 @@
+// Helpers shared by the tests that exercise a single Replica. The test plays
+// the role of every other Replica in the cluster: it decides which messages
+// the Replica under test receives and inspects every message it sends.
+#![allow(dead_code)]
+
+use crossbeam_channel::{unbounded, Sender};
+use little_raft::{
+    cluster::Cluster,
+    message::{LogEntry, Message, MessagePriority},
+    replica::{RaftError, Replica, ReplicaID},
+    state_machine::{
+        ApplyError, ClientId, Snapshot, SnapshotMeta, StateMachine, StateMachineTransition,
+        TransitionRejectedReason, TransitionState, TransitionTimeline,
+    },
+};
+use std::sync::{Arc, Mutex};
+use std::{
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+pub const HEARTBEAT_TIMEOUT: Duration = Duration::from_millis(50);
+pub const ELECTION_TIMEOUT: (Duration, Duration) =
+    (Duration::from_millis(150), Duration::from_millis(200));
+
+// Election timeout long enough for a follower to never start an election
+// during a test.
+pub const NO_ELECTION_TIMEOUT: (Duration, Duration) =
+    (Duration::from_secs(60), Duration::from_secs(60));
+
+// How long to give the Replica to react to whatever the test did.
+pub const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+// ID of the fake peer that wakes the Replica up when the harness starts it.
+pub const GREETER_ID: ReplicaID = usize::MAX;
+
+pub const NOOP: ArithmeticOperation = ArithmeticOperation { id: 0, delta: 0 };
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArithmeticOperation {
+    pub id: usize,
+    pub delta: i32,
+}
+
+// Operations with an ID of at least CLIENT_ID_BASE are tagged for
+// deduplication, with the quotient of the ID by CLIENT_ID_BASE as the client
+// and the remainder as the sequence number.
+pub const CLIENT_ID_BASE: usize = 1_000_000;
+
+// In the legacy format, deltas are expressed in thousandths.
+pub const LEGACY_FORMAT: u32 = 1;
+
+impl StateMachineTransition for ArithmeticOperation {
+    type TransitionID = usize;
+    fn get_id(&self) -> Self::TransitionID {
+        self.id
+    }
+
+    fn for_format(&self, format: u32) -> Self {
+        match format {
+            LEGACY_FORMAT => ArithmeticOperation {
+                id: self.id,
+                delta: self.delta * 1000,
+            },
+            _ => self.clone(),
+        }
+    }
+
+    fn dedup_key(&self) -> Option<(ClientId, u64)> {
+        match self.id >= CLIENT_ID_BASE {
+            true => Some((
+                (self.id / CLIENT_ID_BASE) as ClientId,
+                (self.id % CLIENT_ID_BASE) as u64,
+            )),
+            false => None,
+        }
+    }
+}
+
+// Calculator records everything the Replica tells it about.
+#[derive(Default)]
+pub struct Calculator {
+    pub value: i32,
+    pub pending_transitions: Vec<ArithmeticOperation>,
+    pub applied_ids: Vec<usize>,
+    pub transition_states: Vec<(usize, TransitionState)>,
+    pub timelines: Vec<(usize, TransitionTimeline)>,
+    pub applied_index: usize,
+    // Index at which the Calculator crashes before applying anything.
+    pub crash_at_index: Option<usize>,
+    // IDs of the transitions the Calculator fails to apply.
+    pub fatal_ids: Vec<usize>,
+    // IDs of the transitions the Calculator rejects as invalid.
+    pub invalid_ids: Vec<usize>,
+    pub loaded_snapshots: Vec<Snapshot>,
+    // Snapshots installed from the Leader, as reported once installed.
+    pub installed_snapshots: Vec<SnapshotMeta>,
+    pub hard_state: (usize, Option<ReplicaID>),
+    pub persisted_log: Vec<LogEntry<ArithmeticOperation>>,
+    // Snapshot installed from the Leader, which persisted_log follows.
+    pub saved_snapshot: Option<Snapshot>,
+    // Number of times the Replica persisted entries.
+    pub log_appends: usize,
+    pub step_down: bool,
+    // Where the Calculator drains the entries left unapplied at shutdown, if
+    // anywhere. Otherwise, the Replica applies them.
+    pub drain_sink: Option<Vec<LogEntry<ArithmeticOperation>>>,
+}
+
+impl StateMachine<ArithmeticOperation> for Calculator {
+    fn apply_transition(&mut self, transition: ArithmeticOperation) {
+        self.value += transition.delta;
+        self.applied_ids.push(transition.id);
+    }
+
+    fn try_apply_transition(&mut self, transition: ArithmeticOperation) -> Result<(), ApplyError> {
+        if self.fatal_ids.contains(&transition.id) {
+            return Err(ApplyError::Fatal(format!("cannot apply {}", transition.id)));
+        }
+        self.apply_transition(transition);
+        Ok(())
+    }
+
+    fn apply_and_record(
+        &mut self,
+        transition: ArithmeticOperation,
+        index: usize,
+    ) -> Result<(), ApplyError> {
+        if self.crash_at_index == Some(index) {
+            panic!("crash injected at index {}", index);
+        }
+        self.try_apply_transition(transition)?;
+        self.applied_index = index;
+        Ok(())
+    }
+
+    fn validate_transition(
+        &self,
+        transition: &ArithmeticOperation,
+    ) -> Result<(), TransitionRejectedReason> {
+        if self.invalid_ids.contains(&transition.id) {
+            return Err(TransitionRejectedReason::Invalid(format!(
+                "{} is invalid",
+                transition.id
+            )));
+        }
+        Ok(())
+    }
+
+    fn register_transition_state(&mut self, transition_id: usize, state: TransitionState) {
+        self.transition_states.push((transition_id, state));
+    }
+
+    fn register_transition_timeline(&mut self, transition_id: usize, timeline: TransitionTimeline) {
+        self.timelines.push((transition_id, timeline));
+    }
+
+    fn save_hard_state(&mut self, term: usize, voted_for: Option<ReplicaID>) {
+        self.hard_state = (term, voted_for);
+    }
+
+    fn load_hard_state(&self) -> (usize, Option<ReplicaID>) {
+        self.hard_state
+    }
+
+    fn append_log_entries(&mut self, entries: &[LogEntry<ArithmeticOperation>]) {
+        if let Some(first) = entries.first() {
+            self.log_appends += 1;
+            let offset = self
+                .saved_snapshot
+                .as_ref()
+                .map_or(0, |snapshot| snapshot.last_included_index);
+            self.persisted_log.truncate(first.index - offset - 1);
+            self.persisted_log.extend_from_slice(entries);
+        }
+    }
+
+    fn read_log(&self) -> Vec<LogEntry<ArithmeticOperation>> {
+        self.persisted_log.clone()
+    }
+
+    fn save_snapshot(&mut self, snapshot: &Snapshot) {
+        self.saved_snapshot = Some(snapshot.clone());
+        self.persisted_log.clear();
+    }
+
+    fn read_snapshot(&self) -> Option<Snapshot> {
+        self.saved_snapshot.clone()
+    }
+
+    fn should_step_down(&self) -> bool {
+        self.step_down
+    }
+
+    fn create_snapshot(&mut self) -> Option<Vec<u8>> {
+        Some(self.value.to_le_bytes().to_vec())
+    }
+
+    fn load_snapshot(&mut self, snapshot: &Snapshot) -> Result<(), ApplyError> {
+        let mut value = [0; 4];
+        if snapshot.data.len() != value.len() {
+            return Err(ApplyError::Fatal(format!(
+                "cannot load {} bytes",
+                snapshot.data.len()
+            )));
+        }
+        value.copy_from_slice(&snapshot.data);
+        self.value = i32::from_le_bytes(value);
+        self.applied_index = snapshot.last_included_index;
+        self.loaded_snapshots.push(snapshot.clone());
+        Ok(())
+    }
+
+    fn after_snapshot_install(&mut self, meta: SnapshotMeta) {
+        self.installed_snapshots.push(meta);
+    }
+
+    fn drain_committed_entries(&mut self, entries: &[LogEntry<ArithmeticOperation>]) -> bool {
+        match &mut self.drain_sink {
+            Some(drain_sink) => {
+                drain_sink.extend_from_slice(entries);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn get_pending_transitions(&mut self) -> Vec<ArithmeticOperation> {
+        std::mem::take(&mut self.pending_transitions)
+    }
+
+    fn applied_index(&self) -> usize {
+        self.applied_index
+    }
+}
+
+// ScriptedCluster hands the Replica whatever the test delivered and keeps
+// every message the Replica sent until the test takes it.
+#[derive(Default)]
+pub struct ScriptedCluster {
+    pub leader_id: Option<ReplicaID>,
+    pub pending_messages: Vec<Message<ArithmeticOperation>>,
+    pub sent_messages: Vec<(ReplicaID, Message<ArithmeticOperation>)>,
+    // Priority of each message in sent_messages.
+    pub sent_priorities: Vec<MessagePriority>,
+    pub untrusted_ids: Vec<ReplicaID>,
+    pub reset_peers: Vec<ReplicaID>,
+    pub clock_drifts: Vec<(ReplicaID, Duration)>,
+    // Membership changes reported for reducing the fault tolerance, with
+    // whether they were rejected.
+    pub reduced_fault_tolerance: Vec<(Vec<ReplicaID>, usize, bool)>,
+    pub peer_formats: Vec<(ReplicaID, u32)>,
+    // Peers of each broadcast, whose messages are also in sent_messages.
+    pub broadcasts: Vec<Vec<ReplicaID>>,
+    pub halt: bool,
+}
+
+impl Cluster<ArithmeticOperation> for ScriptedCluster {
+    fn send_message(&mut self, to_id: usize, message: Message<ArithmeticOperation>) {
+        self.sent_messages.push((to_id, message));
+    }
+
+    fn send_message_with_priority(
+        &mut self,
+        to_id: usize,
+        message: Message<ArithmeticOperation>,
+        priority: MessagePriority,
+    ) {
+        self.send_message(to_id, message);
+        self.sent_priorities.push(priority);
+    }
+
+    fn broadcast<F>(&mut self, peer_ids: &[ReplicaID], message_for: F)
+    where
+        F: Fn(ReplicaID) -> Message<ArithmeticOperation>,
+    {
+        self.broadcasts.push(peer_ids.to_vec());
+        for peer_id in peer_ids {
+            let message = message_for(*peer_id);
+            let priority = message.priority();
+            self.send_message_with_priority(*peer_id, message, priority);
+        }
+    }
+
+    fn receive_messages(&mut self) -> Vec<Message<ArithmeticOperation>> {
+        std::mem::take(&mut self.pending_messages)
+    }
+
+    fn verify_peer(&self, from_id: ReplicaID) -> bool {
+        !self.untrusted_ids.contains(&from_id)
+    }
+
+    fn peer_format(&self, peer_id: ReplicaID) -> Option<u32> {
+        self.peer_formats
+            .iter()
+            .find(|(id, _)| *id == peer_id)
+            .map(|(_, format)| *format)
+    }
+
+    fn on_peer_reset(&mut self, peer_id: ReplicaID) {
+        self.reset_peers.push(peer_id);
+    }
+
+    fn register_clock_drift(&mut self, leader_id: ReplicaID, drift: Duration) {
+        self.clock_drifts.push((leader_id, drift));
+    }
+
+    fn register_reduced_fault_tolerance(
+        &mut self,
+        members: &[ReplicaID],
+        tolerated_failures: usize,
+        rejected: bool,
+    ) {
+        self.reduced_fault_tolerance
+            .push((members.to_vec(), tolerated_failures, rejected));
+    }
+
+    fn halt(&self) -> bool {
+        self.halt
+    }
+
+    fn register_leader(&mut self, leader_id: Option<ReplicaID>) {
+        self.leader_id = leader_id;
+    }
+}
+
+pub type TestReplica = Replica<Calculator, ArithmeticOperation, ScriptedCluster>;
+
+pub fn new_replica(
+    id: ReplicaID,
+    peer_ids: Vec<ReplicaID>,
+    election_timeout: (Duration, Duration),
+) -> (
+    TestReplica,
+    Arc<Mutex<ScriptedCluster>>,
+    Arc<Mutex<Calculator>>,
+) {
+    let cluster = Arc::new(Mutex::new(ScriptedCluster::default()));
+    let state_machine = Arc::new(Mutex::new(Calculator::default()));
+    let replica = Replica::new(
+        id,
+        peer_ids,
+        cluster.clone(),
+        state_machine.clone(),
+        NOOP,
+        HEARTBEAT_TIMEOUT,
+        election_timeout,
+    );
+
+    (replica, cluster, state_machine)
+}
+
+// Harness runs a Replica on its own thread and lets the test talk to it.
+pub struct Harness {
+    pub cluster: Arc<Mutex<ScriptedCluster>>,
+    pub state_machine: Arc<Mutex<Calculator>>,
+    message_tx: Sender<()>,
+    transition_tx: Sender<()>,
+    handle: JoinHandle<(TestReplica, Result<(), RaftError>)>,
+}
+
+impl Harness {
+    pub fn new(
+        id: ReplicaID,
+        peer_ids: Vec<ReplicaID>,
+        election_timeout: (Duration, Duration),
+    ) -> Harness {
+        let (replica, cluster, state_machine) = new_replica(id, peer_ids, election_timeout);
+        Harness::start(replica, cluster, state_machine)
+    }
+
+    pub fn start(
+        mut replica: TestReplica,
+        cluster: Arc<Mutex<ScriptedCluster>>,
+        state_machine: Arc<Mutex<Calculator>>,
+    ) -> Harness {
+        let (message_tx, message_rx) = unbounded();
+        let (transition_tx, transition_rx) = unbounded();
+        cluster.lock().unwrap().halt = false;
+
+        // A fresh Replica starts an election right away unless it hears from
+        // the cluster first. Greet it with a message it ignores so that it
+        // waits for a full election timeout instead.
+        cluster
+            .lock()
+            .unwrap()
+            .pending_messages
+            .push(Message::AppendEntryResponse {
+                from_id: GREETER_ID,
+                term: 0,
+                success: false,
+                last_index: 0,
+                mismatch_index: None,
+                content_hash: None,
+                request_timestamp: None,
+            });
+        message_tx.send(()).unwrap();
+
+        let handle = thread::spawn(move || {
+            let result = replica.start(message_rx, transition_rx);
+            (replica, result)
+        });
+
+        Harness {
+            cluster,
+            state_machine,
+            message_tx,
+            transition_tx,
+            handle,
+        }
+    }
+
+    // Deliver messages to the Replica and give it time to process them.
+    pub fn deliver(&self, messages: Vec<Message<ArithmeticOperation>>) {
+        self.cluster
+            .lock()
+            .unwrap()
+            .pending_messages
+            .extend(messages);
+        let _ = self.message_tx.send(());
+        settle();
+    }
+
+    // Submit transitions to the Replica and give it time to process them.
+    pub fn propose(&self, transitions: Vec<ArithmeticOperation>) {
+        self.submit(transitions);
+        settle();
+    }
+
+    // Submit transitions to the Replica without waiting.
+    pub fn submit(&self, transitions: Vec<ArithmeticOperation>) {
+        self.state_machine
+            .lock()
+            .unwrap()
+            .pending_transitions
+            .extend(transitions);
+        let _ = self.transition_tx.send(());
+    }
+
+    // Take all messages the Replica has sent so far.
+    pub fn take_sent(&self) -> Vec<(ReplicaID, Message<ArithmeticOperation>)> {
+        let mut cluster = self.cluster.lock().unwrap();
+        cluster.sent_priorities.clear();
+        std::mem::take(&mut cluster.sent_messages)
+    }
+
+    pub fn leader_id(&self) -> Option<ReplicaID> {
+        self.cluster.lock().unwrap().leader_id
+    }
+
+    // Wait until the Replica reports the given Leader.
+    pub fn wait_for_leader(&self, leader_id: ReplicaID) {
+        while self.leader_id() != Some(leader_id) {
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    // Wait for the Replica to ask for votes and grant it votes from all peers.
+    // Returns the term the Replica was elected in.
+    pub fn elect(&self, peer_ids: &[ReplicaID]) -> usize {
+        let term = loop {
+            let vote_request_term = self.take_sent().into_iter().rev().find_map(|(_, m)| {
+                if let Message::VoteRequest { term, .. } = m {
+                    Some(term)
+                } else {
+                    None
+                }
+            });
+            if let Some(term) = vote_request_term {
+                break term;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+
+        self.deliver(
+            peer_ids
+                .iter()
+                .map(|peer_id| Message::VoteResponse {
+                    from_id: *peer_id,
+                    term,
+                    vote_granted: true,
+                })
+                .collect(),
+        );
+        term
+    }
+
+    // Play a peer whose log ends at last_index and matches the Replica's log
+    // up to matching_index. Reject every AppendEntryRequest the peer receives
+    // until one attaches to the matching part of its log, and return the
+    // number of requests that took.
+    pub fn round_trips_to_reach(
+        &self,
+        peer_id: ReplicaID,
+        last_index: usize,
+        matching_index: usize,
+    ) -> usize {
+        let mut round_trips = 0;
+        loop {
+            let request = self
+                .take_sent()
+                .into_iter()
+                .rev()
+                .find_map(|(to_id, message)| match message {
+                    Message::AppendEntryRequest {
+                        term,
+                        prev_log_index,
+                        ..
+                    } if to_id == peer_id => Some((term, prev_log_index)),
+                    _ => None,
+                });
+            let (term, prev_log_index) = match request {
+                Some(request) => request,
+                None => {
+                    settle();
+                    continue;
+                }
+            };
+
+            round_trips += 1;
+            if prev_log_index <= matching_index {
+                return round_trips;
+            }
+            self.deliver(vec![Message::AppendEntryResponse {
+                from_id: peer_id,
+                term,
+                success: false,
+                last_index,
+                mismatch_index: Some(prev_log_index),
+                content_hash: None,
+                request_timestamp: None,
+            }]);
+        }
+    }
+
+    // Whether the Replica stopped running on its own.
+    pub fn halted(&self) -> bool {
+        self.handle.is_finished()
+    }
+
+    // Halt the Replica and hand it back to the test. The Replica can be
+    // started again with Harness::start.
+    pub fn stop(self) -> TestReplica {
+        self.join().expect("replica thread panicked").0
+    }
+
+    // Halt the Replica and report whether its thread panicked, or else what
+    // Replica::start returned.
+    pub fn join(self) -> thread::Result<(TestReplica, Result<(), RaftError>)> {
+        if let Ok(mut cluster) = self.cluster.lock() {
+            cluster.halt = true;
+        }
+        let _ = self.message_tx.send(());
+        self.handle.join()
+    }
+}
+
+pub fn settle() {
+    thread::sleep(SETTLE_TIME);
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::{TestReplica, ELECTION_TIMEOUT, HEARTBEAT_TIMEOUT, NOOP};
+use little_raft::replica::ConfigError;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+fn try_new(
+    heartbeat_timeout: Duration,
+    election_timeout: (Duration, Duration),
+) -> Option<ConfigError> {
+    TestReplica::try_new(
+        0,
+        vec![1, 2],
+        Arc::new(Mutex::new(Default::default())),
+        Arc::new(Mutex::new(Default::default())),
+        NOOP,
+        heartbeat_timeout,
+        election_timeout,
+    )
+    .err()
+}
+
+#[test]
+fn try_new_accepts_sensible_timeouts() {
+    assert_eq!(try_new(HEARTBEAT_TIMEOUT, ELECTION_TIMEOUT), None);
+}
+
+#[test]
+fn try_new_rejects_an_empty_election_timeout_range() {
+    let (min, max) = (ELECTION_TIMEOUT.1, ELECTION_TIMEOUT.0);
+    assert_eq!(
+        try_new(HEARTBEAT_TIMEOUT, (min, max)),
+        Some(ConfigError::EmptyElectionTimeoutRange { min, max })
+    );
+}
+
+#[test]
+fn try_new_rejects_a_heartbeat_timeout_above_the_election_timeout() {
+    for heartbeat_timeout in [ELECTION_TIMEOUT.0, ELECTION_TIMEOUT.1] {
+        assert_eq!(
+            try_new(heartbeat_timeout, ELECTION_TIMEOUT),
+            Some(ConfigError::HeartbeatTimeoutTooLong {
+                heartbeat_timeout,
+                min_election_timeout: ELECTION_TIMEOUT.0,
+            })
+        );
+    }
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::{
+    new_replica, ArithmeticOperation, Harness, TestReplica, ELECTION_TIMEOUT, HEARTBEAT_TIMEOUT,
+    NO_ELECTION_TIMEOUT,
+};
+use little_raft::{
+    message::{LogEntry, Message},
+    replica::ConflictResolution,
+};
+use std::time::Instant;
+
+const LOG_LEN: usize = 32;
+
+#[test]
+fn binary_search_finds_deep_divergence_in_logarithmic_round_trips() {
+    let peer_ids = vec![1, 2];
+    let (mut replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    replica.set_conflict_resolution(ConflictResolution::BinarySearch);
+    let harness = Harness::start(replica, cluster, state_machine);
+
+    // Fill the log of the Replica in the first term, then let it win the
+    // second one.
+    let entries = (1..=LOG_LEN)
+        .map(|index| LogEntry {
+            transition: ArithmeticOperation {
+                id: index,
+                delta: 1,
+            },
+            index,
+            term: 1,
+            config: None,
+        })
+        .collect();
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 2,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries,
+        commit_index: 0,
+        timestamp: None,
+    }]);
+    harness.elect(&peer_ids);
+
+    // Peer 1 has as many entries, but only the first two match. The probes
+    // land on 33, 16, 8, 4 and 2, where walking back one entry at a time
+    // would take 32 round-trips.
+    assert_eq!(harness.round_trips_to_reach(1, LOG_LEN, 2), 5);
+
+    harness.stop();
+}
+
+// Entries from..=to of the given term, as sent by a Leader.
+fn entries(from: usize, to: usize, term: usize) -> Vec<LogEntry<ArithmeticOperation>> {
+    (from..=to)
+        .map(|index| LogEntry {
+            transition: ArithmeticOperation {
+                id: index * 10 + term,
+                delta: 1,
+            },
+            index,
+            term,
+            config: None,
+        })
+        .collect()
+}
+
+fn append(replica: &mut TestReplica, term: usize, entries: Vec<LogEntry<ArithmeticOperation>>) {
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 2,
+        term,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries,
+        commit_index: 0,
+        timestamp: None,
+    });
+}
+
+#[test]
+fn follower_only_acknowledges_entries_it_was_sent() {
+    // The follower holds a long suffix of the first term that the Leader of
+    // the second term never committed.
+    let (mut follower, _, _) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    append(&mut follower, 1, entries(1, 9, 1));
+
+    // The Leader of the third term holds entries 5 and 6 of the second term
+    // instead, and replicates its log one entry at a time.
+    let (mut leader, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    leader.set_max_entries_per_append(1);
+    leader.set_conflict_resolution(ConflictResolution::BinarySearch);
+    let mut log = entries(1, 4, 1);
+    log.extend(entries(5, 6, 2));
+    append(&mut leader, 2, log);
+    leader.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    let term = leader.current_term();
+    let mut outbound = leader.handle_message(Message::VoteResponse {
+        from_id: 2,
+        term,
+        vote_granted: true,
+    });
+    assert_eq!(term, 3);
+
+    // The follower acknowledges the matching prefix the Leader probes, not
+    // its whole log, so the Leader never believes that the follower holds
+    // entries it does not. Rejections are retried on the next heartbeat.
+    let mut now = Instant::now();
+    for _ in 0..20 {
+        if outbound.is_empty() {
+            now += HEARTBEAT_TIMEOUT;
+            outbound = leader.handle_timeout(now);
+        }
+        let requests = outbound.into_iter().filter(|(to_id, _)| *to_id == 1);
+        let responses: Vec<_> = requests
+            .flat_map(|(_, request)| follower.handle_message(request))
+            .collect();
+        outbound = responses
+            .into_iter()
+            .flat_map(|(_, response)| leader.handle_message(response))
+            .collect();
+        let progress = leader.replication_status()[&1];
+        assert!(progress.match_index <= 7);
+        assert!(progress.next_index <= 8);
+    }
+    assert_eq!(leader.replication_status()[&1].match_index, 7);
+    assert_eq!(leader.commit_index(), 7);
+
+    // Once told about the commit, the follower holds the Leader's log.
+    for (_, request) in leader.handle_timeout(now + HEARTBEAT_TIMEOUT) {
+        follower.handle_message(request);
+    }
+    assert_eq!(follower.commit_index(), 7);
+    let indexes = |term| -> Vec<usize> {
+        let entries = follower.entries_in_term(term).entries;
+        entries.iter().map(|entry| entry.index).collect()
+    };
+    assert_eq!(indexes(1), vec![1, 2, 3, 4]);
+    assert_eq!(indexes(2), vec![5, 6]);
+    assert_eq!(indexes(3), vec![7]);
+}
@@ -0,0 +1,66 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, ELECTION_TIMEOUT, NO_ELECTION_TIMEOUT};
+use little_raft::{message::Message, replica::ReplicaID, state_machine::TransitionState};
+
+// Hand the Leader's latest AppendEntryRequest for a follower to a real
+// Replica playing that follower, and return its response. The transport
+// tampers with the request first.
+fn relay<F>(
+    request: &Message<ArithmeticOperation>,
+    follower_id: ReplicaID,
+    tamper: F,
+) -> Message<ArithmeticOperation>
+where
+    F: FnOnce(&mut Message<ArithmeticOperation>),
+{
+    let (mut follower, _, _) = new_replica(follower_id, vec![0, 1, 2], NO_ELECTION_TIMEOUT);
+    follower.set_verify_content_hashes(true);
+    let mut request = request.clone();
+    tamper(&mut request);
+    let mut responses = follower.handle_message(request);
+    assert_eq!(responses.len(), 1);
+    responses.remove(0).1
+}
+
+#[test]
+fn corrupted_copy_does_not_count_toward_commit() {
+    let peer_ids = vec![1, 2];
+    let (mut replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    replica.set_verify_content_hashes(true);
+    let harness = Harness::start(replica, cluster, state_machine.clone());
+    harness.elect(&peer_ids);
+    harness.propose(vec![ArithmeticOperation { id: 1, delta: 1 }]);
+    let request = harness
+        .take_sent()
+        .into_iter()
+        .rev()
+        .find_map(|(_, message)| match message {
+            Message::AppendEntryRequest { ref entries, .. } if entries.len() == 2 => Some(message),
+            _ => None,
+        })
+        .unwrap();
+
+    // Peer 1 receives a different delta. Its acknowledgement is ignored.
+    harness.deliver(vec![relay(&request, 1, |request| {
+        if let Message::AppendEntryRequest { entries, .. } = request {
+            entries[1].transition.delta = 100;
+        }
+    })]);
+    let committed = (1, TransitionState::Committed);
+    assert!(!state_machine
+        .lock()
+        .unwrap()
+        .transition_states
+        .contains(&committed));
+
+    // Peer 2 receives the right copy, which commits the transition.
+    harness.deliver(vec![relay(&request, 2, |_| {})]);
+    assert!(state_machine
+        .lock()
+        .unwrap()
+        .transition_states
+        .contains(&committed));
+
+    harness.stop();
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use common::{
+    new_replica, ArithmeticOperation, TestReplica, HEARTBEAT_TIMEOUT, NO_ELECTION_TIMEOUT,
+};
+use little_raft::{
+    message::{LogEntry, Message},
+    replica::ReplicaID,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::BTreeMap, time::Instant};
+
+// Indexes and terms of crafted messages stay around those of the logs, so
+// that they land below, within and past them.
+const MAX_INDEX: usize = 24;
+const MAX_TERM: usize = 4;
+
+fn entries(from: usize, to: usize, term: usize) -> Vec<LogEntry<ArithmeticOperation>> {
+    (from..=to)
+        .map(|index| LogEntry {
+            transition: ArithmeticOperation {
+                id: index,
+                delta: 1,
+            },
+            index,
+            term,
+            config: None,
+        })
+        .collect()
+}
+
+// A Replica whose log was compacted up to index 10 and holds entries up to
+// index 14, the last of which are not committed.
+fn compacted_follower() -> TestReplica {
+    let (mut replica, _, _) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    replica.set_snapshot_delta(4);
+    for (prev_log_index, entries, commit_index) in
+        [(0, entries(1, 10, 1), 10), (10, entries(11, 14, 1), 10)]
+    {
+        replica.handle_message(Message::AppendEntryRequest {
+            from_id: 0,
+            term: 1,
+            prev_log_index,
+            prev_log_term: if prev_log_index == 0 { 0 } else { 1 },
+            entries,
+            commit_index,
+            timestamp: None,
+        });
+    }
+    assert_eq!(replica.metrics().index_offset, 10);
+    replica
+}
+
+// A Leader of term 2 whose log was compacted up to index 10.
+fn compacted_leader() -> TestReplica {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_snapshot_delta(4);
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: entries(1, 10, 1),
+        commit_index: 10,
+        timestamp: None,
+    });
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 2,
+        vote_granted: true,
+    });
+    assert!(replica.is_leader());
+    assert_eq!(replica.metrics().index_offset, 10);
+    replica
+}
+
+fn crafted_message(rng: &mut StdRng) -> Message<ArithmeticOperation> {
+    let from_id: ReplicaID = rng.gen_range(0..3);
+    let term = rng.gen_range(0..=MAX_TERM);
+    let index = |rng: &mut StdRng| rng.gen_range(0..=MAX_INDEX);
+    match rng.gen_range(0..8) {
+        0 | 1 => {
+            let prev_log_index = index(rng);
+            // Entries usually follow prev_log_index, but not always.
+            let first = match rng.gen_bool(0.8) {
+                true => prev_log_index + 1,
+                false => index(rng),
+            };
+            let count = rng.gen_range(0..4);
+            let mut entries = entries(first, first + count, rng.gen_range(0..=MAX_TERM));
+            entries.truncate(count);
+            if rng.gen_bool(0.1) {
+                entries.reverse();
+            }
+            Message::AppendEntryRequest {
+                from_id,
+                term,
+                prev_log_index,
+                prev_log_term: rng.gen_range(0..=MAX_TERM),
+                entries,
+                commit_index: index(rng),
+                timestamp: None,
+            }
+        }
+        2 | 3 => Message::AppendEntryResponse {
+            from_id,
+            term,
+            success: rng.gen(),
+            last_index: index(rng),
+            mismatch_index: match rng.gen() {
+                true => Some(index(rng)),
+                false => None,
+            },
+            content_hash: None,
+            request_timestamp: None,
+        },
+        4 => Message::InstallSnapshotRequest {
+            from_id,
+            term,
+            last_included_index: index(rng),
+            last_included_term: rng.gen_range(0..=MAX_TERM),
+            data: 1i32.to_le_bytes().to_vec(),
+            offset: 0,
+            done: true,
+            content_hash: None,
+            applied_sequences: BTreeMap::new(),
+        },
+        5 => Message::InstallSnapshotResponse {
+            from_id,
+            term,
+            last_included_index: index(rng),
+            last_index: index(rng),
+        },
+        6 => Message::VoteRequest {
+            from_id,
+            term,
+            last_log_index: index(rng),
+            last_log_term: rng.gen_range(0..=MAX_TERM),
+        },
+        _ => Message::PreVoteRequest {
+            from_id,
+            term,
+            last_log_index: index(rng),
+            last_log_term: rng.gen_range(0..=MAX_TERM),
+        },
+    }
+}
+
+// Deliver crafted messages to the Replica, with a heartbeat now and then.
+// Whatever the indexes in the messages, the Replica must not panic.
+fn deliver_crafted_messages(new_replica: fn() -> TestReplica) {
+    for seed in 0..200 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut replica = new_replica();
+        let mut now = Instant::now();
+        for _ in 0..50 {
+            replica.handle_message(crafted_message(&mut rng));
+            if rng.gen_bool(0.2) {
+                now += HEARTBEAT_TIMEOUT;
+                replica.handle_timeout(now);
+            }
+        }
+    }
+}
+
+#[test]
+fn crafted_indexes_cannot_panic_a_follower() {
+    deliver_crafted_messages(compacted_follower);
+}
+
+#[test]
+fn crafted_indexes_cannot_panic_a_leader() {
+    deliver_crafted_messages(compacted_leader);
+}
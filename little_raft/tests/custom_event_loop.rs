@@ -0,0 +1,92 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, HEARTBEAT_TIMEOUT, NO_ELECTION_TIMEOUT};
+use little_raft::message::{LogEntry, Message};
+use std::time::{Duration, Instant};
+
+#[test]
+fn handle_message_and_handle_timeout_drive_the_replica() {
+    let (mut replica, cluster, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+
+    // As a follower, the Replica adopts the Leader's term, then acknowledges
+    // and applies its entry.
+    let outbound = replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![LogEntry {
+            transition: ArithmeticOperation { id: 1, delta: 5 },
+            index: 1,
+            term: 1,
+            config: None,
+        }],
+        commit_index: 1,
+        timestamp: None,
+    });
+    match &outbound[..] {
+        [(
+            1,
+            Message::AppendEntryResponse {
+                term: 1,
+                success: true,
+                last_index: 1,
+                ..
+            },
+        )] => {}
+        outbound => panic!("unexpected messages {:?}", outbound),
+    }
+    assert_eq!(state_machine.lock().unwrap().value, 5);
+
+    // Nothing happens before the election deadline.
+    assert!(replica.handle_timeout(Instant::now()).is_empty());
+
+    // Once the Leader has been silent for too long, the Replica campaigns.
+    let outbound = replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    assert_eq!(outbound.len(), 2);
+    let term = match outbound[0].1 {
+        Message::VoteRequest {
+            term: 2,
+            last_log_index: 1,
+            last_log_term: 1,
+            ..
+        } => 2,
+        ref message => panic!("unexpected message {:?}", message),
+    };
+    assert_eq!(outbound[1], (2, outbound[0].1.clone()));
+
+    // A single vote makes it the Leader, which then sends heartbeats.
+    let outbound = replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term,
+        vote_granted: true,
+    });
+    assert_eq!(outbound.len(), 2);
+    for (_, message) in outbound {
+        match message {
+            Message::AppendEntryRequest {
+                term: request_term,
+                entries,
+                ..
+            } => {
+                assert_eq!(request_term, term);
+                assert_eq!(entries.len(), 1);
+            }
+            message => panic!("unexpected message {:?}", message),
+        }
+    }
+
+    // Heartbeats are repeated once per heartbeat timeout.
+    assert!(replica
+        .handle_timeout(Instant::now() + Duration::from_millis(1))
+        .is_empty());
+    assert_eq!(
+        replica
+            .handle_timeout(Instant::now() + HEARTBEAT_TIMEOUT)
+            .len(),
+        2
+    );
+
+    // Nothing went through the Cluster.
+    assert!(cluster.lock().unwrap().sent_messages.is_empty());
+}
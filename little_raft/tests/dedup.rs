@@ -0,0 +1,150 @@
+mod common;
+
+use common::{
+    new_replica, ArithmeticOperation, Calculator, ScriptedCluster, CLIENT_ID_BASE,
+    HEARTBEAT_TIMEOUT, NOOP, NO_ELECTION_TIMEOUT,
+};
+use little_raft::{
+    message::{LogEntry, Message},
+    replica::Replica,
+    state_machine::TransitionState,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+// Entries from index prev_log_index + 1 on, all committed.
+fn append_entries(prev_log_index: usize, ids: Vec<usize>) -> Message<ArithmeticOperation> {
+    let commit_index = prev_log_index + ids.len();
+    Message::AppendEntryRequest {
+        from_id: 1,
+        term: 0,
+        prev_log_index,
+        prev_log_term: 0,
+        entries: ids
+            .into_iter()
+            .zip(prev_log_index + 1..)
+            .map(|(id, index)| LogEntry {
+                transition: ArithmeticOperation { id, delta: 1 },
+                index,
+                term: 0,
+                config: None,
+            })
+            .collect(),
+        commit_index,
+        timestamp: None,
+    }
+}
+
+#[test]
+fn resubmitted_transitions_are_applied_once() {
+    let (first, second) = (CLIENT_ID_BASE + 1, CLIENT_ID_BASE + 2);
+    let other_client = 2 * CLIENT_ID_BASE + 1;
+    let (mut replica, _, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.handle_message(append_entries(
+        0,
+        vec![first, second, first, other_client, 7],
+    ));
+    {
+        let state_machine = state_machine.lock().unwrap();
+        assert_eq!(state_machine.value, 4);
+        assert_eq!(
+            state_machine.applied_ids,
+            vec![first, second, other_client, 7]
+        );
+        // The repeated transition is still reported applied.
+        assert_eq!(
+            state_machine
+                .transition_states
+                .iter()
+                .filter(|state| **state == (first, TransitionState::Applied))
+                .count(),
+            2
+        );
+    }
+
+    // A restarted Replica still knows which sequence numbers it applied.
+    let restarted_state_machine = {
+        let state_machine = state_machine.lock().unwrap();
+        Arc::new(Mutex::new(Calculator {
+            value: state_machine.value,
+            applied_index: state_machine.applied_index,
+            persisted_log: state_machine.persisted_log.clone(),
+            ..Calculator::default()
+        }))
+    };
+    let mut replica = Replica::new(
+        0,
+        vec![1, 2],
+        Arc::new(Mutex::new(ScriptedCluster::default())),
+        restarted_state_machine.clone(),
+        NOOP,
+        HEARTBEAT_TIMEOUT,
+        NO_ELECTION_TIMEOUT,
+    );
+    replica.handle_message(append_entries(5, vec![second, CLIENT_ID_BASE + 3]));
+    let state_machine = restarted_state_machine.lock().unwrap();
+    assert_eq!(state_machine.applied_ids, vec![CLIENT_ID_BASE + 3]);
+    assert_eq!(state_machine.value, 5);
+}
+
+#[test]
+fn snapshots_carry_the_applied_sequences() {
+    let first = CLIENT_ID_BASE + 1;
+    let (mut leader, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    leader.set_snapshot_delta(2);
+    leader.handle_message(append_entries(0, vec![first, 7]));
+    leader.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    let mut outbound = leader.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    assert_eq!(leader.metrics().index_offset, 2);
+
+    // Peer 2 only catches up with the Leader through its snapshot.
+    let (mut follower, _, state_machine) = new_replica(2, vec![0, 1], NO_ELECTION_TIMEOUT);
+    let mut now = Instant::now();
+    while follower.metrics().index_offset < 2 {
+        if outbound.is_empty() {
+            now += HEARTBEAT_TIMEOUT;
+            outbound = leader.handle_timeout(now);
+        }
+        let requests = outbound.into_iter().filter(|(to_id, _)| *to_id == 2);
+        let responses: Vec<_> = requests
+            .flat_map(|(_, request)| follower.handle_message(request))
+            .collect();
+        outbound = responses
+            .into_iter()
+            .flat_map(|(_, response)| leader.handle_message(response))
+            .collect();
+    }
+
+    assert_eq!(follower.metrics().log_len, 1);
+
+    // The client retries its transition, which the snapshot already applied.
+    follower.handle_message(Message::AppendEntryRequest {
+        from_id: 0,
+        term: 1,
+        prev_log_index: 2,
+        prev_log_term: 0,
+        entries: vec![LogEntry {
+            transition: ArithmeticOperation {
+                id: first,
+                delta: 1,
+            },
+            index: 3,
+            term: 1,
+            config: None,
+        }],
+        commit_index: 3,
+        timestamp: None,
+    });
+    let state_machine = state_machine.lock().unwrap();
+    assert_eq!(state_machine.value, 2);
+    assert!(state_machine.applied_ids.is_empty());
+    assert!(state_machine
+        .transition_states
+        .contains(&(first, TransitionState::Applied)));
+}
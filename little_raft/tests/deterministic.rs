@@ -0,0 +1,65 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, ELECTION_TIMEOUT};
+use little_raft::{message::Message, replica::ReplicaID};
+use std::{collections::BTreeMap, time::Duration};
+
+// Run an election and a proposal through a deterministic Replica. Returns its
+// election timeout and the distinct messages it sent to each peer, in order.
+// Repeated heartbeats are collapsed since their number depends on timing, and
+// wall-clock timestamps are dropped.
+fn run_scenario() -> (
+    Duration,
+    BTreeMap<ReplicaID, Vec<Message<ArithmeticOperation>>>,
+) {
+    let peer_ids = vec![0, 2];
+    let (mut replica, cluster, state_machine) = new_replica(1, peer_ids.clone(), ELECTION_TIMEOUT);
+    replica.set_deterministic(true);
+    let harness = Harness::start(replica, cluster, state_machine);
+
+    let mut sent: BTreeMap<ReplicaID, Vec<Message<ArithmeticOperation>>> = BTreeMap::new();
+    let mut record = |messages: Vec<(ReplicaID, Message<ArithmeticOperation>)>| {
+        for (to_id, mut message) in messages {
+            if let Message::AppendEntryRequest { timestamp, .. } = &mut message {
+                *timestamp = None;
+            }
+            let messages = sent.entry(to_id).or_default();
+            if messages.last() != Some(&message) {
+                messages.push(message);
+            }
+        }
+    };
+
+    let term = harness.elect(&peer_ids);
+    record(harness.take_sent());
+    harness.propose(vec![ArithmeticOperation { id: 1, delta: 1 }]);
+    record(harness.take_sent());
+    harness.deliver(
+        peer_ids
+            .iter()
+            .map(|peer_id| Message::AppendEntryResponse {
+                from_id: *peer_id,
+                term,
+                success: true,
+                last_index: 2,
+                mismatch_index: None,
+                content_hash: None,
+                request_timestamp: None,
+            })
+            .collect(),
+    );
+    record(harness.take_sent());
+
+    let replica = harness.stop();
+    (replica.last_election_timeout(), sent)
+}
+
+#[test]
+fn deterministic_replica_repeats_itself() {
+    let (election_timeout, sent) = run_scenario();
+    assert_eq!(run_scenario(), (election_timeout, sent));
+
+    // The Replica ranks second out of three in the cluster.
+    let (min, max) = ELECTION_TIMEOUT;
+    assert_eq!(election_timeout, min + (max - min) / 3);
+}
@@ -0,0 +1,90 @@
+mod common;
+
+use common::{ArithmeticOperation, Harness, ELECTION_TIMEOUT, NOOP};
+use little_raft::{
+    message::{LogEntry, Message},
+    state_machine::{TransitionAbandonedReason, TransitionState},
+};
+
+#[test]
+fn overwritten_transitions_are_reported_abandoned() {
+    let peer_ids = vec![1, 2];
+    let harness = Harness::new(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    let term = harness.elect(&peer_ids);
+    harness.propose(vec![
+        ArithmeticOperation { id: 1, delta: 1 },
+        ArithmeticOperation { id: 2, delta: 2 },
+    ]);
+
+    // Peer 2 wins the next term without the two transitions, which sit at
+    // indexes 2 and 3 after the no-op, and replaces them with its own.
+    harness.deliver(vec![
+        Message::AppendEntryResponse {
+            from_id: 2,
+            term: term + 1,
+            success: false,
+            last_index: 0,
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        },
+        Message::AppendEntryRequest {
+            from_id: 2,
+            term: term + 1,
+            prev_log_index: 1,
+            prev_log_term: term,
+            entries: vec![
+                LogEntry {
+                    transition: NOOP,
+                    index: 2,
+                    term: term + 1,
+                    config: None,
+                },
+                LogEntry {
+                    transition: ArithmeticOperation { id: 3, delta: 3 },
+                    index: 3,
+                    term: term + 1,
+                    config: None,
+                },
+            ],
+            commit_index: 0,
+            timestamp: None,
+        },
+    ]);
+    let state_machine = harness.state_machine.clone();
+    harness.stop();
+
+    let abandoned = TransitionState::Abandoned(TransitionAbandonedReason::Overwritten);
+    let transition_states = &state_machine.lock().unwrap().transition_states;
+    assert!(transition_states.contains(&(1, abandoned.clone())));
+    assert!(transition_states.contains(&(2, abandoned)));
+}
+
+#[test]
+fn inflight_proposals_are_taken_on_step_down() {
+    let peer_ids = vec![1, 2];
+    let harness = Harness::new(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    let term = harness.elect(&peer_ids);
+    harness.propose(vec![
+        ArithmeticOperation { id: 1, delta: 1 },
+        ArithmeticOperation { id: 2, delta: 2 },
+    ]);
+    harness.deliver(vec![Message::AppendEntryResponse {
+        from_id: 2,
+        term: term + 1,
+        success: false,
+        last_index: 0,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }]);
+    let state_machine = harness.state_machine.clone();
+    let mut replica = harness.stop();
+
+    assert_eq!(replica.take_inflight_proposals(), vec![(1, 2), (2, 3)]);
+    assert!(replica.take_inflight_proposals().is_empty());
+    let abandoned = TransitionState::Abandoned(TransitionAbandonedReason::NotLeader);
+    let transition_states = &state_machine.lock().unwrap().transition_states;
+    assert!(transition_states.contains(&(1, abandoned.clone())));
+    assert!(transition_states.contains(&(2, abandoned)));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{
+    settle, ArithmeticOperation, Calculator, HEARTBEAT_TIMEOUT, NOOP, NO_ELECTION_TIMEOUT,
+};
+use crossbeam_channel::{unbounded, Receiver};
+use little_raft::{cluster::Cluster, message::Message, replica::Replica};
+use std::{
+    sync::{Arc, Mutex},
+    thread,
+};
+
+// ChannelCluster hands the Replica messages straight from a channel, and
+// records how many messages the Replica had sent each time it pulled one.
+struct ChannelCluster {
+    rx: Receiver<Message<ArithmeticOperation>>,
+    sent: Arc<Mutex<usize>>,
+    sent_at_pull: Arc<Mutex<Vec<usize>>>,
+    halt: bool,
+}
+
+impl Cluster<ArithmeticOperation> for ChannelCluster {
+    fn send_message(&mut self, _to_id: usize, _message: Message<ArithmeticOperation>) {
+        *self.sent.lock().unwrap() += 1;
+    }
+
+    fn receive_messages(&mut self) -> Vec<Message<ArithmeticOperation>> {
+        self.rx.try_iter().collect()
+    }
+
+    fn drain_messages(&mut self) -> Box<dyn Iterator<Item = Message<ArithmeticOperation>>> {
+        let (rx, sent, sent_at_pull) = (
+            self.rx.clone(),
+            self.sent.clone(),
+            self.sent_at_pull.clone(),
+        );
+        Box::new(std::iter::from_fn(move || {
+            sent_at_pull.lock().unwrap().push(*sent.lock().unwrap());
+            rx.try_recv().ok()
+        }))
+    }
+
+    fn halt(&self) -> bool {
+        self.halt
+    }
+
+    fn register_leader(&mut self, _leader_id: Option<usize>) {}
+}
+
+#[test]
+fn replica_processes_each_message_before_pulling_the_next() {
+    let (tx, rx) = unbounded();
+    let sent_at_pull = Arc::new(Mutex::new(Vec::new()));
+    let cluster = Arc::new(Mutex::new(ChannelCluster {
+        rx,
+        sent: Arc::default(),
+        sent_at_pull: sent_at_pull.clone(),
+        halt: false,
+    }));
+    let mut replica = Replica::new(
+        0,
+        vec![1, 2],
+        cluster.clone(),
+        Arc::new(Mutex::new(Calculator::default())),
+        NOOP,
+        HEARTBEAT_TIMEOUT,
+        NO_ELECTION_TIMEOUT,
+    );
+    for _ in 0..2 {
+        tx.send(Message::AppendEntryRequest {
+            from_id: 1,
+            term: 0,
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: Vec::new(),
+            commit_index: 0,
+            timestamp: None,
+        })
+        .unwrap();
+    }
+    let (message_tx, message_rx) = unbounded();
+    let (_transition_tx, transition_rx) = unbounded();
+    message_tx.send(()).unwrap();
+    let handle = thread::spawn(move || replica.start(message_rx, transition_rx));
+    settle();
+    cluster.lock().unwrap().halt = true;
+    message_tx.send(()).unwrap();
+    handle.join().unwrap().unwrap();
+
+    // Each heartbeat was answered before the next one was pulled.
+    assert!(sent_at_pull.lock().unwrap().starts_with(&[0, 1, 2]));
+}
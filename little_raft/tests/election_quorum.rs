@@ -0,0 +1,22 @@
+mod common;
+
+use common::{new_replica, NO_ELECTION_TIMEOUT};
+use little_raft::message::Message;
+use std::time::Instant;
+
+#[test]
+fn candidates_need_a_strict_majority_of_the_cluster() {
+    for (total_nodes, needed_peers) in [(2, 1), (3, 1), (4, 2), (5, 2), (6, 3)] {
+        let (mut replica, _, _) = new_replica(0, (1..total_nodes).collect(), NO_ELECTION_TIMEOUT);
+        replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+        for peer_id in 1..=needed_peers {
+            assert!(!replica.is_leader(), "{} nodes", total_nodes);
+            replica.handle_message(Message::VoteResponse {
+                from_id: peer_id,
+                term: 1,
+                vote_granted: true,
+            });
+        }
+        assert!(replica.is_leader(), "{} nodes", total_nodes);
+    }
+}
@@ -0,0 +1,45 @@
+mod common;
+
+use common::{new_replica, settle, Harness, ELECTION_TIMEOUT, HEARTBEAT_TIMEOUT};
+use little_raft::message::Message;
+use std::time::{Duration, Instant};
+
+#[test]
+fn last_election_timeout_is_within_the_configured_range() {
+    let harness = Harness::new(0, vec![1, 2], ELECTION_TIMEOUT);
+    for _ in 0..5 {
+        settle();
+    }
+
+    let replica = harness.stop();
+    let timeout = replica.last_election_timeout();
+    assert!(timeout > Duration::from_secs(0));
+    assert!(timeout >= ELECTION_TIMEOUT.0);
+    assert!(timeout <= ELECTION_TIMEOUT.1);
+}
+
+#[test]
+fn timeouts_can_be_retuned_at_runtime() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    let (min, max) = (Duration::from_secs(2), Duration::from_secs(3));
+    replica.set_election_timeout_range(min, max);
+    replica.set_heartbeat_timeout(Duration::from_secs(1));
+
+    // The next election deadline is picked from the new range.
+    replica.handle_timeout(Instant::now() + ELECTION_TIMEOUT.1);
+    assert!((min..=max).contains(&replica.last_election_timeout()));
+
+    // The Leader waits for the new heartbeat timeout before sending
+    // heartbeats.
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    assert!(replica.is_leader());
+    let elected = Instant::now();
+    assert!(replica
+        .handle_timeout(elected + HEARTBEAT_TIMEOUT * 2)
+        .is_empty());
+    assert_eq!(replica.handle_timeout(elected + min).len(), 2);
+}
@@ -0,0 +1,44 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    message::{LogEntry, Message},
+    replica::RaftError,
+    state_machine::ApplyError,
+};
+
+#[test]
+fn fatal_apply_error_halts_the_replica() {
+    let (replica, cluster, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    state_machine.lock().unwrap().fatal_ids = vec![2];
+    let harness = Harness::start(replica, cluster, state_machine.clone());
+
+    let entries = (1..=3)
+        .map(|index| LogEntry {
+            transition: ArithmeticOperation {
+                id: index,
+                delta: 1,
+            },
+            index,
+            term: 1,
+            config: None,
+        })
+        .collect();
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries,
+        commit_index: 3,
+        timestamp: None,
+    }]);
+    assert!(harness.halted());
+
+    // The Replica stopped at the failing entry instead of skipping it.
+    let (replica, result) = harness.join().unwrap();
+    let error = ApplyError::Fatal("cannot apply 2".to_string());
+    assert_eq!(result, Err(RaftError::Apply(error.clone())));
+    assert_eq!(replica.fatal_error(), Some(&error));
+    assert_eq!(state_machine.lock().unwrap().applied_ids, vec![1]);
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use common::{
+    new_replica, ArithmeticOperation, Harness, TestReplica, ELECTION_TIMEOUT, NO_ELECTION_TIMEOUT,
+};
+use little_raft::{
+    clock::{Clock, ManualClock},
+    handle::ReadError,
+    message::{LogEntry, Message},
+    state_machine::TransitionState,
+};
+use std::{
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn follower_read_waits_for_the_write() {
+    let peer_ids = vec![1, 2];
+    let (leader, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    let leader_handle = leader.handle();
+    let leader = Harness::start(leader, cluster, state_machine);
+    let term = leader.elect(&peer_ids);
+
+    // Write through the Leader, which peer 2 acknowledges.
+    leader.propose(vec![ArithmeticOperation { id: 1, delta: 7 }]);
+    leader.deliver(vec![Message::AppendEntryResponse {
+        from_id: 2,
+        term,
+        success: true,
+        last_index: 2,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }]);
+    assert!(leader
+        .state_machine
+        .lock()
+        .unwrap()
+        .transition_states
+        .contains(&(1, TransitionState::Applied)));
+    let min_index = leader_handle.last_applied();
+
+    // Peer 1 lags behind: it has not received anything yet.
+    let (follower, cluster, state_machine) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    let follower_handle = follower.handle();
+    let follower = Harness::start(follower, cluster, state_machine.clone());
+    let reader = thread::spawn(move || {
+        let read =
+            follower_handle.wait_for_applied(min_index, Instant::now() + Duration::from_secs(5));
+        (read, state_machine.lock().unwrap().value)
+    });
+
+    // Relay the Leader's latest AppendEntryRequest to peer 1.
+    let request = leader
+        .take_sent()
+        .into_iter()
+        .rev()
+        .find_map(|(to_id, message)| if to_id == 1 { Some(message) } else { None })
+        .unwrap();
+    follower.deliver(vec![request]);
+    assert_eq!(reader.join().unwrap(), (Ok(min_index), 7));
+
+    leader.stop();
+    follower.stop();
+}
+
+#[test]
+fn follower_read_times_out() {
+    let (replica, cluster, state_machine) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    let handle = replica.handle();
+    let harness = Harness::start(replica, cluster, state_machine);
+    assert_eq!(
+        handle.wait_for_applied(1, Instant::now() + Duration::from_millis(50)),
+        Err(ReadError::Timeout { last_applied: 0 })
+    );
+    harness.stop();
+}
+
+#[test]
+fn bounded_stale_read_respects_the_budget() {
+    let clock = ManualClock::new();
+    let (mut replica, _, _) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    replica.set_clock(Arc::new(clock.clone()));
+    let handle = replica.handle();
+    let budget = Duration::from_millis(100);
+    assert_eq!(
+        handle.bounded_stale_read(budget),
+        Err(ReadError::TooStale { staleness: None })
+    );
+
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 0,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![LogEntry {
+            transition: ArithmeticOperation { id: 1, delta: 1 },
+            index: 1,
+            term: 1,
+            config: None,
+        }],
+        commit_index: 1,
+        timestamp: None,
+    });
+    assert_eq!(handle.bounded_stale_read(budget), Ok(1));
+    clock.advance(budget);
+    assert_eq!(handle.bounded_stale_read(budget), Ok(1));
+
+    // The Leader falls silent.
+    clock.advance(Duration::from_millis(1));
+    assert_eq!(
+        handle.bounded_stale_read(budget),
+        Err(ReadError::TooStale {
+            staleness: Some(budget + Duration::from_millis(1))
+        })
+    );
+}
+
+#[test]
+fn bounded_stale_read_on_the_leader_needs_a_majority() {
+    let clock = ManualClock::new();
+    let (mut replica, _, _) = new_replica(0, vec![1, 2, 3, 4], NO_ELECTION_TIMEOUT);
+    replica.set_clock(Arc::new(clock.clone()));
+    let handle = replica.handle();
+    let budget = Duration::from_millis(100);
+    clock.advance(NO_ELECTION_TIMEOUT.1);
+    replica.handle_timeout(clock.now());
+    for peer_id in 1..=2 {
+        replica.handle_message(Message::VoteResponse {
+            from_id: peer_id,
+            term: 1,
+            vote_granted: true,
+        });
+    }
+    let acknowledge = |replica: &mut TestReplica, peer_id| {
+        replica.handle_message(Message::AppendEntryResponse {
+            from_id: peer_id,
+            term: 1,
+            success: true,
+            last_index: 1,
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        });
+    };
+
+    // A single follower does not vouch that the Leader is still the Leader.
+    acknowledge(&mut replica, 1);
+    assert_eq!(
+        handle.bounded_stale_read(budget),
+        Err(ReadError::TooStale { staleness: None })
+    );
+
+    // With a second one, a majority acknowledged the Leader.
+    clock.advance(Duration::from_millis(30));
+    acknowledge(&mut replica, 2);
+    assert_eq!(handle.bounded_stale_read(budget), Ok(1));
+
+    // The Leader is as fresh as the older acknowledgement of that majority.
+    clock.advance(Duration::from_millis(80));
+    acknowledge(&mut replica, 3);
+    assert_eq!(handle.bounded_stale_read(budget), Ok(1));
+    clock.advance(Duration::from_millis(60));
+    assert_eq!(
+        handle.bounded_stale_read(budget),
+        Err(ReadError::TooStale {
+            staleness: Some(Duration::from_millis(140))
+        })
+    );
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, NO_ELECTION_TIMEOUT};
+use little_raft::message::{LogEntry, Message};
+use std::{panic, time::Instant};
+
+#[test]
+fn forced_commits_are_applied_in_order() {
+    let (mut replica, _, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: (1..=3)
+            .map(|index| LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: index as i32,
+                },
+                index,
+                term: 1,
+                config: None,
+            })
+            .collect(),
+        commit_index: 0,
+        timestamp: None,
+    });
+
+    // The entries are committed but not applied until the Replica runs.
+    replica.force_commit_index(3);
+    let handle = replica.handle();
+    assert_eq!((handle.commit_index(), handle.last_applied()), (3, 0));
+    assert!(state_machine.lock().unwrap().applied_ids.is_empty());
+
+    replica.handle_timeout(Instant::now());
+    assert_eq!(handle.last_applied(), 3);
+    assert_eq!(state_machine.lock().unwrap().applied_ids, vec![1, 2, 3]);
+
+    // The commit index never goes past the log.
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| replica.force_commit_index(4)));
+    assert!(result.is_err());
+}
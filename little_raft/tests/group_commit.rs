@@ -0,0 +1,68 @@
+mod common;
+
+use common::{
+    new_replica, settle, ArithmeticOperation, Harness, ELECTION_TIMEOUT, HEARTBEAT_TIMEOUT,
+};
+use little_raft::{clock::ManualClock, message::Message};
+use std::sync::Arc;
+
+// Number of entries in each AppendEntryRequest the Replica sent so far.
+fn sent_entries(harness: &Harness) -> Vec<usize> {
+    harness
+        .take_sent()
+        .into_iter()
+        .filter_map(|(_, message)| match message {
+            Message::AppendEntryRequest { entries, .. } => Some(entries.len()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn acknowledge(harness: &Harness, last_index: usize) {
+    harness.deliver(
+        (1..=2)
+            .map(|peer_id| Message::AppendEntryResponse {
+                from_id: peer_id,
+                term: 1,
+                success: true,
+                last_index,
+                mismatch_index: None,
+                content_hash: None,
+                request_timestamp: None,
+            })
+            .collect(),
+    );
+}
+
+#[test]
+fn leader_batches_transitions() {
+    let clock = ManualClock::new();
+    let (mut replica, cluster, state_machine) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    replica.set_clock(Arc::new(clock.clone()));
+    replica.set_group_commit(HEARTBEAT_TIMEOUT / 2, 3);
+    let harness = Harness::start(replica, cluster, state_machine);
+    settle();
+    clock.advance(ELECTION_TIMEOUT.1);
+    settle();
+    harness.elect(&[1, 2]);
+    acknowledge(&harness, 1);
+    harness.take_sent();
+
+    // The first transitions are held back until the batch is full.
+    for id in 1..=2 {
+        harness.propose(vec![ArithmeticOperation { id, delta: 1 }]);
+        assert!(sent_entries(&harness).is_empty());
+    }
+    harness.propose(vec![ArithmeticOperation { id: 3, delta: 1 }]);
+    assert_eq!(sent_entries(&harness), vec![3, 3]);
+    acknowledge(&harness, 4);
+    harness.take_sent();
+
+    // Or until the delay passes, which comes before the next heartbeat.
+    harness.propose(vec![ArithmeticOperation { id: 4, delta: 1 }]);
+    assert!(sent_entries(&harness).is_empty());
+    clock.advance(HEARTBEAT_TIMEOUT / 2);
+    settle();
+    assert_eq!(sent_entries(&harness), vec![1, 1]);
+    harness.stop();
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, ELECTION_TIMEOUT, HEARTBEAT_TIMEOUT};
+use little_raft::message::Message;
+use std::{thread, time::Duration};
+
+const PROPOSALS: usize = 25;
+
+// Elect the Replica, submit transitions faster than the heartbeat timeout and
+// count the AppendEntryRequests a peer receives meanwhile.
+fn append_entry_requests_sent(coalescing_window: Duration) -> usize {
+    let peer_ids = vec![1, 2];
+    let (mut replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    replica.set_heartbeat_coalescing_window(coalescing_window);
+    let harness = Harness::start(replica, cluster, state_machine);
+    harness.elect(&peer_ids);
+    harness.take_sent();
+
+    for id in 1..=PROPOSALS {
+        harness.submit(vec![ArithmeticOperation { id, delta: 1 }]);
+        thread::sleep(Duration::from_millis(20));
+    }
+
+    let sent = harness
+        .take_sent()
+        .into_iter()
+        .filter(|(to_id, message)| {
+            *to_id == 1 && matches!(message, Message::AppendEntryRequest { .. })
+        })
+        .count();
+    harness.stop();
+    sent
+}
+
+#[test]
+fn heartbeats_right_after_appends_are_skipped() {
+    assert!(append_entry_requests_sent(HEARTBEAT_TIMEOUT) <= PROPOSALS + 1);
+}
+
+#[test]
+fn heartbeats_are_sent_without_coalescing() {
+    assert!(append_entry_requests_sent(Duration::from_secs(0)) >= PROPOSALS + 5);
+}
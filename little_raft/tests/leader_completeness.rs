@@ -0,0 +1,53 @@
+mod common;
+
+use common::{settle, Harness, ELECTION_TIMEOUT};
+use little_raft::message::Message;
+
+#[test]
+fn leader_without_committed_entries_is_caught() {
+    let peer_ids = vec![1, 2];
+    let harness = Harness::new(0, peer_ids.clone(), ELECTION_TIMEOUT);
+
+    // The Leader of term 1 tells us that index 5 is committed, but we never
+    // got the entries.
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 5,
+        prev_log_term: 1,
+        entries: vec![],
+        commit_index: 5,
+        timestamp: None,
+    }]);
+
+    // Peers with a broken vote check elect us anyway. Release builds step
+    // down instead of panicking.
+    harness.elect(&peer_ids);
+    if cfg!(debug_assertions) {
+        assert!(harness.join().is_err());
+    } else {
+        settle();
+        assert_eq!(harness.leader_id(), None);
+        harness.stop();
+    }
+}
+
+#[test]
+fn leader_with_committed_entries_passes_the_check() {
+    let peer_ids = vec![1, 2];
+    let harness = Harness::new(0, peer_ids.clone(), ELECTION_TIMEOUT);
+
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![],
+        commit_index: 0,
+        timestamp: None,
+    }]);
+
+    harness.elect(&peer_ids);
+    assert_eq!(harness.leader_id(), Some(0));
+    assert!(harness.join().is_ok());
+}
@@ -0,0 +1,129 @@
+mod common;
+
+use common::{new_replica, Harness, ELECTION_TIMEOUT, HEARTBEAT_TIMEOUT, NO_ELECTION_TIMEOUT};
+use little_raft::message::Message;
+use std::time::Instant;
+
+#[test]
+fn replication_progress_is_reset_on_every_leader_change() {
+    let peer_ids = vec![1, 2];
+    let harness = Harness::new(0, peer_ids.clone(), ELECTION_TIMEOUT);
+
+    for _ in 0..3 {
+        harness.elect(&peer_ids);
+        assert_eq!(harness.leader_id(), Some(0));
+
+        // The first AppendEntryRequest to every peer must start right after
+        // the end of the log the Leader had when it got elected, which means
+        // it only carries the Leader's no-op.
+        let sent = harness.take_sent();
+        let mut first_request_peers = Vec::new();
+        let mut term = 0;
+        for (to_id, message) in sent {
+            if let Message::AppendEntryRequest {
+                term: request_term,
+                prev_log_index,
+                entries,
+                ..
+            } = message
+            {
+                if first_request_peers.contains(&to_id) {
+                    continue;
+                }
+                first_request_peers.push(to_id);
+                term = request_term;
+                assert_eq!(entries.len(), 1);
+                assert_eq!(entries[0].index, prev_log_index + 1);
+                assert_eq!(entries[0].term, request_term);
+            }
+        }
+        first_request_peers.sort_unstable();
+        assert_eq!(first_request_peers, peer_ids);
+
+        // Let one peer catch up so the Leader has some progress to forget,
+        // then make the Leader step down.
+        harness.deliver(vec![Message::AppendEntryResponse {
+            from_id: 1,
+            term,
+            success: true,
+            last_index: 1,
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        }]);
+        harness.deliver(vec![Message::AppendEntryResponse {
+            from_id: 2,
+            term: term + 1,
+            success: false,
+            last_index: 0,
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        }]);
+        assert_eq!(harness.leader_id(), None);
+        harness.take_sent();
+    }
+
+    harness.stop();
+}
+
+#[test]
+fn new_leader_sends_heartbeats_right_away() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    let vote_requests = replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    let term = match vote_requests[0].1 {
+        Message::VoteRequest { term, .. } => term,
+        ref message => panic!("unexpected message {:?}", message),
+    };
+
+    // The vote that wins the election is answered with heartbeats.
+    let outbound = replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term,
+        vote_granted: true,
+    });
+    let heartbeats: Vec<usize> = outbound
+        .into_iter()
+        .filter_map(|(to_id, message)| match message {
+            Message::AppendEntryRequest { .. } => Some(to_id),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(heartbeats, vec![1, 2]);
+}
+
+#[test]
+fn new_leader_commits_before_its_first_heartbeat() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    let elected = Instant::now();
+    let outbound = replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+
+    // The peers acknowledge the no-op the Leader sent when it got elected,
+    // which commits it without waiting for a heartbeat.
+    for (to_id, message) in outbound {
+        if let Message::AppendEntryRequest {
+            term,
+            prev_log_index,
+            entries,
+            ..
+        } = message
+        {
+            replica.handle_message(Message::AppendEntryResponse {
+                from_id: to_id,
+                term,
+                success: true,
+                last_index: prev_log_index + entries.len(),
+                mismatch_index: None,
+                content_hash: None,
+                request_timestamp: None,
+            });
+        }
+    }
+    assert_eq!(replica.handle().commit_index(), 1);
+    assert!(elected.elapsed() < HEARTBEAT_TIMEOUT);
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, ELECTION_TIMEOUT, NO_ELECTION_TIMEOUT};
+use little_raft::message::{LogEntry, Message};
+
+#[test]
+fn handle_reports_leadership_while_the_replica_runs() {
+    let peer_ids = vec![1, 2];
+    let (replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    let handle = replica.handle();
+    assert_eq!(
+        (
+            handle.current_term(),
+            handle.is_leader(),
+            handle.leader_id()
+        ),
+        (0, false, None)
+    );
+
+    let harness = Harness::start(replica, cluster, state_machine);
+    let term = harness.elect(&peer_ids);
+    assert_eq!(
+        (
+            handle.current_term(),
+            handle.is_leader(),
+            handle.leader_id()
+        ),
+        (term, true, Some(0))
+    );
+
+    let replica = harness.stop();
+    assert_eq!(
+        (
+            replica.current_term(),
+            replica.is_leader(),
+            replica.leader_id()
+        ),
+        (term, true, Some(0))
+    );
+}
+
+#[test]
+fn follower_reports_the_leader_it_hears_from() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.handle_message(Message::VoteRequest {
+        from_id: 1,
+        term: 3,
+        last_log_index: 0,
+        last_log_term: 0,
+    });
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 3,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![LogEntry {
+            transition: ArithmeticOperation { id: 1, delta: 1 },
+            index: 1,
+            term: 3,
+            config: None,
+        }],
+        commit_index: 0,
+        timestamp: None,
+    });
+
+    let handle = replica.handle();
+    assert_eq!(
+        (
+            replica.current_term(),
+            replica.is_leader(),
+            replica.leader_id()
+        ),
+        (3, false, Some(1))
+    );
+    assert_eq!(
+        (
+            handle.current_term(),
+            handle.is_leader(),
+            handle.leader_id()
+        ),
+        (3, false, Some(1))
+    );
+}
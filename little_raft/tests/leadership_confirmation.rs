@@ -0,0 +1,54 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, ELECTION_TIMEOUT};
+use little_raft::{message::Message, state_machine::TransitionState};
+
+// Return the term of the AppendEntryRequests sent so far and whether any of
+// them carried the transition with the given ID.
+fn replicated(harness: &Harness, id: usize) -> (usize, bool) {
+    let (mut term, mut found) = (0, false);
+    for (_, message) in harness.take_sent() {
+        if let Message::AppendEntryRequest {
+            term: request_term,
+            entries,
+            ..
+        } = message
+        {
+            term = request_term;
+            found |= entries.iter().any(|entry| entry.transition.id == id);
+        }
+    }
+    (term, found)
+}
+
+#[test]
+fn new_leader_defers_writes_until_confirmed() {
+    let peer_ids = vec![1, 2];
+    let (mut replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    replica.set_confirm_leadership_before_writes(true);
+    let harness = Harness::start(replica, cluster, state_machine.clone());
+    harness.elect(&peer_ids);
+
+    harness.propose(vec![ArithmeticOperation { id: 1, delta: 1 }]);
+    let (term, found) = replicated(&harness, 1);
+    assert!(!found);
+    assert!(state_machine.lock().unwrap().transition_states.is_empty());
+
+    // A single peer makes a majority in a cluster of three.
+    harness.deliver(vec![Message::AppendEntryResponse {
+        from_id: 1,
+        term,
+        success: false,
+        last_index: 0,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }]);
+    assert!(replicated(&harness, 1).1);
+    assert_eq!(
+        state_machine.lock().unwrap().transition_states,
+        vec![(1, TransitionState::Queued)]
+    );
+
+    harness.stop();
+}
@@ -0,0 +1,136 @@
+mod common;
+
+use common::{
+    new_replica, ArithmeticOperation, Calculator, ScriptedCluster, TestReplica, NO_ELECTION_TIMEOUT,
+};
+use little_raft::{
+    message::Message,
+    replica::ReplicaID,
+    state_machine::{TransitionAbandonedReason, TransitionState},
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+// Network drives three Replicas with handle_message and delivers their
+// messages in waves: every message sent during a wave is delivered in the
+// next one.
+struct Network {
+    replicas: Vec<TestReplica>,
+    clusters: Vec<Arc<Mutex<ScriptedCluster>>>,
+    state_machines: Vec<Arc<Mutex<Calculator>>>,
+    in_flight: Vec<(ReplicaID, Message<ArithmeticOperation>)>,
+}
+
+impl Network {
+    fn new() -> Network {
+        let mut network = Network {
+            replicas: Vec::new(),
+            clusters: Vec::new(),
+            state_machines: Vec::new(),
+            in_flight: Vec::new(),
+        };
+        for id in 0..3 {
+            let peer_ids = (0..3).filter(|peer_id| *peer_id != id).collect();
+            let (replica, cluster, state_machine) = new_replica(id, peer_ids, NO_ELECTION_TIMEOUT);
+            network.replicas.push(replica);
+            network.clusters.push(cluster);
+            network.state_machines.push(state_machine);
+        }
+        network
+    }
+
+    // Queue what the Replicas sent through their Cluster rather than as a
+    // result of handle_message or handle_timeout.
+    fn collect_sent(&mut self) {
+        for cluster in &self.clusters {
+            self.in_flight
+                .append(&mut cluster.lock().unwrap().sent_messages);
+        }
+    }
+
+    // Deliver every message in flight and return them.
+    fn wave(&mut self) -> Vec<(ReplicaID, Message<ArithmeticOperation>)> {
+        self.collect_sent();
+        let delivered = std::mem::take(&mut self.in_flight);
+        for (to_id, message) in &delivered {
+            let mut outbound = self.replicas[*to_id].handle_message(message.clone());
+            self.in_flight.append(&mut outbound);
+        }
+        delivered
+    }
+}
+
+#[test]
+fn leadership_moves_to_the_target_once_it_caught_up() {
+    let mut network = Network::new();
+    network.replicas[0].set_confirm_leadership_before_writes(true);
+
+    // Replica 0 wins an election with the vote of Replica 2 alone, and
+    // Replica 1 does not hear of the Leader until the transfer starts.
+    network.in_flight = network.replicas[0]
+        .handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1)
+        .into_iter()
+        .filter(|(to_id, _)| *to_id == 2)
+        .collect();
+    network.wave();
+    network.wave();
+    assert!(network.replicas[0].is_leader());
+    network.in_flight.retain(|(to_id, _)| *to_id != 1);
+
+    // The Leader brings Replica 1 up to date before handing leadership over,
+    // and abandons the transitions it receives in the meantime.
+    network.replicas[0].transfer_leadership(1);
+    network.collect_sent();
+    assert!(!network
+        .in_flight
+        .iter()
+        .any(|(_, message)| matches!(message, Message::TimeoutNow { .. })));
+    let state_machine = network.state_machines[0].clone();
+    state_machine.lock().unwrap().pending_transitions =
+        vec![ArithmeticOperation { id: 7, delta: 1 }];
+    network.wave();
+    network.wave();
+    assert!(state_machine.lock().unwrap().transition_states.contains(&(
+        7,
+        TransitionState::Abandoned(TransitionAbandonedReason::LeadershipTransfer)
+    )));
+    assert!(!network.replicas[0].is_leader());
+
+    // Replica 1 starts an election as soon as it is told to, and wins it
+    // within one round-trip.
+    let delivered = network.wave();
+    assert!(delivered
+        .iter()
+        .any(|(to_id, message)| *to_id == 1 && matches!(message, Message::TimeoutNow { .. })));
+    network.wave();
+    network.wave();
+    assert!(network.replicas[1].is_leader());
+    assert_eq!(network.replicas[1].current_term(), 2);
+}
+
+#[test]
+fn leadership_transfer_is_given_up_after_an_election_timeout() {
+    let mut network = Network::new();
+    network.in_flight = network.replicas[0].handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    network.wave();
+    network.wave();
+    assert!(network.replicas[0].is_leader());
+
+    // Replica 1 never answers, so the Leader keeps leading.
+    network.replicas[0].transfer_leadership(1);
+    network.replicas[0].handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    network.collect_sent();
+    let outbound = network.replicas[0].handle_message(Message::AppendEntryResponse {
+        from_id: 1,
+        term: 1,
+        success: true,
+        last_index: 1,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    });
+    assert!(outbound.is_empty());
+    assert!(network.replicas[0].is_leader());
+}
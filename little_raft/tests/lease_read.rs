@@ -0,0 +1,220 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, TestReplica, ELECTION_TIMEOUT, HEARTBEAT_TIMEOUT};
+use little_raft::{
+    clock::{Clock, ManualClock},
+    message::Message,
+    replica::ReplicaID,
+};
+use std::{panic, sync::Arc, thread, time::Duration};
+
+type Outbound = Vec<(ReplicaID, Message<ArithmeticOperation>)>;
+
+// Elect a Replica run on a manual clock with the votes of the given peers,
+// and return the AppendEntryRequests it sends as the new Leader.
+fn elect(
+    peer_ids: Vec<ReplicaID>,
+    voter_ids: &[ReplicaID],
+) -> (TestReplica, ManualClock, Outbound) {
+    let clock = ManualClock::new();
+    let (mut replica, _, _) = new_replica(0, peer_ids, ELECTION_TIMEOUT);
+    replica.set_clock(Arc::new(clock.clone()));
+    assert!(!replica.can_serve_lease_read());
+
+    clock.advance(ELECTION_TIMEOUT.1);
+    replica.handle_timeout(clock.now());
+    let term = replica.current_term();
+    let mut outbound = Vec::new();
+    for peer_id in voter_ids {
+        outbound = replica.handle_message(Message::VoteResponse {
+            from_id: *peer_id,
+            term,
+            vote_granted: true,
+        });
+    }
+    assert!(replica.is_leader());
+    (replica, clock, outbound)
+}
+
+// Answer the AppendEntryRequest the Leader sent to peer_id as a follower that
+// accepts it.
+fn acknowledge(replica: &mut TestReplica, outbound: &Outbound, peer_id: ReplicaID) {
+    let (prev_log_index, entries, timestamp) = outbound
+        .iter()
+        .find_map(|(to_id, message)| match message {
+            Message::AppendEntryRequest {
+                prev_log_index,
+                entries,
+                timestamp,
+                ..
+            } if *to_id == peer_id => Some((*prev_log_index, entries.len(), *timestamp)),
+            _ => None,
+        })
+        .expect("no AppendEntryRequest sent to the peer");
+    replica.handle_message(Message::AppendEntryResponse {
+        from_id: peer_id,
+        term: replica.current_term(),
+        success: true,
+        last_index: prev_log_index + entries,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: timestamp,
+    });
+}
+
+#[test]
+fn lease_lasts_an_election_timeout_after_a_majority_acknowledged_the_leader() {
+    let (mut replica, clock, outbound) = elect(vec![1, 2, 3, 4], &[1, 2]);
+
+    // Votes do not start the lease, and neither does a minority of
+    // acknowledgements.
+    assert!(!replica.can_serve_lease_read());
+    acknowledge(&mut replica, &outbound, 1);
+    assert!(!replica.can_serve_lease_read());
+
+    // Once a majority acknowledged the no-op, it is committed and applied.
+    acknowledge(&mut replica, &outbound, 2);
+    assert_eq!(replica.handle().last_applied(), 1);
+    assert!(replica.can_serve_lease_read());
+
+    // Without further acknowledgements the lease runs out.
+    clock.advance(ELECTION_TIMEOUT.0);
+    assert!(!replica.can_serve_lease_read());
+
+    // The oldest request answered by the majority bounds the lease.
+    clock.advance(HEARTBEAT_TIMEOUT);
+    let heartbeats = replica.handle_timeout(clock.now());
+    acknowledge(&mut replica, &heartbeats, 3);
+    acknowledge(&mut replica, &heartbeats, 4);
+    assert!(replica.can_serve_lease_read());
+}
+
+#[test]
+fn lease_runs_from_when_the_acknowledged_requests_were_sent() {
+    let (mut replica, clock, outbound) = elect(vec![1, 2], &[1]);
+
+    // The acknowledgements of the no-op take most of an election timeout to
+    // come back. Followers reset their election timer when the requests
+    // arrived, so the lease is already almost over.
+    clock.advance(ELECTION_TIMEOUT.0 - Duration::from_millis(10));
+    acknowledge(&mut replica, &outbound, 1);
+    assert!(replica.can_serve_lease_read());
+    clock.advance(Duration::from_millis(10));
+    assert!(!replica.can_serve_lease_read());
+
+    // Acknowledgements that do not tell which request they answer cannot
+    // extend the lease.
+    replica.handle_message(Message::AppendEntryResponse {
+        from_id: 1,
+        term: replica.current_term(),
+        success: true,
+        last_index: 1,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    });
+    assert!(!replica.can_serve_lease_read());
+}
+
+#[test]
+fn lease_requires_an_applied_no_op() {
+    let (mut replica, _, outbound) = elect(vec![1, 2], &[1]);
+
+    // An acknowledgement that does not cover the no-op leaves it uncommitted.
+    let heartbeat = outbound
+        .iter()
+        .map(|(to_id, message)| match message {
+            Message::AppendEntryRequest {
+                from_id,
+                term,
+                timestamp,
+                ..
+            } => (
+                *to_id,
+                Message::AppendEntryRequest {
+                    from_id: *from_id,
+                    term: *term,
+                    prev_log_index: 0,
+                    prev_log_term: 0,
+                    entries: vec![],
+                    commit_index: 0,
+                    timestamp: *timestamp,
+                },
+            ),
+            message => (*to_id, message.clone()),
+        })
+        .collect();
+    acknowledge(&mut replica, &heartbeat, 1);
+    assert!(!replica.can_serve_lease_read());
+    acknowledge(&mut replica, &outbound, 1);
+    assert!(replica.can_serve_lease_read());
+}
+
+#[test]
+fn lease_is_not_read_from_the_system_clock() {
+    let (mut replica, _, outbound) = elect(vec![1, 2], &[1]);
+    acknowledge(&mut replica, &outbound, 1);
+
+    // Only the Replica's clock tells the lease apart from an expired one.
+    thread::sleep(ELECTION_TIMEOUT.0);
+    assert!(replica.can_serve_lease_read());
+}
+
+#[test]
+fn lease_ends_before_a_follower_with_a_fast_clock_supports_another_leader() {
+    let (mut replica, clock, outbound) = elect(vec![1, 2], &[1]);
+    replica.set_clock_skew_bound(ELECTION_TIMEOUT.0 / 10);
+
+    // Peer 1 acknowledges the Leader, and its clock gains 10% on the
+    // Leader's.
+    let follower_clock = ManualClock::new();
+    let (mut follower, _, _) = new_replica(1, vec![0, 2], ELECTION_TIMEOUT);
+    follower.set_clock(Arc::new(follower_clock.clone()));
+    follower.set_pre_vote(true);
+    let request = outbound
+        .into_iter()
+        .find_map(|(to_id, message)| (to_id == 1).then_some(message))
+        .unwrap();
+    for (_, response) in follower.handle_message(request) {
+        replica.handle_message(response);
+    }
+    assert!(replica.can_serve_lease_read());
+
+    // Peer 2 looks for support to replace the Leader until peer 1 gives it.
+    let term = replica.current_term();
+    loop {
+        clock.advance(Duration::from_millis(1));
+        follower_clock.advance(Duration::from_micros(1100));
+        let outbound = follower.handle_message(Message::PreVoteRequest {
+            from_id: 2,
+            term: term + 1,
+            last_log_index: 1,
+            last_log_term: term,
+        });
+        if outbound.iter().any(|(_, message)| {
+            matches!(
+                message,
+                Message::PreVoteResponse {
+                    vote_granted: true,
+                    ..
+                }
+            )
+        }) {
+            break;
+        }
+    }
+
+    // The lease is over by then, though it would not be without the bound.
+    assert!(!replica.can_serve_lease_read());
+    replica.set_clock_skew_bound(Duration::ZERO);
+    assert!(replica.can_serve_lease_read());
+}
+
+#[test]
+fn clock_skew_bound_must_leave_a_lease() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        replica.set_clock_skew_bound(ELECTION_TIMEOUT.0)
+    }));
+    assert!(result.is_err());
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    message::{LogEntry, Message},
+    replica::RejectReason,
+};
+
+#[test]
+fn votes_go_to_candidates_with_a_later_last_term_or_a_longer_log() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: (1..=3)
+            .map(|index| LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: 1,
+                },
+                index,
+                term: 1,
+                config: None,
+            })
+            .collect(),
+        commit_index: 0,
+        timestamp: None,
+    });
+
+    // Each Candidate runs in a new term, so the Replica may vote again.
+    let mut vote = |term, last_log_index, last_log_term| {
+        let outbound = replica.handle_message(Message::VoteRequest {
+            from_id: 2,
+            term,
+            last_log_index,
+            last_log_term,
+        });
+        match outbound[..] {
+            [(_, Message::VoteResponse { vote_granted, .. })] => vote_granted,
+            ref outbound => panic!("unexpected messages {:?}", outbound),
+        }
+    };
+
+    // A longer log does not make up for an earlier last term.
+    assert!(!vote(2, 5, 0));
+    // With the same last term, the shorter log is behind.
+    assert!(!vote(3, 2, 1));
+    // A later last term wins even with a shorter log.
+    assert!(vote(4, 2, 2));
+    // Identical logs are up to date.
+    assert!(vote(5, 3, 1));
+
+    assert_eq!(replica.vote_rejections(RejectReason::LogBehind), 2);
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, HEARTBEAT_TIMEOUT, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    message::{LogEntry, Message},
+    state_machine::TransitionState,
+};
+use std::{mem, time::Instant};
+
+const ENTRY_SIZE: usize = mem::size_of::<ArithmeticOperation>();
+
+// Entries from..=to of the given term, each adding 1.
+fn entries(from: usize, to: usize, term: usize) -> Vec<LogEntry<ArithmeticOperation>> {
+    (from..=to)
+        .map(|index| LogEntry {
+            transition: ArithmeticOperation {
+                id: index,
+                delta: 1,
+            },
+            index,
+            term,
+            config: None,
+        })
+        .collect()
+}
+
+fn append(
+    prev_log_index: usize,
+    entries: Vec<LogEntry<ArithmeticOperation>>,
+    commit_index: usize,
+) -> Message<ArithmeticOperation> {
+    Message::AppendEntryRequest {
+        from_id: 0,
+        term: 1,
+        prev_log_index,
+        prev_log_term: if prev_log_index == 0 { 0 } else { 1 },
+        entries,
+        commit_index,
+        timestamp: None,
+    }
+}
+
+#[test]
+fn log_is_compacted_once_it_reaches_the_memory_limit() {
+    let (mut replica, _, state_machine) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    replica.set_snapshot_delta(100);
+    replica.set_hard_log_memory_limit(6 * ENTRY_SIZE);
+
+    // Below the limit, nothing happens.
+    replica.handle_message(append(0, entries(1, 4, 1), 4));
+    assert_eq!(replica.emergency_compactions(), 0);
+    assert_eq!(replica.metrics().log_len, 5);
+
+    // The entry that reaches the limit makes the Replica compact the log,
+    // long before the snapshot delta.
+    replica.handle_message(append(4, entries(5, 5, 1), 5));
+    assert_eq!(replica.emergency_compactions(), 1);
+    let metrics = replica.metrics();
+    assert_eq!((metrics.index_offset, metrics.log_len), (5, 1));
+    assert_eq!(replica.snapshot().unwrap().last_included_index, 5);
+    assert_eq!(state_machine.lock().unwrap().value, 5);
+
+    // Entries that are not committed are kept, however much memory they take.
+    replica.handle_message(append(5, entries(6, 11, 1), 5));
+    assert_eq!(replica.emergency_compactions(), 1);
+    assert_eq!(replica.metrics().log_len, 7);
+
+    // They are compacted once committed and applied.
+    replica.handle_message(append(11, vec![], 11));
+    assert_eq!(replica.emergency_compactions(), 2);
+    let metrics = replica.metrics();
+    assert_eq!((metrics.index_offset, metrics.log_len), (11, 1));
+    assert_eq!(state_machine.lock().unwrap().value, 11);
+}
+
+#[test]
+fn leader_defers_transitions_while_its_log_is_too_large() {
+    let (mut replica, _, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_confirm_leadership_before_writes(true);
+    replica.set_hard_log_memory_limit(4 * ENTRY_SIZE);
+
+    // With the entries of the previous Leader that are not committed yet and
+    // its own no-op, the log of the new Leader is at the limit.
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: entries(1, 2, 1),
+        commit_index: 0,
+        timestamp: None,
+    });
+    let mut now = Instant::now() + NO_ELECTION_TIMEOUT.1;
+    replica.handle_timeout(now);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 2,
+        vote_granted: true,
+    });
+    assert!(replica.is_leader());
+    state_machine
+        .lock()
+        .unwrap()
+        .pending_transitions
+        .push(ArithmeticOperation { id: 9, delta: 1 });
+
+    // The peer that confirms the Leader also commits its log, which the
+    // Leader then compacts. The transition stays pending in the meantime.
+    replica.handle_message(Message::AppendEntryResponse {
+        from_id: 1,
+        term: 2,
+        success: true,
+        last_index: 3,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    });
+    assert_eq!(replica.emergency_compactions(), 1);
+    assert_eq!(replica.metrics().index_offset, 3);
+    assert_eq!(state_machine.lock().unwrap().pending_transitions.len(), 1);
+
+    // The Leader picks it up on its next heartbeat.
+    now += HEARTBEAT_TIMEOUT;
+    replica.handle_timeout(now);
+    let state_machine = state_machine.lock().unwrap();
+    assert!(state_machine.pending_transitions.is_empty());
+    assert!(state_machine
+        .transition_states
+        .contains(&(9, TransitionState::Queued)));
+}
@@ -0,0 +1,88 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, TestReplica, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    message::{LogEntry, Message},
+    replica::TermEntries,
+};
+
+// Build a follower whose log spans terms 1 to 3, committed up to index 4:
+//
+// index: 1  2  3  4  5
+// term:  1  1  2  3  3
+//
+// With a snapshot delta, the committed entries are compacted.
+fn replica_with_multi_term_log(snapshot_delta: Option<usize>) -> TestReplica {
+    let (mut replica, cluster, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    if let Some(delta) = snapshot_delta {
+        replica.set_snapshot_delta(delta);
+    }
+    let harness = Harness::start(replica, cluster, state_machine);
+    let entries = [1, 1, 2, 3, 3]
+        .iter()
+        .enumerate()
+        .map(|(i, term)| LogEntry {
+            transition: ArithmeticOperation {
+                id: i + 1,
+                delta: 1,
+            },
+            index: i + 1,
+            term: *term,
+            config: None,
+        })
+        .collect();
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 1,
+        term: 3,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries,
+        commit_index: 4,
+        timestamp: None,
+    }]);
+    harness.stop()
+}
+
+// Indexes of the entries returned by entries_in_term, and whether some may be
+// missing.
+fn indexes(term_entries: TermEntries<ArithmeticOperation>) -> (Vec<usize>, bool) {
+    let indexes = term_entries.entries.iter().map(|entry| entry.index);
+    (indexes.collect(), term_entries.truncated)
+}
+
+#[test]
+fn entries_in_term_returns_committed_entries_of_the_term() {
+    let replica = replica_with_multi_term_log(None);
+    assert_eq!(indexes(replica.entries_in_term(1)), (vec![1, 2], false));
+    assert_eq!(indexes(replica.entries_in_term(2)), (vec![3], false));
+    assert_eq!(indexes(replica.entries_in_term(3)), (vec![4], false));
+    assert_eq!(indexes(replica.entries_in_term(4)), (vec![], false));
+}
+
+#[test]
+fn entries_in_term_reports_compacted_entries() {
+    let replica = replica_with_multi_term_log(Some(2));
+    assert_eq!(replica.snapshot().unwrap().last_included_index, 4);
+    for term in 1..=3 {
+        assert_eq!(indexes(replica.entries_in_term(term)), (vec![], true));
+    }
+    assert_eq!(indexes(replica.entries_in_term(4)), (vec![], false));
+}
+
+#[test]
+fn first_committed_index_in_term_returns_the_fence_of_the_term() {
+    let replica = replica_with_multi_term_log(None);
+    assert_eq!(replica.first_committed_index_in_term(1), Some(1));
+    assert_eq!(replica.first_committed_index_in_term(2), Some(3));
+    assert_eq!(replica.first_committed_index_in_term(3), Some(4));
+    assert_eq!(replica.first_committed_index_in_term(4), None);
+}
+
+#[test]
+fn first_committed_index_in_term_is_unknown_once_compacted() {
+    let replica = replica_with_multi_term_log(Some(2));
+    assert_eq!(replica.snapshot().unwrap().last_included_index, 4);
+    for term in 1..=4 {
+        assert_eq!(replica.first_committed_index_in_term(term), None);
+    }
+}
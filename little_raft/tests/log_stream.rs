@@ -0,0 +1,102 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, TestReplica, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    log_stream::{LogRecord, LogStreamError},
+    message::{LogEntry, Message},
+};
+
+// Replicate entries up to last_index on the follower and commit them.
+fn replicate(replica: &mut TestReplica, first_index: usize, last_index: usize) {
+    let entries = (first_index..=last_index)
+        .map(|index| LogEntry {
+            transition: ArithmeticOperation {
+                id: index,
+                delta: index as i32,
+            },
+            index,
+            term: 1,
+            config: None,
+        })
+        .collect();
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: first_index - 1,
+        prev_log_term: if first_index == 1 { 0 } else { 1 },
+        entries,
+        commit_index: last_index,
+        timestamp: None,
+    });
+}
+
+// Stream the log from from_index through its encoded form, the way an
+// external consumer sees it.
+fn consume(replica: &TestReplica, from_index: usize) -> Result<Vec<LogRecord>, LogStreamError> {
+    let stream = replica.log_stream(from_index, |transition| {
+        transition.delta.to_le_bytes().to_vec()
+    })?;
+    let bytes: Vec<u8> = stream.flat_map(|record| record.encode()).collect();
+
+    let mut records = Vec::new();
+    let mut position = 0;
+    while position < bytes.len() {
+        let (record, read) = LogRecord::decode(&bytes[position..]).expect("truncated record");
+        records.push(record);
+        position += read;
+    }
+    Ok(records)
+}
+
+fn record(index: usize) -> LogRecord {
+    LogRecord {
+        index,
+        term: 1,
+        payload: (index as i32).to_le_bytes().to_vec(),
+    }
+}
+
+#[test]
+fn log_stream_resumes_across_compaction() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_snapshot_delta(3);
+
+    replicate(&mut replica, 1, 2);
+    assert_eq!(consume(&replica, 0), Ok(vec![record(1), record(2)]));
+    assert_eq!(consume(&replica, 2), Ok(vec![record(2)]));
+    assert_eq!(consume(&replica, 3), Ok(vec![]));
+
+    // Applying the fourth entry compacts the log, so a consumer that stopped
+    // after the second one has to restore the snapshot.
+    replicate(&mut replica, 3, 4);
+    assert_eq!(
+        consume(&replica, 3),
+        Err(LogStreamError::Compacted { snapshot_index: 4 })
+    );
+    assert_eq!(replica.snapshot().unwrap().last_included_index, 4);
+
+    replicate(&mut replica, 5, 5);
+    assert_eq!(consume(&replica, 5), Ok(vec![record(5)]));
+}
+
+#[test]
+fn log_record_decoding_needs_a_complete_record() {
+    let encoded = LogRecord {
+        index: 300,
+        term: 7,
+        payload: vec![1, 2, 3],
+    }
+    .encode();
+    assert_eq!(
+        LogRecord::decode(&encoded),
+        Some((
+            LogRecord {
+                index: 300,
+                term: 7,
+                payload: vec![1, 2, 3],
+            },
+            encoded.len()
+        ))
+    );
+    assert_eq!(LogRecord::decode(&encoded[..encoded.len() - 1]), None);
+}
@@ -0,0 +1,243 @@
+mod common;
+
+use common::{new_replica, TestReplica, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    message::{Configuration, LogEntry, Message},
+    replica::FaultToleranceCheck,
+};
+use std::time::Instant;
+
+// Win the next election with the vote of peer 1, and have the no-op committed.
+fn elect(replica: &mut TestReplica) {
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: replica.current_term(),
+        vote_granted: true,
+    });
+    assert!(replica.is_leader());
+    acknowledge(replica, 1, 1);
+    assert_eq!(replica.handle().commit_index(), 1);
+}
+
+fn acknowledge(replica: &mut TestReplica, from_id: usize, last_index: usize) {
+    replica.handle_message(Message::AppendEntryResponse {
+        from_id,
+        term: replica.current_term(),
+        success: true,
+        last_index,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    });
+}
+
+fn configs(replica: &TestReplica) -> Vec<Option<Configuration>> {
+    replica
+        .entries_in_term(replica.current_term())
+        .entries
+        .into_iter()
+        .map(|entry| entry.config)
+        .collect()
+}
+
+#[test]
+fn membership_changes_once_the_configuration_entry_is_applied() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    elect(&mut replica);
+    let peer_state_bytes = replica.peer_state_bytes();
+
+    // Only one change may be in progress at a time, and a majority of the old
+    // membership commits it.
+    replica.add_server(3);
+    replica.add_server(4);
+    acknowledge(&mut replica, 1, 3);
+    assert_eq!(replica.handle().commit_index(), 2);
+    assert_eq!(
+        configs(&replica),
+        vec![
+            None,
+            Some(Configuration {
+                members: vec![0, 1, 2, 3],
+                old_members: None,
+            })
+        ]
+    );
+    assert_eq!(replica.peer_state_bytes(), peer_state_bytes * 3 / 2);
+
+    // Past that, commits need a majority of the four members.
+    replica.remove_server(2);
+    acknowledge(&mut replica, 1, 3);
+    assert_eq!(replica.handle().commit_index(), 2);
+    acknowledge(&mut replica, 3, 3);
+    assert_eq!(replica.handle().commit_index(), 3);
+    assert_eq!(replica.peer_state_bytes(), peer_state_bytes);
+
+    // Acknowledgements from the removed Replica no longer count.
+    replica.add_server(4);
+    acknowledge(&mut replica, 2, 4);
+    assert_eq!(replica.handle().commit_index(), 3);
+}
+
+#[test]
+fn removed_leader_steps_down_and_stays_quiet() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    elect(&mut replica);
+
+    // The Leader keeps leading until its removal is applied.
+    replica.remove_server(0);
+    assert!(replica.is_leader());
+    acknowledge(&mut replica, 1, 2);
+    assert!(!replica.is_leader());
+
+    let term = replica.current_term();
+    let outbound = replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    assert!(outbound.is_empty());
+    assert_eq!(replica.current_term(), term);
+}
+
+#[test]
+fn leadership_is_not_transferred_to_a_removed_replica() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    elect(&mut replica);
+
+    // Peer 2 is on its way out, so the transfer is refused and does not hold
+    // up the next membership change.
+    replica.remove_server(2);
+    replica.transfer_leadership(2);
+    acknowledge(&mut replica, 1, 2);
+    assert_eq!(replica.handle().commit_index(), 2);
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    assert!(replica.is_leader());
+    replica.add_server(3);
+    acknowledge(&mut replica, 1, 3);
+    assert_eq!(replica.handle().commit_index(), 3);
+}
+
+#[test]
+fn votes_from_non_members_do_not_count() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 3,
+        term: replica.current_term(),
+        vote_granted: true,
+    });
+    assert!(!replica.is_leader());
+}
+
+#[test]
+fn joint_consensus_moves_the_cluster_to_disjoint_members() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    elect(&mut replica);
+
+    // A majority of the old members commits the joint configuration, and the
+    // Leader follows up with the new members alone.
+    replica.change_membership(vec![3, 4, 5]);
+    acknowledge(&mut replica, 1, 3);
+    assert_eq!(replica.handle().commit_index(), 2);
+
+    // The new configuration needs a majority of both the old and the new
+    // members.
+    acknowledge(&mut replica, 2, 3);
+    acknowledge(&mut replica, 3, 3);
+    assert_eq!(replica.handle().commit_index(), 2);
+    acknowledge(&mut replica, 4, 3);
+    assert_eq!(replica.handle().commit_index(), 3);
+    assert_eq!(
+        configs(&replica),
+        vec![
+            None,
+            Some(Configuration {
+                members: vec![3, 4, 5],
+                old_members: Some(vec![0, 1, 2]),
+            }),
+            Some(Configuration {
+                members: vec![3, 4, 5],
+                old_members: None,
+            }),
+        ]
+    );
+
+    // The Leader is not a new member, so it steps down.
+    assert!(!replica.is_leader());
+}
+
+#[test]
+fn joint_configuration_elections_need_both_majorities() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![LogEntry {
+            transition: common::NOOP,
+            index: 1,
+            term: 1,
+            config: Some(Configuration {
+                members: vec![0, 3, 4],
+                old_members: Some(vec![0, 1, 2]),
+            }),
+        }],
+        commit_index: 1,
+        timestamp: None,
+    });
+
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    for peer_id in 1..=3 {
+        assert!(!replica.is_leader());
+        replica.handle_message(Message::VoteResponse {
+            from_id: peer_id,
+            term: replica.current_term(),
+            vote_granted: true,
+        });
+    }
+    assert!(replica.is_leader());
+}
+
+#[test]
+fn shrinking_to_two_members_is_reported() {
+    let (mut replica, cluster, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    elect(&mut replica);
+
+    // Two members cannot tolerate any failure, but the change goes on.
+    replica.remove_server(2);
+    assert_eq!(
+        cluster.lock().unwrap().reduced_fault_tolerance,
+        vec![(vec![0, 1], 0, false)]
+    );
+    acknowledge(&mut replica, 1, 2);
+    assert_eq!(
+        configs(&replica),
+        vec![
+            None,
+            Some(Configuration {
+                members: vec![0, 1],
+                old_members: None,
+            })
+        ]
+    );
+}
+
+#[test]
+fn changes_below_a_strict_fault_tolerance_floor_are_rejected() {
+    let (mut replica, cluster, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_fault_tolerance_floor(1, FaultToleranceCheck::Reject);
+    elect(&mut replica);
+
+    replica.remove_server(2);
+    replica.change_membership(vec![0, 3]);
+    assert_eq!(
+        cluster.lock().unwrap().reduced_fault_tolerance,
+        vec![(vec![0, 1], 0, true), (vec![0, 3], 0, true)]
+    );
+    acknowledge(&mut replica, 1, 2);
+    assert_eq!(replica.handle().commit_index(), 1);
+
+    // Growing the cluster keeps its fault tolerance, so it is not checked.
+    replica.add_server(3);
+    acknowledge(&mut replica, 1, 2);
+    assert_eq!(cluster.lock().unwrap().reduced_fault_tolerance.len(), 2);
+    assert_eq!(configs(&replica).len(), 2);
+}
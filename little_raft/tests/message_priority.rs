@@ -0,0 +1,50 @@
+mod common;
+
+use common::{new_replica, Harness, NO_ELECTION_TIMEOUT};
+use little_raft::message::Message;
+
+// Deliver a VoteRequest from a flapping peer and a heartbeat from the Leader
+// in the same batch, and return whether the heartbeat was accepted.
+fn heartbeat_accepted(prioritize: bool) -> bool {
+    let (mut replica, cluster, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_prioritize_append_entries(prioritize);
+    let harness = Harness::start(replica, cluster, state_machine);
+
+    harness.deliver(vec![
+        Message::VoteRequest {
+            from_id: 2,
+            term: 5,
+            last_log_index: 0,
+            last_log_term: 0,
+        },
+        Message::AppendEntryRequest {
+            from_id: 1,
+            term: 1,
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![],
+            commit_index: 0,
+            timestamp: None,
+        },
+    ]);
+
+    let accepted = harness
+        .take_sent()
+        .into_iter()
+        .find_map(|(_, message)| match message {
+            Message::AppendEntryResponse { success, .. } => Some(success),
+            _ => None,
+        });
+    harness.stop();
+    accepted.unwrap()
+}
+
+#[test]
+fn append_entries_are_processed_first_when_prioritized() {
+    assert!(heartbeat_accepted(true));
+}
+
+#[test]
+fn messages_are_processed_in_order_by_default() {
+    assert!(!heartbeat_accepted(false));
+}
@@ -0,0 +1,107 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, TestReplica, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    clock::ManualClock,
+    handle::Metrics,
+    message::{LogEntry, Message},
+    replica::State,
+};
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+// Metrics as scraped through the handle, which must agree with the Replica's
+// own. Time since the last heartbeat is checked separately.
+fn scrape(replica: &TestReplica) -> Metrics {
+    let mut metrics = replica.handle().metrics();
+    let since_last_heartbeat = metrics.since_last_heartbeat.take();
+    let mut own = replica.metrics();
+    assert_eq!(
+        since_last_heartbeat.is_some(),
+        own.since_last_heartbeat.take().is_some()
+    );
+    assert_eq!(metrics, own);
+    metrics.since_last_heartbeat = since_last_heartbeat;
+    metrics
+}
+
+#[test]
+fn metrics_follow_the_replica() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    assert_eq!(
+        scrape(&replica),
+        Metrics {
+            current_term: 0,
+            commit_index: 0,
+            last_applied: 0,
+            log_len: 1,
+            index_offset: 0,
+            state: State::Follower,
+            since_last_heartbeat: None,
+            last_election_timeout: Duration::from_secs(0),
+        }
+    );
+
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 0,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: (1..=2)
+            .map(|index| LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: 1,
+                },
+                index,
+                term: 0,
+                config: None,
+            })
+            .collect(),
+        commit_index: 2,
+        timestamp: None,
+    });
+    let metrics = scrape(&replica);
+    assert_eq!(
+        (metrics.commit_index, metrics.last_applied, metrics.log_len),
+        (2, 2, 3)
+    );
+    assert!(metrics.since_last_heartbeat.unwrap() < Duration::from_secs(1));
+    assert_eq!(metrics.last_election_timeout, NO_ELECTION_TIMEOUT.0);
+
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    assert_eq!(scrape(&replica).state, State::Candidate);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    let metrics = scrape(&replica);
+    assert_eq!((metrics.current_term, metrics.state), (1, State::Leader));
+    assert_eq!(metrics.log_len, 4);
+}
+
+#[test]
+fn time_since_the_last_heartbeat_follows_the_replica_clock() {
+    let clock = ManualClock::new();
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_clock(Arc::new(clock.clone()));
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 0,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![],
+        commit_index: 0,
+        timestamp: None,
+    });
+    clock.advance(Duration::from_millis(30));
+    let since_last_heartbeat = Some(Duration::from_millis(30));
+    assert_eq!(
+        replica.handle().metrics().since_last_heartbeat,
+        since_last_heartbeat
+    );
+    assert_eq!(replica.metrics().since_last_heartbeat, since_last_heartbeat);
+}
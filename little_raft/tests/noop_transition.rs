@@ -0,0 +1,67 @@
+mod common;
+
+use common::{ArithmeticOperation, Harness, ELECTION_TIMEOUT, NOOP};
+use little_raft::state_machine::{TransitionAbandonedReason, TransitionState};
+
+#[test]
+fn noop_entries_are_not_reported() {
+    // A Replica without peers elects itself and commits on its own.
+    let harness = Harness::new(0, vec![], ELECTION_TIMEOUT);
+    harness.wait_for_leader(0);
+
+    harness.propose(vec![
+        ArithmeticOperation { id: 1, delta: 5 },
+        ArithmeticOperation { id: 2, delta: -3 },
+    ]);
+    let state_machine = harness.state_machine.clone();
+    harness.stop();
+
+    // The no-op appended by the new Leader is applied like any other entry,
+    // but the user only ever hears about their own transitions.
+    let state_machine = state_machine.lock().unwrap();
+    assert_eq!(state_machine.applied_ids, vec![NOOP.id, 1, 2]);
+    assert!(state_machine
+        .transition_states
+        .iter()
+        .all(|(id, _)| *id != NOOP.id));
+    assert_eq!(
+        state_machine
+            .transition_states
+            .iter()
+            .filter(|(_, state)| *state == TransitionState::Applied)
+            .map(|(id, _)| *id)
+            .collect::<Vec<_>>(),
+        vec![1, 2]
+    );
+}
+
+#[test]
+fn transitions_with_the_noop_id_are_abandoned() {
+    let harness = Harness::new(0, vec![], ELECTION_TIMEOUT);
+    harness.wait_for_leader(0);
+
+    harness.propose(vec![
+        ArithmeticOperation {
+            id: NOOP.id,
+            delta: 7,
+        },
+        ArithmeticOperation { id: 1, delta: 5 },
+    ]);
+    let state_machine = harness.state_machine.clone();
+    harness.stop();
+
+    let state_machine = state_machine.lock().unwrap();
+    assert_eq!(state_machine.value, 5);
+    assert_eq!(
+        state_machine.transition_states,
+        vec![
+            (
+                NOOP.id,
+                TransitionState::Abandoned(TransitionAbandonedReason::ReservedID)
+            ),
+            (1, TransitionState::Queued),
+            (1, TransitionState::Committed),
+            (1, TransitionState::Applied),
+        ]
+    );
+}
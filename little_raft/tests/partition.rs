@@ -0,0 +1,84 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, ELECTION_TIMEOUT};
+use little_raft::{message::Message, state_machine::TransitionState};
+use std::collections::HashSet;
+
+fn ack(from_id: usize, term: usize) -> Message<ArithmeticOperation> {
+    Message::AppendEntryResponse {
+        from_id,
+        term,
+        success: true,
+        last_index: 2,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }
+}
+
+#[test]
+fn leader_partitioned_from_a_minority_keeps_committing() {
+    let peer_ids = vec![1, 2, 3, 4];
+    let (mut replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    replica.set_partition(HashSet::from([1]));
+    let harness = Harness::start(replica, cluster, state_machine.clone());
+    let term = harness.elect(&[2, 3, 4]);
+    harness.propose(vec![ArithmeticOperation { id: 1, delta: 1 }]);
+
+    // Nothing reaches peer 1, and its acknowledgement does not get through.
+    assert!(harness.take_sent().iter().all(|(to_id, _)| *to_id != 1));
+    let committed = (1, TransitionState::Committed);
+    harness.deliver(vec![ack(1, term), ack(2, term)]);
+    assert!(!state_machine
+        .lock()
+        .unwrap()
+        .transition_states
+        .contains(&committed));
+
+    // The rest of the cluster is a majority.
+    harness.deliver(vec![ack(3, term)]);
+    assert!(state_machine
+        .lock()
+        .unwrap()
+        .transition_states
+        .contains(&committed));
+
+    harness.stop();
+}
+
+#[test]
+fn follower_partitioned_from_the_leader_campaigns() {
+    let (mut replica, cluster, state_machine) = new_replica(1, vec![0, 2, 3, 4], ELECTION_TIMEOUT);
+    replica.set_partition(HashSet::from([0]));
+    let harness = Harness::start(replica, cluster, state_machine);
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 0,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: Vec::new(),
+        commit_index: 0,
+        timestamp: None,
+    }]);
+    assert_eq!(harness.leader_id(), None);
+
+    // The Leader's heartbeats never arrive, so the follower campaigns among
+    // the peers it can reach.
+    let vote_requests = loop {
+        let vote_requests: Vec<usize> = harness
+            .take_sent()
+            .into_iter()
+            .filter_map(|(to_id, message)| match message {
+                Message::VoteRequest { .. } => Some(to_id),
+                _ => None,
+            })
+            .collect();
+        if !vote_requests.is_empty() {
+            break vote_requests;
+        }
+        common::settle();
+    };
+    assert_eq!(vote_requests, vec![2, 3, 4]);
+
+    harness.stop();
+}
@@ -0,0 +1,83 @@
+mod common;
+
+use common::{new_replica, settle, ArithmeticOperation, Harness, ELECTION_TIMEOUT};
+use little_raft::{message::Message, state_machine::TransitionState};
+
+fn ack(from_id: usize, term: usize, last_index: usize) -> Message<ArithmeticOperation> {
+    Message::AppendEntryResponse {
+        from_id,
+        term,
+        success: true,
+        last_index,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }
+}
+
+#[test]
+fn paused_replica_commits_but_applies_only_after_resuming() {
+    let peer_ids = vec![1, 2];
+    let (replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    let handle = replica.handle();
+    let harness = Harness::start(replica, cluster, state_machine);
+    let term = harness.elect(&peer_ids);
+
+    handle.pause_apply();
+    harness.propose(vec![
+        ArithmeticOperation { id: 1, delta: 5 },
+        ArithmeticOperation { id: 2, delta: 7 },
+    ]);
+    harness.deliver(vec![ack(1, term, 3)]);
+    assert_eq!(handle.commit_index(), 3);
+    assert_eq!(handle.last_applied(), 0);
+    assert!(harness.state_machine.lock().unwrap().applied_ids.is_empty());
+
+    handle.resume_apply();
+    settle();
+    assert_eq!(handle.last_applied(), 3);
+    let state_machine = harness.state_machine.lock().unwrap();
+    assert_eq!(state_machine.applied_ids, vec![0, 1, 2]);
+    assert_eq!(state_machine.value, 12);
+    drop(state_machine);
+
+    harness.stop();
+}
+
+#[test]
+fn leader_defers_transitions_while_backlog_is_full() {
+    let peer_ids = vec![1, 2];
+    let (mut replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    replica.set_apply_backlog_limit(2);
+    replica.pause_apply();
+    let handle = replica.handle();
+    let harness = Harness::start(replica, cluster, state_machine);
+    let term = harness.elect(&peer_ids);
+
+    // The no-op and the first two transitions fill the backlog.
+    harness.propose(vec![
+        ArithmeticOperation { id: 1, delta: 5 },
+        ArithmeticOperation { id: 2, delta: 7 },
+    ]);
+    harness.propose(vec![ArithmeticOperation { id: 3, delta: 1 }]);
+    harness.deliver(vec![ack(1, term, 3)]);
+    {
+        let state_machine = harness.state_machine.lock().unwrap();
+        assert_eq!(state_machine.pending_transitions.len(), 1);
+        assert!(!state_machine
+            .transition_states
+            .contains(&(3, TransitionState::Queued)));
+    }
+
+    // Once the backlog is applied, the Leader picks the transition up.
+    handle.resume_apply();
+    settle();
+    let state_machine = harness.state_machine.lock().unwrap();
+    assert!(state_machine.pending_transitions.is_empty());
+    assert!(state_machine
+        .transition_states
+        .contains(&(3, TransitionState::Queued)));
+    drop(state_machine);
+
+    harness.stop();
+}
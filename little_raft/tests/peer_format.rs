@@ -0,0 +1,48 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, ELECTION_TIMEOUT, LEGACY_FORMAT};
+use little_raft::message::Message;
+
+#[test]
+fn entries_are_sent_in_the_format_each_peer_understands() {
+    let peer_ids = vec![1, 2];
+    let (replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    cluster.lock().unwrap().peer_formats = vec![(2, LEGACY_FORMAT)];
+    let harness = Harness::start(replica, cluster, state_machine);
+    let term = harness.elect(&peer_ids);
+    harness.take_sent();
+
+    harness.propose(vec![ArithmeticOperation { id: 1, delta: 5 }]);
+    let deltas: Vec<(usize, i32)> = harness
+        .take_sent()
+        .into_iter()
+        .filter_map(|(to_id, message)| match message {
+            Message::AppendEntryRequest { entries, .. } => Some((to_id, entries)),
+            _ => None,
+        })
+        .flat_map(|(to_id, entries)| {
+            entries
+                .into_iter()
+                .filter(|entry| entry.transition.id == 1)
+                .map(move |entry| (to_id, entry.transition.delta))
+        })
+        .collect();
+    assert!(deltas.contains(&(1, 5)));
+    assert!(deltas.contains(&(2, 5000)));
+    assert!(deltas
+        .iter()
+        .all(|delta| *delta == (1, 5) || *delta == (2, 5000)));
+
+    // The Leader's own log keeps its format.
+    harness.deliver(vec![Message::AppendEntryResponse {
+        from_id: 2,
+        term,
+        success: true,
+        last_index: 2,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }]);
+    assert_eq!(harness.state_machine.lock().unwrap().value, 5);
+    harness.stop();
+}
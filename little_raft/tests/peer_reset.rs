@@ -0,0 +1,51 @@
+mod common;
+
+use common::{ArithmeticOperation, Harness, ELECTION_TIMEOUT};
+use little_raft::message::{LogEntry, Message};
+
+#[test]
+fn peers_are_reset_on_leader_change_and_divergence() {
+    let peer_ids = vec![1, 2];
+    let harness = Harness::new(0, peer_ids.clone(), ELECTION_TIMEOUT);
+
+    // The Replica holds three entries of the first term when it wins the
+    // second one.
+    let entries = (1..=3)
+        .map(|index| LogEntry {
+            transition: ArithmeticOperation {
+                id: index,
+                delta: 1,
+            },
+            index,
+            term: 1,
+            config: None,
+        })
+        .collect();
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 2,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries,
+        commit_index: 0,
+        timestamp: None,
+    }]);
+    let term = harness.elect(&peer_ids);
+    assert_eq!(harness.cluster.lock().unwrap().reset_peers, vec![1, 2]);
+
+    // Peer 1 only has the first entry. A duplicate of its rejection is stray
+    // and resets nothing.
+    let rejection = Message::AppendEntryResponse {
+        from_id: 1,
+        term,
+        success: false,
+        last_index: 1,
+        mismatch_index: Some(3),
+        content_hash: None,
+        request_timestamp: None,
+    };
+    harness.deliver(vec![rejection.clone(), rejection]);
+    assert_eq!(harness.cluster.lock().unwrap().reset_peers, vec![1, 2, 1]);
+
+    harness.stop();
+}
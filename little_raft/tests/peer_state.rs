@@ -0,0 +1,121 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, TestReplica, NO_ELECTION_TIMEOUT};
+use little_raft::message::{LogEntry, Message};
+use std::{collections::BTreeMap, time::Instant};
+
+const PEER_IDS: [usize; 4] = [1, 2, 3, 4];
+
+// Win the next election with the votes of peers 1 and 2, and return where the
+// new Leader starts replicating to each peer.
+fn elect(replica: &mut TestReplica) -> BTreeMap<usize, usize> {
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    let term = replica.current_term();
+    let mut outbound = Vec::new();
+    for peer_id in [1, 2] {
+        outbound = replica.handle_message(Message::VoteResponse {
+            from_id: peer_id,
+            term,
+            vote_granted: true,
+        });
+    }
+    assert!(replica.is_leader());
+    outbound
+        .into_iter()
+        .filter_map(|(to_id, message)| match message {
+            Message::AppendEntryRequest { prev_log_index, .. } => Some((to_id, prev_log_index)),
+            _ => None,
+        })
+        .collect()
+}
+
+fn acknowledge(replica: &mut TestReplica, from_id: usize, last_index: usize) {
+    replica.handle_message(Message::AppendEntryResponse {
+        from_id,
+        term: replica.current_term(),
+        success: true,
+        last_index,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    });
+}
+
+#[test]
+fn peer_state_stays_consistent_across_leader_changes() {
+    let (mut replica, _, _) = new_replica(0, PEER_IDS.to_vec(), NO_ELECTION_TIMEOUT);
+    replica.set_seed_next_index(true);
+    let peer_state_bytes = replica.peer_state_bytes();
+    assert!(peer_state_bytes > 0);
+
+    replica.handle_message(Message::VoteRequest {
+        from_id: 4,
+        term: 1,
+        last_log_index: 0,
+        last_log_term: 0,
+    });
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 4,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: (1..=3)
+            .map(|index| LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: 1,
+                },
+                index,
+                term: 1,
+                config: None,
+            })
+            .collect(),
+        commit_index: 0,
+        timestamp: None,
+    });
+
+    // Nothing is known about the peers yet, so replication starts right after
+    // the end of the log, before the no-op at index 4.
+    let prev_log_indexes = elect(&mut replica);
+    assert_eq!(
+        prev_log_indexes,
+        PEER_IDS.iter().map(|peer_id| (*peer_id, 3)).collect()
+    );
+    acknowledge(&mut replica, 1, 4);
+    acknowledge(&mut replica, 2, 2);
+
+    // A newer term dethrones the Replica before it commits anything.
+    replica.handle_message(Message::AppendEntryResponse {
+        from_id: 3,
+        term: replica.current_term() + 1,
+        success: false,
+        last_index: 0,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    });
+    assert!(!replica.is_leader());
+    assert_eq!(replica.handle().commit_index(), 0);
+
+    // The next term starts from what each peer acknowledged, but counts no
+    // acknowledgement from the previous term toward committing its no-op.
+    let prev_log_indexes = elect(&mut replica);
+    assert_eq!(
+        prev_log_indexes,
+        vec![(1, 4), (2, 2), (3, 4), (4, 4)].into_iter().collect()
+    );
+    acknowledge(&mut replica, 3, 5);
+    assert_eq!(replica.handle().commit_index(), 0);
+    acknowledge(&mut replica, 4, 5);
+    assert_eq!(replica.handle().commit_index(), 5);
+
+    // The bookkeeping neither grows nor shrinks with leader changes.
+    assert_eq!(replica.peer_state_bytes(), peer_state_bytes);
+}
+
+#[test]
+fn peer_state_grows_linearly_with_the_cluster() {
+    let (small, _, _) = new_replica(0, PEER_IDS.to_vec(), NO_ELECTION_TIMEOUT);
+    let (large, _, _) = new_replica(0, (1..=8).collect(), NO_ELECTION_TIMEOUT);
+    assert_eq!(large.peer_state_bytes(), 2 * small.peer_state_bytes());
+}
@@ -0,0 +1,120 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, TestReplica, NO_ELECTION_TIMEOUT};
+use little_raft::message::{LogEntry, Message};
+use std::time::{Duration, Instant};
+
+// Make the Replica the Leader of term 2, with ten entries from term 1 and its
+// no-op at index 11.
+fn elect_with_log(replica: &mut TestReplica) {
+    replica.handle_message(Message::VoteRequest {
+        from_id: 1,
+        term: 1,
+        last_log_index: 0,
+        last_log_term: 0,
+    });
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: (1..=10)
+            .map(|index| LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: 1,
+                },
+                index,
+                term: 1,
+                config: None,
+            })
+            .collect(),
+        commit_index: 0,
+        timestamp: None,
+    });
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 2,
+        vote_granted: true,
+    });
+    assert!(replica.is_leader());
+}
+
+// Indexes of the entries of each AppendEntryRequest sent to Replica 2.
+fn batches(outbound: Vec<(usize, Message<ArithmeticOperation>)>) -> Vec<Vec<usize>> {
+    outbound
+        .into_iter()
+        .filter_map(|(to_id, message)| match message {
+            Message::AppendEntryRequest { entries, .. } if to_id == 2 => {
+                Some(entries.iter().map(|entry| entry.index).collect())
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn respond(
+    replica: &mut TestReplica,
+    success: bool,
+    last_index: usize,
+    mismatch_index: Option<usize>,
+) -> Vec<Vec<usize>> {
+    batches(replica.handle_message(Message::AppendEntryResponse {
+        from_id: 2,
+        term: 2,
+        success,
+        last_index,
+        mismatch_index,
+        content_hash: None,
+        request_timestamp: None,
+    }))
+}
+
+#[test]
+fn leader_pipelines_batches_within_the_window() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_max_entries_per_append(2);
+    replica.set_max_inflight_appends(3);
+    elect_with_log(&mut replica);
+
+    // Replica 2 has nothing, so it gets the first three batches at once.
+    assert!(respond(&mut replica, false, 0, Some(10)).is_empty());
+    let heartbeat = replica.handle_timeout(Instant::now() + Duration::from_secs(1));
+    assert_eq!(batches(heartbeat), vec![vec![1, 2], vec![3, 4], vec![5, 6]]);
+
+    // Each acknowledgement frees a slot in the window, whatever their order.
+    // A late acknowledgement does not move Replica 2's progress back.
+    assert_eq!(respond(&mut replica, true, 4, None), vec![vec![7, 8]]);
+    assert_eq!(respond(&mut replica, true, 2, None), vec![vec![9, 10]]);
+    assert_eq!(respond(&mut replica, true, 6, None), vec![vec![11]]);
+    assert_eq!(replica.handle().commit_index(), 0);
+
+    // Batch [7, 8] was lost, so Replica 2 rejects the batches after it. The
+    // Leader goes back to index 7 once, ignoring the later rejection.
+    assert!(respond(&mut replica, false, 6, Some(8)).is_empty());
+    assert!(respond(&mut replica, false, 6, Some(10)).is_empty());
+    let heartbeat = replica.handle_timeout(Instant::now() + Duration::from_secs(1));
+    assert_eq!(batches(heartbeat), vec![vec![7, 8], vec![9, 10], vec![11]]);
+
+    assert!(respond(&mut replica, true, 11, None).is_empty());
+    assert_eq!(replica.handle().commit_index(), 11);
+}
+
+#[test]
+fn full_window_still_sends_heartbeats() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_max_entries_per_append(2);
+    replica.set_max_inflight_appends(1);
+    elect_with_log(&mut replica);
+
+    respond(&mut replica, false, 0, Some(10));
+    let heartbeat = replica.handle_timeout(Instant::now() + Duration::from_secs(1));
+    assert_eq!(batches(heartbeat), vec![vec![1, 2]]);
+
+    // The acknowledgement of the batch was lost, so the next heartbeat
+    // carries no entries. Replica 2's answer to it reopens the window.
+    let heartbeat = replica.handle_timeout(Instant::now() + Duration::from_secs(2));
+    assert_eq!(batches(heartbeat), vec![Vec::<usize>::new()]);
+    assert_eq!(respond(&mut replica, true, 2, None), vec![vec![3, 4]]);
+}
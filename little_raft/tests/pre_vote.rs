@@ -0,0 +1,130 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, TestReplica, NO_ELECTION_TIMEOUT};
+use little_raft::message::{LogEntry, Message};
+use std::time::Instant;
+
+fn pre_vote_replica() -> TestReplica {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_pre_vote(true);
+    replica
+}
+
+fn pre_vote_request(from_id: usize, term: usize) -> Message<ArithmeticOperation> {
+    Message::PreVoteRequest {
+        from_id,
+        term,
+        last_log_index: 0,
+        last_log_term: 0,
+    }
+}
+
+#[test]
+fn election_starts_only_after_a_majority_of_pre_votes() {
+    let mut replica = pre_vote_replica();
+
+    // A Replica that cannot reach its peers keeps asking for pre-votes in the
+    // same term instead of inflating its term.
+    for _ in 0..2 {
+        let outbound = replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+        assert_eq!(
+            outbound,
+            vec![(1, pre_vote_request(0, 1)), (2, pre_vote_request(0, 1))]
+        );
+    }
+
+    let outbound = replica.handle_message(Message::PreVoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    assert_eq!(outbound.len(), 2);
+    for (_, message) in outbound {
+        assert!(matches!(message, Message::VoteRequest { term: 1, .. }));
+    }
+}
+
+#[test]
+fn pre_votes_are_refused_while_the_leader_is_alive() {
+    let mut replica = pre_vote_replica();
+
+    // Granting a pre-vote does not move the Replica to the asked term, so a
+    // pre-vote for a lower term is still granted afterwards.
+    for term in [5, 1] {
+        let outbound = replica.handle_message(pre_vote_request(2, term));
+        assert_eq!(
+            outbound,
+            vec![(
+                2,
+                Message::PreVoteResponse {
+                    from_id: 0,
+                    term,
+                    vote_granted: true,
+                }
+            )]
+        );
+    }
+
+    // Once the Replica hears from a Leader, it refuses to replace it.
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![LogEntry {
+            transition: ArithmeticOperation { id: 1, delta: 1 },
+            index: 1,
+            term: 1,
+            config: None,
+        }],
+        commit_index: 0,
+        timestamp: None,
+    });
+    let outbound = replica.handle_message(Message::PreVoteRequest {
+        from_id: 2,
+        term: 2,
+        last_log_index: 1,
+        last_log_term: 1,
+    });
+    assert!(matches!(
+        outbound[..],
+        [(
+            2,
+            Message::PreVoteResponse {
+                vote_granted: false,
+                ..
+            }
+        )]
+    ));
+}
+
+#[test]
+fn late_pre_votes_are_dropped_once_the_leader_is_heard_from() {
+    let mut replica = pre_vote_replica();
+    let heartbeat = Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![],
+        commit_index: 0,
+        timestamp: None,
+    };
+    replica.handle_message(heartbeat.clone());
+
+    // The Leader goes quiet long enough for the Replica to ask for pre-votes,
+    // then turns out to be alive before the pre-votes come back.
+    let outbound = replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    assert_eq!(
+        outbound,
+        vec![(1, pre_vote_request(0, 2)), (2, pre_vote_request(0, 2))]
+    );
+    replica.handle_message(heartbeat);
+    let outbound = replica.handle_message(Message::PreVoteResponse {
+        from_id: 2,
+        term: 2,
+        vote_granted: true,
+    });
+    assert!(outbound.is_empty());
+    assert_eq!(replica.current_term(), 1);
+}
@@ -0,0 +1,51 @@
+mod common;
+
+use common::{ArithmeticOperation, Harness, ELECTION_TIMEOUT};
+use little_raft::message::Message;
+use std::mem;
+
+#[test]
+fn proposal_queue_follows_appends_and_commits() {
+    let peer_ids = vec![1, 2];
+    let harness = Harness::new(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    harness.elect(&peer_ids);
+    harness.propose(vec![
+        ArithmeticOperation { id: 1, delta: 1 },
+        ArithmeticOperation { id: 2, delta: 2 },
+        ArithmeticOperation { id: 3, delta: 3 },
+    ]);
+
+    let (cluster, state_machine) = (harness.cluster.clone(), harness.state_machine.clone());
+    let replica = harness.stop();
+    assert_eq!(replica.proposal_queue_depth(), 3);
+    assert_eq!(
+        replica.proposal_queue_bytes(),
+        3 * mem::size_of::<ArithmeticOperation>()
+    );
+
+    // Once a peer acknowledges the whole log, everything is committed.
+    let term = cluster
+        .lock()
+        .unwrap()
+        .sent_messages
+        .iter()
+        .find_map(|(_, message)| match message {
+            Message::AppendEntryRequest { term, .. } => Some(*term),
+            _ => None,
+        })
+        .unwrap();
+    let harness = Harness::start(replica, cluster, state_machine);
+    harness.deliver(vec![Message::AppendEntryResponse {
+        from_id: 1,
+        term,
+        success: true,
+        last_index: 4,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }]);
+
+    let replica = harness.stop();
+    assert_eq!(replica.proposal_queue_depth(), 0);
+    assert_eq!(replica.proposal_queue_bytes(), 0);
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use common::{
+    new_replica, settle, ArithmeticOperation, Harness, ELECTION_TIMEOUT, NO_ELECTION_TIMEOUT,
+};
+use little_raft::{
+    handle::ProposeError,
+    message::Message,
+    state_machine::{TransitionAbandonedReason, TransitionRejectedReason, TransitionState},
+};
+use std::{
+    collections::BTreeMap,
+    thread,
+    time::{Duration, Instant},
+};
+
+// Far enough for the proposals that get an answer to get it.
+fn deadline() -> Instant {
+    Instant::now() + Duration::from_secs(10)
+}
+
+#[test]
+fn proposals_resolve_once_committed() {
+    let (replica, cluster, state_machine) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    let proposals = replica.propose_handle();
+    assert_eq!(
+        proposals.propose_blocking(ArithmeticOperation { id: 1, delta: 1 }, deadline()),
+        Err(ProposeError::NotLeader { leader_id: None })
+    );
+
+    let harness = Harness::start(replica, cluster, state_machine);
+    let term = harness.elect(&[1, 2]);
+    harness.state_machine.lock().unwrap().invalid_ids.push(3);
+    let proposer = {
+        let proposals = proposals.clone();
+        thread::spawn(move || {
+            (
+                proposals.propose_blocking(ArithmeticOperation { id: 2, delta: 1 }, deadline()),
+                proposals.propose_blocking(ArithmeticOperation { id: 3, delta: 1 }, deadline()),
+            )
+        })
+    };
+    settle();
+    assert!(!proposer.is_finished());
+
+    // The proposal was appended after the no-op, and commits once a peer
+    // acknowledges it.
+    harness.deliver(vec![Message::AppendEntryResponse {
+        from_id: 1,
+        term,
+        success: true,
+        last_index: 2,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }]);
+    let (committed, rejected) = proposer.join().unwrap();
+    assert_eq!(committed, Ok(()));
+    assert_eq!(
+        rejected,
+        Err(ProposeError::Rejected(TransitionRejectedReason::Invalid(
+            "3 is invalid".to_string()
+        )))
+    );
+    assert!(harness
+        .state_machine
+        .lock()
+        .unwrap()
+        .transition_states
+        .contains(&(2, TransitionState::Committed)));
+    harness.stop();
+}
+
+#[test]
+fn proposals_overwritten_by_a_snapshot_are_abandoned() {
+    let (mut replica, _, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_confirm_leadership_before_writes(true);
+    let proposals = replica.propose_handle();
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    let proposer = thread::spawn(move || {
+        proposals.propose_blocking(ArithmeticOperation { id: 7, delta: 1 }, deadline())
+    });
+    settle();
+    let acknowledge = |term, success| Message::AppendEntryResponse {
+        from_id: 1,
+        term,
+        success,
+        last_index: 1,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    };
+    replica.handle_message(acknowledge(1, true));
+    assert!(state_machine
+        .lock()
+        .unwrap()
+        .transition_states
+        .contains(&(7, TransitionState::Queued)));
+
+    // A Leader of the next term replaces the proposal's entry with a
+    // snapshot that does not hold it.
+    replica.handle_message(acknowledge(2, false));
+    replica.handle_message(Message::InstallSnapshotRequest {
+        from_id: 1,
+        term: 2,
+        last_included_index: 2,
+        last_included_term: 2,
+        data: 5i32.to_le_bytes().to_vec(),
+        offset: 0,
+        done: true,
+        content_hash: None,
+        applied_sequences: BTreeMap::new(),
+    });
+    assert_eq!(
+        proposer.join().unwrap(),
+        Err(ProposeError::Abandoned(
+            TransitionAbandonedReason::Overwritten
+        ))
+    );
+    assert!(state_machine.lock().unwrap().transition_states.contains(&(
+        7,
+        TransitionState::Abandoned(TransitionAbandonedReason::Overwritten)
+    )));
+}
+
+#[test]
+fn proposals_of_a_leader_that_stepped_down_time_out() {
+    let (mut replica, _, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_confirm_leadership_before_writes(true);
+    let proposals = replica.propose_handle();
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    let deadline = Instant::now() + Duration::from_millis(500);
+    let proposer = thread::spawn(move || {
+        proposals.propose_blocking(ArithmeticOperation { id: 7, delta: 1 }, deadline)
+    });
+    settle();
+    let acknowledge = |term, success| Message::AppendEntryResponse {
+        from_id: 1,
+        term,
+        success,
+        last_index: 1,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    };
+    replica.handle_message(acknowledge(1, true));
+    assert!(state_machine
+        .lock()
+        .unwrap()
+        .transition_states
+        .contains(&(7, TransitionState::Queued)));
+
+    // The Leader steps down, and nothing tells whether the next Leader keeps
+    // the proposal's entry.
+    replica.handle_message(acknowledge(2, false));
+    assert_eq!(proposer.join().unwrap(), Err(ProposeError::Timeout));
+    assert!(Instant::now() >= deadline);
+}
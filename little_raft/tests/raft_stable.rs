@@ -75,15 +75,7 @@ struct ThreadCluster {
 
 impl Cluster<ArithmeticOperation> for ThreadCluster {
     fn register_leader(&mut self, leader_id: Option<usize>) {
-        if let Some(id) = leader_id {
-            if id == self.id {
-                self.is_leader = true;
-            } else {
-                self.is_leader = false;
-            }
-        } else {
-            self.is_leader = false;
-        }
+        self.is_leader = leader_id == Some(self.id);
     }
 
     fn send_message(&mut self, to_id: usize, message: Message<ArithmeticOperation>) {
@@ -125,13 +117,11 @@ fn create_clusters(
     clusters
 }
 
+type Transmitters = BTreeMap<usize, Sender<Message<ArithmeticOperation>>>;
+type MessageReceivers = Vec<Receiver<Message<ArithmeticOperation>>>;
+
 // Create channels for the threads to communicate with.
-fn create_communication_between_clusters(
-    n: usize,
-) -> (
-    BTreeMap<usize, Sender<Message<ArithmeticOperation>>>,
-    Vec<Receiver<Message<ArithmeticOperation>>>,
-) {
+fn create_communication_between_clusters(n: usize) -> (Transmitters, MessageReceivers) {
     let (mut transmitters, mut receivers) = (BTreeMap::new(), Vec::new());
     for i in 0..n {
         let (tx, rx) = unbounded::<Message<ArithmeticOperation>>();
@@ -179,14 +169,14 @@ fn create_state_machines(
 // Create sending ends of message notifiers, sending ends of transition
 // notifiers, receiving ends of message notifiers, receiving neds of transition
 // notifiers.
-fn create_notifiers(
-    n: usize,
-) -> (
+type Notifiers = (
     Vec<Sender<()>>,
     Vec<Sender<()>>,
     Vec<Receiver<()>>,
     Vec<Receiver<()>>,
-) {
+);
+
+fn create_notifiers(n: usize) -> Notifiers {
     let mut message_tx = Vec::new();
     let mut message_rx = Vec::new();
     let mut transition_tx = Vec::new();
@@ -302,7 +292,7 @@ fn run_replicas() {
                 (MIN_ELECTION_TIMEOUT, MAX_ELECTION_TIMEOUT),
             );
 
-            replica.start(m_rx, t_rx);
+            replica.start(m_rx, t_rx).unwrap();
         });
     }
 
@@ -339,7 +329,7 @@ fn run_replicas() {
     // Below we confirm that every replica applied the same transitions in the
     // same order.
     let applied_transactions: Vec<(usize, usize)> = applied_transitions_rx.try_iter().collect();
-    let expected_vec: Vec<usize> = vec![0, 1, 2, 3, 4];
+    let expected_vec: Vec<usize> = vec![1, 2, 3, 4];
     assert_eq!(
         expected_vec,
         applied_transactions.iter().fold(Vec::new(), |mut acc, x| {
@@ -0,0 +1,290 @@
+mod common;
+
+use common::{
+    ArithmeticOperation, Calculator, Harness, ScriptedCluster, TestReplica, HEARTBEAT_TIMEOUT,
+    NOOP, NO_ELECTION_TIMEOUT,
+};
+use little_raft::{
+    message::{LogEntry, Message},
+    replica::{RejectReason, Replica},
+};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+fn new_replica(
+    state_machine: Calculator,
+) -> (
+    TestReplica,
+    Arc<Mutex<ScriptedCluster>>,
+    Arc<Mutex<Calculator>>,
+) {
+    let cluster = Arc::new(Mutex::new(ScriptedCluster::default()));
+    let state_machine = Arc::new(Mutex::new(state_machine));
+    let replica = Replica::new(
+        0,
+        vec![1, 2],
+        cluster.clone(),
+        state_machine.clone(),
+        NOOP,
+        HEARTBEAT_TIMEOUT,
+        NO_ELECTION_TIMEOUT,
+    );
+    (replica, cluster, state_machine)
+}
+
+fn start_replica(state_machine: Calculator) -> (Harness, Arc<Mutex<Calculator>>) {
+    let (replica, cluster, state_machine) = new_replica(state_machine);
+    (
+        Harness::start(replica, cluster, state_machine.clone()),
+        state_machine,
+    )
+}
+
+// Entries from..=to of the given term, each adding its index.
+fn entries(from: usize, to: usize, term: usize) -> Vec<LogEntry<ArithmeticOperation>> {
+    (from..=to)
+        .map(|index| LogEntry {
+            transition: ArithmeticOperation {
+                id: index,
+                delta: index as i32,
+            },
+            index,
+            term,
+            config: None,
+        })
+        .collect()
+}
+
+// The Leader replicates and commits the whole log, adding 1, 2 and 3.
+fn replicate_log(harness: &Harness) {
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: entries(1, 3, 1),
+        commit_index: 3,
+        timestamp: None,
+    }]);
+}
+
+#[test]
+fn durably_applied_entries_are_not_applied_again() {
+    // The state machine had applied the first two entries before the restart.
+    let (harness, state_machine) = start_replica(Calculator {
+        value: 3,
+        applied_index: 2,
+        ..Calculator::default()
+    });
+    replicate_log(&harness);
+    harness.stop();
+
+    let state_machine = state_machine.lock().unwrap();
+    assert_eq!(state_machine.applied_ids, vec![3]);
+    assert_eq!(state_machine.value, 6);
+}
+
+#[test]
+fn crash_during_apply_leaves_state_and_index_consistent() {
+    let (harness, state_machine) = start_replica(Calculator {
+        crash_at_index: Some(3),
+        ..Calculator::default()
+    });
+    replicate_log(&harness);
+    assert!(harness.join().is_err());
+
+    // Restart from what the state machine had recorded before the crash.
+    let (value, applied_index) = {
+        let state_machine = state_machine.lock().unwrap_or_else(|e| e.into_inner());
+        (state_machine.value, state_machine.applied_index)
+    };
+    assert_eq!((value, applied_index), (3, 2));
+    let (harness, state_machine) = start_replica(Calculator {
+        value,
+        applied_index,
+        ..Calculator::default()
+    });
+    replicate_log(&harness);
+    harness.stop();
+
+    let state_machine = state_machine.lock().unwrap();
+    assert_eq!(state_machine.applied_ids, vec![3]);
+    assert_eq!(state_machine.value, 6);
+    assert_eq!(state_machine.applied_index, 3);
+}
+
+#[test]
+fn restarted_replica_keeps_its_term_vote_and_log() {
+    let (harness, state_machine) = start_replica(Calculator::default());
+    harness.deliver(vec![Message::VoteRequest {
+        from_id: 1,
+        term: 1,
+        last_log_index: 0,
+        last_log_term: 0,
+    }]);
+    replicate_log(&harness);
+    harness.stop();
+
+    let (hard_state, persisted_log) = {
+        let state_machine = state_machine.lock().unwrap();
+        (
+            state_machine.hard_state,
+            state_machine.persisted_log.clone(),
+        )
+    };
+    assert_eq!(hard_state, (1, Some(1)));
+    assert_eq!(persisted_log.len(), 3);
+    let (harness, _) = start_replica(Calculator {
+        hard_state,
+        persisted_log,
+        ..Calculator::default()
+    });
+
+    // The Replica already voted in term 1, and its log is ahead of a
+    // Candidate that lost it.
+    harness.deliver(vec![
+        Message::VoteRequest {
+            from_id: 2,
+            term: 1,
+            last_log_index: 3,
+            last_log_term: 1,
+        },
+        Message::VoteRequest {
+            from_id: 2,
+            term: 2,
+            last_log_index: 0,
+            last_log_term: 0,
+        },
+    ]);
+    assert!(!harness.take_sent().iter().any(|(_, message)| matches!(
+        message,
+        Message::VoteResponse {
+            vote_granted: true,
+            ..
+        }
+    )));
+    let replica = harness.stop();
+    assert_eq!(replica.vote_rejections(RejectReason::AlreadyVoted), 1);
+    assert_eq!(replica.vote_rejections(RejectReason::LogBehind), 1);
+}
+
+// Whether the Replica acknowledged entries up to last_index.
+fn acknowledged(outbound: &[(usize, Message<ArithmeticOperation>)], last_index: usize) -> bool {
+    outbound.iter().any(|(_, message)| {
+        matches!(message, Message::AppendEntryResponse { success: true, last_index: index, .. }
+            if *index == last_index)
+    })
+}
+
+// The state a Replica persisted before a restart. With lose_applied, the state
+// machine lost its applied state, but not the log and snapshot.
+fn persisted(state_machine: &Mutex<Calculator>, lose_applied: bool) -> Calculator {
+    let state_machine = state_machine.lock().unwrap();
+    let (value, applied_index) = match lose_applied {
+        true => (0, 0),
+        false => (state_machine.value, state_machine.applied_index),
+    };
+    Calculator {
+        value,
+        applied_index,
+        hard_state: state_machine.hard_state,
+        persisted_log: state_machine.persisted_log.clone(),
+        saved_snapshot: state_machine.saved_snapshot.clone(),
+        ..Calculator::default()
+    }
+}
+
+#[test]
+fn restarted_replica_resumes_after_an_installed_snapshot() {
+    // The follower holds entries of a term the Leader's snapshot replaced,
+    // then receives the entries that follow the snapshot.
+    let (mut replica, _, state_machine) = new_replica(Calculator::default());
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: entries(1, 3, 1),
+        commit_index: 0,
+        timestamp: None,
+    });
+    replica.handle_message(Message::InstallSnapshotRequest {
+        from_id: 1,
+        term: 2,
+        last_included_index: 5,
+        last_included_term: 2,
+        data: 15i32.to_le_bytes().to_vec(),
+        offset: 0,
+        done: true,
+        content_hash: None,
+        applied_sequences: BTreeMap::new(),
+    });
+    let outbound = replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 2,
+        prev_log_index: 5,
+        prev_log_term: 2,
+        entries: entries(6, 7, 2),
+        commit_index: 7,
+        timestamp: None,
+    });
+    assert!(acknowledged(&outbound, 7));
+    assert_eq!(state_machine.lock().unwrap().value, 28);
+
+    // Whether or not the state machine kept what it applied, the restarted
+    // Replica picks up from the snapshot and the entries after it.
+    for lose_applied in [false, true] {
+        let (mut replica, _, restarted) = new_replica(persisted(&state_machine, lose_applied));
+        let metrics = replica.metrics();
+        assert_eq!((metrics.index_offset, metrics.log_len), (5, 3));
+        let outbound = replica.handle_message(Message::AppendEntryRequest {
+            from_id: 1,
+            term: 2,
+            prev_log_index: 7,
+            prev_log_term: 2,
+            entries: entries(8, 8, 2),
+            commit_index: 8,
+            timestamp: None,
+        });
+        assert!(acknowledged(&outbound, 8));
+        assert_eq!(restarted.lock().unwrap().value, 36);
+    }
+}
+
+#[test]
+fn leader_tolerates_applied_entries_missing_from_its_log() {
+    // The state machine persists what it applied, but not the log.
+    let (mut replica, _, state_machine) = new_replica(Calculator {
+        value: 6,
+        applied_index: 3,
+        ..Calculator::default()
+    });
+    replica.set_confirm_leadership_before_writes(true);
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    assert!(replica.is_leader());
+
+    // Once a peer confirms the Leader, it takes the pending transition.
+    state_machine
+        .lock()
+        .unwrap()
+        .pending_transitions
+        .push(ArithmeticOperation { id: 4, delta: 4 });
+    replica.handle_message(Message::AppendEntryResponse {
+        from_id: 1,
+        term: 1,
+        success: true,
+        last_index: 1,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    });
+    assert_eq!(replica.metrics().log_len, 3);
+}
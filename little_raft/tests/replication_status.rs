@@ -0,0 +1,65 @@
+mod common;
+
+use common::{new_replica, NO_ELECTION_TIMEOUT};
+use little_raft::{message::Message, replica::PeerProgress};
+use std::time::Instant;
+
+#[test]
+fn replication_status_shows_how_far_behind_each_peer_is() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    assert!(replica.replication_status().is_empty());
+
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    replica.handle_message(Message::AppendEntryResponse {
+        from_id: 1,
+        term: 1,
+        success: true,
+        last_index: 1,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    });
+    assert_eq!(replica.handle().commit_index(), 1);
+
+    // Replica 2 never acknowledged the no-op.
+    assert_eq!(
+        replica.replication_status(),
+        vec![
+            (
+                1,
+                PeerProgress {
+                    next_index: 2,
+                    match_index: 1,
+                    lag: 0,
+                }
+            ),
+            (
+                2,
+                PeerProgress {
+                    next_index: 1,
+                    match_index: 0,
+                    lag: 1,
+                }
+            ),
+        ]
+        .into_iter()
+        .collect()
+    );
+
+    // A Replica that is no longer the Leader has nothing to report.
+    replica.handle_message(Message::AppendEntryResponse {
+        from_id: 2,
+        term: 2,
+        success: false,
+        last_index: 0,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    });
+    assert!(replica.replication_status().is_empty());
+}
@@ -0,0 +1,26 @@
+mod common;
+
+use common::{new_replica, ELECTION_TIMEOUT};
+use std::time::{Duration, Instant};
+
+// Election timeouts the Replica picks over a few elections it cannot win.
+fn election_timeouts(seed: u64) -> Vec<Duration> {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    replica.set_rng_seed(seed);
+    (0..5)
+        .map(|_| {
+            replica.handle_timeout(Instant::now() + ELECTION_TIMEOUT.1);
+            replica.last_election_timeout()
+        })
+        .collect()
+}
+
+#[test]
+fn seeded_replicas_pick_the_same_timeouts() {
+    let timeouts = election_timeouts(7);
+    assert_eq!(election_timeouts(7), timeouts);
+    assert_ne!(election_timeouts(8), timeouts);
+    assert!(timeouts
+        .iter()
+        .all(|timeout| (ELECTION_TIMEOUT.0..=ELECTION_TIMEOUT.1).contains(timeout)));
+}
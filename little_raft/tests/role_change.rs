@@ -0,0 +1,60 @@
+mod common;
+
+use common::{new_replica, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    message::Message,
+    replica::{RoleChange, State},
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+#[test]
+fn role_changes_are_reported_with_their_term() {
+    let (mut replica, cluster, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = changes.clone();
+    // The Cluster is not locked when the callback is called.
+    replica.set_on_state_change(move |change| {
+        assert!(cluster.try_lock().is_ok());
+        recorded.lock().unwrap().push(change);
+    });
+
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    replica.handle_message(Message::AppendEntryResponse {
+        from_id: 2,
+        term: 2,
+        success: false,
+        last_index: 0,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    });
+
+    assert_eq!(
+        *changes.lock().unwrap(),
+        vec![
+            RoleChange {
+                old_state: State::Follower,
+                new_state: State::Candidate,
+                term: 1,
+            },
+            RoleChange {
+                old_state: State::Candidate,
+                new_state: State::Leader,
+                term: 1,
+            },
+            RoleChange {
+                old_state: State::Leader,
+                new_state: State::Follower,
+                term: 2,
+            },
+        ]
+    );
+}
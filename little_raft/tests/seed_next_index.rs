@@ -0,0 +1,57 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, ELECTION_TIMEOUT};
+use little_raft::message::Message;
+
+// Index of the last entry in the log of the lagging peer.
+const LAGGING_LAST_INDEX: usize = 1;
+
+// Elect the Replica, grow its log while peer 1 lags behind, make the Replica
+// lose and regain leadership, and count the round-trips it takes to reach
+// peer 1 again.
+fn round_trips_after_reelection(seed_next_index: bool) -> usize {
+    let peer_ids = vec![1, 2];
+    let (mut replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    replica.set_seed_next_index(seed_next_index);
+    let harness = Harness::start(replica, cluster, state_machine);
+    harness.elect(&peer_ids);
+    harness.propose(
+        (1..=5)
+            .map(|id| ArithmeticOperation { id, delta: 1 })
+            .collect(),
+    );
+
+    // Peer 1 only acknowledges the no-op of the first term before peer 2
+    // reports a newer term.
+    harness.deliver(vec![
+        Message::AppendEntryResponse {
+            from_id: 1,
+            term: 1,
+            success: true,
+            last_index: LAGGING_LAST_INDEX,
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        },
+        Message::AppendEntryResponse {
+            from_id: 2,
+            term: 2,
+            success: false,
+            last_index: 0,
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        },
+    ]);
+    harness.elect(&peer_ids);
+
+    let round_trips = harness.round_trips_to_reach(1, LAGGING_LAST_INDEX, LAGGING_LAST_INDEX);
+    harness.stop();
+    round_trips
+}
+
+#[test]
+fn seeded_next_index_skips_rejection_round_trip() {
+    assert_eq!(round_trips_after_reelection(false), 2);
+    assert_eq!(round_trips_after_reelection(true), 1);
+}
@@ -0,0 +1,43 @@
+mod common;
+
+use common::{Harness, ELECTION_TIMEOUT};
+use little_raft::message::{Message, MessagePriority};
+
+#[test]
+fn heartbeats_are_high_priority() {
+    let peer_ids = vec![1, 2];
+    let harness = Harness::new(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    let term = harness.elect(&peer_ids);
+
+    // Peer 1 has the whole log, so it only gets heartbeats, while peer 2 is
+    // still sent the no-op entry.
+    harness.deliver(vec![Message::AppendEntryResponse {
+        from_id: 1,
+        term,
+        success: true,
+        last_index: 1,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }]);
+    harness.take_sent();
+    harness.deliver(vec![]);
+
+    let cluster = harness.cluster.clone();
+    harness.stop();
+    let cluster = cluster.lock().unwrap();
+    assert!(!cluster.sent_messages.is_empty());
+    for ((to_id, message), priority) in cluster.sent_messages.iter().zip(&cluster.sent_priorities) {
+        match (to_id, message) {
+            (1, Message::AppendEntryRequest { entries, .. }) => {
+                assert!(entries.is_empty());
+                assert_eq!(*priority, MessagePriority::High);
+            }
+            (2, Message::AppendEntryRequest { entries, .. }) => {
+                assert!(!entries.is_empty());
+                assert_eq!(*priority, MessagePriority::Normal);
+            }
+            message => panic!("unexpected message {:?}", message),
+        }
+    }
+}
@@ -0,0 +1,52 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, ELECTION_TIMEOUT};
+use little_raft::state_machine::{StateMachine, TransitionState};
+use std::sync::{Arc, Mutex};
+
+// A reimplementation of the Calculator that is being migrated to. It has a bug
+// which makes it ignore subtractions.
+#[derive(Default)]
+struct NewCalculator {
+    value: i64,
+    applied_ids: Vec<usize>,
+}
+
+impl StateMachine<ArithmeticOperation> for NewCalculator {
+    fn apply_transition(&mut self, transition: ArithmeticOperation) {
+        self.value += i64::from(transition.delta.max(0));
+        self.applied_ids.push(transition.id);
+    }
+
+    fn register_transition_state(&mut self, _: usize, _: TransitionState) {}
+
+    fn get_pending_transitions(&mut self) -> Vec<ArithmeticOperation> {
+        Vec::new()
+    }
+}
+
+#[test]
+fn shadow_state_machine_is_applied_the_same_transitions() {
+    let (mut replica, cluster, state_machine) = new_replica(0, vec![], ELECTION_TIMEOUT);
+    let shadow = Arc::new(Mutex::new(NewCalculator::default()));
+    replica.set_shadow_state_machine(shadow.clone());
+    let harness = Harness::start(replica, cluster, state_machine.clone());
+    harness.wait_for_leader(0);
+
+    harness.propose(vec![
+        ArithmeticOperation { id: 1, delta: 5 },
+        ArithmeticOperation { id: 2, delta: 3 },
+    ]);
+    {
+        let (state_machine, shadow) = (state_machine.lock().unwrap(), shadow.lock().unwrap());
+        assert_eq!(state_machine.applied_ids, shadow.applied_ids);
+        assert_eq!(i64::from(state_machine.value), shadow.value);
+    }
+
+    // The bug in the shadow state machine shows up as a diverging value.
+    harness.propose(vec![ArithmeticOperation { id: 3, delta: -4 }]);
+    harness.stop();
+    let (state_machine, shadow) = (state_machine.lock().unwrap(), shadow.lock().unwrap());
+    assert_eq!(state_machine.applied_ids, shadow.applied_ids);
+    assert_ne!(i64::from(state_machine.value), shadow.value);
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use common::{
+    new_replica, settle, ArithmeticOperation, Harness, ELECTION_TIMEOUT, NO_ELECTION_TIMEOUT,
+};
+use crossbeam_channel::unbounded;
+use little_raft::message::{LogEntry, Message};
+
+#[test]
+fn shutdown_stops_the_replica_right_away() {
+    // A follower that would otherwise sleep until its election timeout.
+    let (replica, cluster, state_machine) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    let handle = replica.handle();
+    let harness = Harness::start(replica, cluster, state_machine);
+    handle.shutdown();
+    settle();
+    assert!(harness.halted());
+    assert!(harness.join().unwrap().1.is_ok());
+
+    // A Leader, which also waits for transitions and heartbeats.
+    let peer_ids = vec![1, 2];
+    let (replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    let handle = replica.handle();
+    let harness = Harness::start(replica, cluster, state_machine);
+    harness.elect(&peer_ids);
+    assert!(handle.is_leader());
+    handle.shutdown();
+    settle();
+    assert!(harness.halted());
+    assert!(harness.join().unwrap().1.is_ok());
+}
+
+#[test]
+fn shutdown_before_start_returns_right_away() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.handle().shutdown();
+    let (_message_tx, message_rx) = unbounded();
+    let (_transition_tx, transition_rx) = unbounded();
+    assert_eq!(replica.start(message_rx, transition_rx), Ok(()));
+}
+
+#[test]
+fn shutdown_flushes_a_final_snapshot() {
+    let (mut replica, cluster, state_machine) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    replica.set_snapshot_on_shutdown(true);
+    let handle = replica.handle();
+    let harness = Harness::start(replica, cluster, state_machine);
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 0,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: (1..=3)
+            .map(|index| LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: 2,
+                },
+                index,
+                term: 1,
+                config: None,
+            })
+            .collect(),
+        commit_index: 3,
+        timestamp: None,
+    }]);
+
+    handle.shutdown();
+    settle();
+    assert!(harness.halted());
+    let replica = harness.stop();
+    let snapshot = replica.snapshot().unwrap();
+    assert_eq!(snapshot.last_included_index, 3);
+    assert_eq!(snapshot.data, 6i32.to_le_bytes().to_vec());
+}
@@ -0,0 +1,71 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    handle::ReplicaHandle,
+    message::{LogEntry, Message},
+};
+
+fn entry(index: usize) -> LogEntry<ArithmeticOperation> {
+    LogEntry {
+        transition: ArithmeticOperation {
+            id: index,
+            delta: index as i32,
+        },
+        index,
+        term: 1,
+        config: None,
+    }
+}
+
+// Start a follower that applies the first entry, then commits two more while
+// applying is paused.
+fn lagging_follower() -> (Harness, ReplicaHandle) {
+    let (replica, cluster, state_machine) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    let handle = replica.handle();
+    let harness = Harness::start(replica, cluster, state_machine);
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 0,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![entry(1)],
+        commit_index: 1,
+        timestamp: None,
+    }]);
+    handle.pause_apply();
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 0,
+        term: 1,
+        prev_log_index: 1,
+        prev_log_term: 1,
+        entries: vec![entry(2), entry(3)],
+        commit_index: 3,
+        timestamp: None,
+    }]);
+    assert_eq!(handle.commit_index(), 3);
+    assert_eq!(handle.last_applied(), 1);
+    (harness, handle)
+}
+
+#[test]
+fn committed_entries_are_applied_on_shutdown() {
+    let (harness, handle) = lagging_follower();
+    let state_machine = harness.state_machine.clone();
+
+    harness.stop();
+    assert_eq!(handle.last_applied(), 3);
+    assert_eq!(state_machine.lock().unwrap().applied_ids, vec![1, 2, 3]);
+}
+
+#[test]
+fn committed_entries_are_drained_on_shutdown() {
+    let (harness, _) = lagging_follower();
+    let state_machine = harness.state_machine.clone();
+    state_machine.lock().unwrap().drain_sink = Some(Vec::new());
+
+    harness.stop();
+    let state_machine = state_machine.lock().unwrap();
+    assert_eq!(state_machine.drain_sink, Some(vec![entry(2), entry(3)]));
+    assert_eq!(state_machine.applied_ids, vec![1]);
+}
@@ -0,0 +1,654 @@
+mod common;
+
+use common::{
+    new_replica, settle, ArithmeticOperation, Harness, ELECTION_TIMEOUT, NO_ELECTION_TIMEOUT,
+};
+use little_raft::{
+    clock::{Clock, ManualClock},
+    message::{LogEntry, Message},
+    replica::State,
+    state_machine::SnapshotMeta,
+};
+use std::{
+    collections::BTreeMap,
+    panic,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+#[test]
+fn leader_sends_snapshot_to_peer_behind_compacted_log() {
+    let peer_ids = vec![1, 2];
+    let (mut replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    replica.set_snapshot_delta(2);
+    let harness = Harness::start(replica, cluster, state_machine);
+    let term = harness.elect(&peer_ids);
+
+    // Only peer 1 keeps up, which is enough to commit and compact the no-op
+    // and both transitions.
+    harness.propose(vec![
+        ArithmeticOperation { id: 1, delta: 5 },
+        ArithmeticOperation { id: 2, delta: 7 },
+    ]);
+    harness.deliver(vec![Message::AppendEntryResponse {
+        from_id: 1,
+        term,
+        success: true,
+        last_index: 3,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }]);
+    harness.take_sent();
+    settle();
+
+    // Peer 2 still needs the no-op, which is gone from the log.
+    let snapshot_requests: Vec<_> = harness
+        .take_sent()
+        .into_iter()
+        .filter_map(|(to_id, message)| match message {
+            Message::InstallSnapshotRequest {
+                last_included_index,
+                last_included_term,
+                data,
+                done,
+                ..
+            } if to_id == 2 => Some((last_included_index, last_included_term, data, done)),
+            _ => None,
+        })
+        .collect();
+    assert!(!snapshot_requests.is_empty());
+    for request in snapshot_requests {
+        assert_eq!(request, (3, term, 12i32.to_le_bytes().to_vec(), true));
+    }
+
+    // Once peer 2 installed the snapshot, it is sent entries again.
+    harness.deliver(vec![Message::InstallSnapshotResponse {
+        from_id: 2,
+        term,
+        last_included_index: 3,
+        last_index: 3,
+    }]);
+    harness.take_sent();
+    settle();
+    let sent = harness.take_sent();
+    assert!(sent.iter().any(|(to_id, message)| matches!(
+        message,
+        Message::AppendEntryRequest {
+            prev_log_index: 3,
+            prev_log_term,
+            ..
+        } if *to_id == 2 && *prev_log_term == term
+    )));
+    assert!(!sent
+        .iter()
+        .any(|(_, message)| matches!(message, Message::InstallSnapshotRequest { .. })));
+
+    harness.stop();
+}
+
+#[test]
+fn leader_replicates_new_entries_after_compaction() {
+    let peer_ids = vec![1, 2];
+    let (mut replica, cluster, state_machine) = new_replica(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    replica.set_snapshot_delta(2);
+    let harness = Harness::start(replica, cluster, state_machine);
+    let term = harness.elect(&peer_ids);
+
+    // Both peers keep up while the Leader compacts its log up to index 3.
+    harness.propose(vec![
+        ArithmeticOperation { id: 1, delta: 5 },
+        ArithmeticOperation { id: 2, delta: 7 },
+    ]);
+    harness.deliver(
+        peer_ids
+            .iter()
+            .map(|&peer_id| Message::AppendEntryResponse {
+                from_id: peer_id,
+                term,
+                success: true,
+                last_index: 3,
+                mismatch_index: None,
+                content_hash: None,
+                request_timestamp: None,
+            })
+            .collect(),
+    );
+    settle();
+    harness.take_sent();
+
+    // The next entry follows the compacted ones, with the term of the last of
+    // them.
+    harness.propose(vec![ArithmeticOperation { id: 3, delta: 1 }]);
+    let mut requests: Vec<_> = harness
+        .take_sent()
+        .into_iter()
+        .filter_map(|(to_id, message)| match message {
+            Message::AppendEntryRequest {
+                prev_log_index,
+                prev_log_term,
+                entries,
+                ..
+            } if !entries.is_empty() => Some((
+                to_id,
+                prev_log_index,
+                prev_log_term,
+                entries.iter().map(|entry| entry.index).collect::<Vec<_>>(),
+            )),
+            _ => None,
+        })
+        .collect();
+    // Requests that were not acknowledged in time are sent again.
+    requests.sort();
+    requests.dedup();
+    assert_eq!(requests, vec![(1, 3, term, vec![4]), (2, 3, term, vec![4])]);
+
+    harness.stop();
+}
+
+#[test]
+fn follower_installs_snapshot_and_resumes_replication() {
+    let harness = Harness::new(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+
+    // The follower holds an entry that the Leader's snapshot overrides.
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 0,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![LogEntry {
+            transition: ArithmeticOperation { id: 1, delta: 1 },
+            index: 1,
+            term: 1,
+            config: None,
+        }],
+        commit_index: 0,
+        timestamp: None,
+    }]);
+    harness.take_sent();
+
+    harness.deliver(vec![Message::InstallSnapshotRequest {
+        from_id: 0,
+        term: 2,
+        last_included_index: 5,
+        last_included_term: 2,
+        data: 42i32.to_le_bytes().to_vec(),
+        offset: 0,
+        done: true,
+        content_hash: None,
+        applied_sequences: BTreeMap::new(),
+    }]);
+    assert!(harness.take_sent().iter().any(|(to_id, message)| matches!(
+        message,
+        Message::InstallSnapshotResponse {
+            from_id: 1,
+            last_included_index: 5,
+            ..
+        } if *to_id == 0
+    )));
+    {
+        let state_machine = harness.state_machine.lock().unwrap();
+        assert_eq!(state_machine.value, 42);
+        assert_eq!(state_machine.applied_index, 5);
+        assert!(state_machine.applied_ids.is_empty());
+    }
+
+    // Replication picks up right after the snapshot.
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 0,
+        term: 2,
+        prev_log_index: 5,
+        prev_log_term: 2,
+        entries: vec![LogEntry {
+            transition: ArithmeticOperation { id: 6, delta: 1 },
+            index: 6,
+            term: 2,
+            config: None,
+        }],
+        commit_index: 6,
+        timestamp: None,
+    }]);
+    assert!(harness.take_sent().iter().any(|(_, message)| matches!(
+        message,
+        Message::AppendEntryResponse {
+            success: true,
+            last_index: 6,
+            ..
+        }
+    )));
+    assert_eq!(harness.state_machine.lock().unwrap().value, 43);
+    assert_eq!(harness.state_machine.lock().unwrap().applied_ids, vec![6]);
+
+    harness.stop();
+}
+
+#[test]
+fn vote_requests_describe_the_last_entry_after_compaction() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_snapshot_delta(2);
+
+    // Applying the first three entries compacts them, leaving the fourth one,
+    // of a later term, as the last entry of the log.
+    replica.handle_message(Message::AppendEntryRequest {
+        from_id: 1,
+        term: 2,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: (1..=4)
+            .map(|index| LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: 1,
+                },
+                index,
+                term: if index < 4 { 1 } else { 2 },
+                config: None,
+            })
+            .collect(),
+        commit_index: 3,
+        timestamp: None,
+    });
+    assert_eq!(replica.snapshot().unwrap().last_included_index, 3);
+
+    let outbound = replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    assert_eq!(outbound.len(), 2);
+    for (_, message) in outbound {
+        assert!(matches!(
+            message,
+            Message::VoteRequest {
+                last_log_index: 4,
+                last_log_term: 2,
+                ..
+            }
+        ));
+    }
+}
+
+#[test]
+fn follower_skips_entries_covered_by_its_snapshot() {
+    let (mut replica, _, state_machine) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    replica.set_snapshot_delta(2);
+    let append = |prev_log_index, last_index, commit_index| Message::AppendEntryRequest {
+        from_id: 0,
+        term: 0,
+        prev_log_index,
+        prev_log_term: 0,
+        entries: (prev_log_index + 1..=last_index)
+            .map(|index| LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: 1,
+                },
+                index,
+                term: 0,
+                config: None,
+            })
+            .collect(),
+        commit_index,
+        timestamp: None,
+    };
+    replica.handle_message(append(0, 3, 3));
+    assert_eq!(replica.snapshot().unwrap().last_included_index, 3);
+
+    // A request that starts inside the snapshot only appends what the log is
+    // missing.
+    let outbound = replica.handle_message(append(1, 5, 5));
+    assert!(matches!(
+        outbound.as_slice(),
+        [(
+            0,
+            Message::AppendEntryResponse {
+                success: true,
+                last_index: 5,
+                ..
+            }
+        )]
+    ));
+    assert_eq!(replica.commit_index(), 5);
+    assert_eq!(
+        state_machine.lock().unwrap().applied_ids,
+        vec![1, 2, 3, 4, 5]
+    );
+}
+
+#[test]
+fn snapshot_is_sent_and_reassembled_in_chunks() {
+    let (mut leader, _, leader_state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    leader.set_snapshot_delta(2);
+    leader.set_max_snapshot_chunk_size(3);
+    leader.set_confirm_leadership_before_writes(true);
+    leader.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    leader.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+
+    // Only peer 1 keeps up, so the Leader compacts entries peer 2 needs.
+    leader_state_machine.lock().unwrap().pending_transitions = vec![
+        ArithmeticOperation { id: 1, delta: 5 },
+        ArithmeticOperation { id: 2, delta: 7 },
+    ];
+    for last_index in [1, 3].iter().copied() {
+        leader.handle_message(Message::AppendEntryResponse {
+            from_id: 1,
+            term: 1,
+            success: true,
+            last_index,
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        });
+    }
+    assert_eq!(leader.snapshot().unwrap().last_included_index, 3);
+
+    // The four bytes of the snapshot take two chunks.
+    let chunks: Vec<_> = leader
+        .handle_timeout(Instant::now() + Duration::from_secs(1))
+        .into_iter()
+        .filter(|(to_id, _)| *to_id == 2)
+        .map(|(_, message)| message)
+        .collect();
+    let layout: Vec<_> = chunks
+        .iter()
+        .map(|message| match message {
+            Message::InstallSnapshotRequest {
+                data, offset, done, ..
+            } => (data.len(), *offset, *done),
+            message => panic!("unexpected message {:?}", message),
+        })
+        .collect();
+    assert_eq!(layout, vec![(3, 0, false), (1, 3, true)]);
+
+    // The follower drops a chunk that does not follow the ones it has, and
+    // starts over when the transfer restarts.
+    let (mut follower, _, follower_state_machine) = new_replica(2, vec![0, 1], NO_ELECTION_TIMEOUT);
+    let mut outbound = Vec::new();
+    for &index in [1, 0, 0, 1].iter() {
+        assert!(follower_state_machine
+            .lock()
+            .unwrap()
+            .loaded_snapshots
+            .is_empty());
+        outbound = follower.handle_message(chunks[index].clone());
+    }
+    assert_eq!(follower_state_machine.lock().unwrap().value, 12);
+    assert_eq!(
+        outbound,
+        vec![(
+            0,
+            Message::InstallSnapshotResponse {
+                from_id: 2,
+                term: 1,
+                last_included_index: 3,
+                last_index: 3,
+            }
+        )]
+    );
+}
+
+#[test]
+fn chunks_of_another_snapshot_are_dropped() {
+    let (mut follower, _, state_machine) = new_replica(2, vec![0, 1], NO_ELECTION_TIMEOUT);
+    let data = 12i32.to_le_bytes();
+    let chunk = |last_included_index, offset: usize, len| Message::InstallSnapshotRequest {
+        from_id: 0,
+        term: 1,
+        last_included_index,
+        last_included_term: 1,
+        data: data[offset..offset + len].to_vec(),
+        offset,
+        done: offset + len == data.len(),
+        content_hash: None,
+        applied_sequences: BTreeMap::new(),
+    };
+
+    // The last chunk of a later snapshot does not complete this one.
+    follower.handle_message(chunk(3, 0, 3));
+    follower.handle_message(chunk(5, 3, 1));
+    assert!(state_machine.lock().unwrap().loaded_snapshots.is_empty());
+
+    follower.handle_message(chunk(3, 3, 1));
+    assert_eq!(state_machine.lock().unwrap().value, 12);
+    assert_eq!(follower.snapshot().unwrap().last_included_index, 3);
+}
+
+#[test]
+fn follower_catches_up_within_a_heartbeat_of_installing_a_snapshot() {
+    let (mut leader, _, leader_state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    leader.set_snapshot_delta(2);
+    leader.set_confirm_leadership_before_writes(true);
+    leader.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    leader.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+
+    // Peer 1 only acknowledges part of the log, so the Leader compacts entries
+    // peer 2 needs and keeps the ones past its snapshot.
+    leader_state_machine.lock().unwrap().pending_transitions = (1..=4)
+        .map(|id| ArithmeticOperation { id, delta: 1 })
+        .collect();
+    for last_index in [1, 3] {
+        leader.handle_message(Message::AppendEntryResponse {
+            from_id: 1,
+            term: 1,
+            success: true,
+            last_index,
+            mismatch_index: None,
+            content_hash: None,
+            request_timestamp: None,
+        });
+    }
+    assert_eq!(leader.snapshot().unwrap().last_included_index, 3);
+    let last_log_index = leader.metrics().index_offset + leader.metrics().log_len - 1;
+    assert_eq!(last_log_index, 5);
+
+    // A single heartbeat sends peer 2 the snapshot. Its acknowledgement gets
+    // the entries that follow without waiting for the next heartbeat.
+    let (mut follower, _, _) = new_replica(2, vec![0, 1], NO_ELECTION_TIMEOUT);
+    let mut outbound = leader.handle_timeout(Instant::now() + Duration::from_secs(1));
+    let mut round_trips = 0;
+    while !outbound.is_empty() {
+        let mut responses = Vec::new();
+        for (to_id, message) in outbound {
+            if to_id == 2 {
+                responses.extend(follower.handle_message(message));
+            }
+        }
+        outbound = Vec::new();
+        for (_, response) in responses {
+            outbound.extend(leader.handle_message(response));
+        }
+        round_trips += 1;
+    }
+    let metrics = follower.metrics();
+    assert_eq!(metrics.index_offset + metrics.log_len - 1, last_log_index);
+    assert_eq!(round_trips, 2);
+}
+
+#[test]
+fn state_machine_hears_of_each_installed_snapshot_once() {
+    let (mut follower, _, state_machine) = new_replica(2, vec![0, 1], NO_ELECTION_TIMEOUT);
+    let data = 12i32.to_le_bytes();
+    let chunk = |offset: usize, len| Message::InstallSnapshotRequest {
+        from_id: 0,
+        term: 2,
+        last_included_index: 3,
+        last_included_term: 1,
+        data: data[offset..offset + len].to_vec(),
+        offset,
+        done: offset + len == data.len(),
+        content_hash: None,
+        applied_sequences: BTreeMap::new(),
+    };
+
+    // Only the last chunk completes the install, and a snapshot that was
+    // already installed is not installed again.
+    follower.handle_message(chunk(0, 3));
+    assert!(state_machine.lock().unwrap().installed_snapshots.is_empty());
+    follower.handle_message(chunk(3, 1));
+    follower.handle_message(chunk(0, 4));
+    let state_machine = state_machine.lock().unwrap();
+    assert_eq!(
+        state_machine.installed_snapshots,
+        vec![SnapshotMeta {
+            last_included_index: 3,
+            last_included_term: 1,
+        }]
+    );
+    assert_eq!(state_machine.loaded_snapshots.len(), 1);
+}
+
+#[test]
+fn replica_installing_a_snapshot_neither_campaigns_nor_appends_overlapping_entries() {
+    let clock = ManualClock::new();
+    let (mut follower, _, state_machine) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    follower.set_clock(Arc::new(clock.clone()));
+    let append = |prev_log_index: usize, index| Message::AppendEntryRequest {
+        from_id: 0,
+        term: 1,
+        prev_log_index,
+        prev_log_term: if prev_log_index == 0 { 0 } else { 1 },
+        entries: vec![LogEntry {
+            transition: ArithmeticOperation {
+                id: index,
+                delta: 1,
+            },
+            index,
+            term: 1,
+            config: None,
+        }],
+        commit_index: 0,
+        timestamp: None,
+    };
+    let data = 12i32.to_le_bytes();
+    let chunk = |offset: usize, len| Message::InstallSnapshotRequest {
+        from_id: 0,
+        term: 1,
+        last_included_index: 5,
+        last_included_term: 1,
+        data: data[offset..offset + len].to_vec(),
+        offset,
+        done: offset + len == data.len(),
+        content_hash: None,
+        applied_sequences: BTreeMap::new(),
+    };
+    follower.handle_message(append(0, 1));
+    follower.handle_message(chunk(0, 3));
+
+    // Entries the snapshot replaces are neither appended nor acknowledged,
+    // and the Leader cannot hand leadership over.
+    let outbound = follower.handle_message(append(1, 2));
+    assert!(matches!(
+        outbound[..],
+        [(
+            0,
+            Message::AppendEntryResponse {
+                success: false,
+                mismatch_index: None,
+                ..
+            }
+        )]
+    ));
+    assert_eq!(follower.metrics().log_len, 2);
+    assert!(follower
+        .handle_message(Message::TimeoutNow {
+            from_id: 0,
+            term: 1,
+        })
+        .is_empty());
+    assert_eq!(follower.metrics().state, State::Follower);
+
+    // Once the snapshot is installed, replication resumes after it.
+    follower.handle_message(chunk(3, 1));
+    assert_eq!(state_machine.lock().unwrap().value, 12);
+    let outbound = follower.handle_message(append(5, 6));
+    assert!(matches!(
+        outbound[..],
+        [(
+            0,
+            Message::AppendEntryResponse {
+                success: true,
+                last_index: 6,
+                ..
+            }
+        )]
+    ));
+
+    // A transfer the Leader gave up on only delays the next election by an
+    // election timeout.
+    follower.handle_message(chunk(0, 3));
+    clock.advance(NO_ELECTION_TIMEOUT.1);
+    assert!(follower.handle_timeout(clock.now()).is_empty());
+    assert_eq!(follower.metrics().state, State::Follower);
+    clock.advance(NO_ELECTION_TIMEOUT.1);
+    follower.handle_timeout(clock.now());
+    assert_eq!(follower.metrics().state, State::Candidate);
+}
+
+#[test]
+fn snapshot_is_refreshed_once_it_is_too_old() {
+    let clock = ManualClock::new();
+    let (mut replica, _, state_machine) = new_replica(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    replica.set_clock(Arc::new(clock.clone()));
+    replica.set_snapshot_delta(100);
+    let max_age = Duration::from_secs(10);
+    replica.set_max_snapshot_age(max_age);
+    let append = |replica: &mut common::TestReplica, index| {
+        replica.handle_message(Message::AppendEntryRequest {
+            from_id: 0,
+            term: 1,
+            prev_log_index: index - 1,
+            prev_log_term: if index == 1 { 0 } else { 1 },
+            entries: vec![LogEntry {
+                transition: ArithmeticOperation {
+                    id: index,
+                    delta: 1,
+                },
+                index,
+                term: 1,
+                config: None,
+            }],
+            commit_index: index,
+            timestamp: None,
+        });
+    };
+
+    // Far fewer entries than the snapshot delta are applied, and nothing
+    // happens on the cluster afterwards.
+    append(&mut replica, 1);
+    append(&mut replica, 2);
+    clock.advance(max_age - Duration::from_secs(1));
+    replica.handle_timeout(clock.now());
+    assert!(replica.snapshot().is_none());
+    clock.advance(Duration::from_secs(1));
+    replica.handle_timeout(clock.now());
+    assert_eq!(replica.snapshot().unwrap().last_included_index, 2);
+
+    // The snapshot ages from when it was taken, and is only replaced once
+    // entries were applied past it.
+    clock.advance(max_age);
+    replica.handle_timeout(clock.now());
+    assert_eq!(replica.snapshot().unwrap().last_included_index, 2);
+    append(&mut replica, 3);
+    assert_eq!(replica.snapshot().unwrap().last_included_index, 3);
+    assert_eq!(state_machine.lock().unwrap().value, 3);
+}
+
+#[test]
+fn max_snapshot_age_of_zero_is_rejected() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
+        replica.set_max_snapshot_age(Duration::ZERO)
+    }));
+    assert!(result.is_err());
+}
+
+#[test]
+fn snapshot_delta_of_zero_is_rejected() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(|| replica.set_snapshot_delta(0)));
+    assert!(result.is_err());
+}
@@ -0,0 +1,47 @@
+mod common;
+
+use common::{Harness, ELECTION_TIMEOUT};
+use little_raft::message::Message;
+use std::{thread, time::Duration};
+
+// Wait for the Replica to ask for votes and return the term of the election.
+fn next_vote_request(harness: &Harness) -> usize {
+    loop {
+        for (_, message) in harness.take_sent() {
+            if let Message::VoteRequest { term, .. } = message {
+                return term;
+            }
+        }
+        thread::sleep(Duration::from_millis(5));
+    }
+}
+
+#[test]
+fn stale_vote_grants_do_not_count() {
+    let harness = Harness::new(0, vec![1, 2], ELECTION_TIMEOUT);
+    let first_term = next_vote_request(&harness);
+
+    // Another Candidate with a higher term makes us step down.
+    harness.deliver(vec![Message::VoteRequest {
+        from_id: 2,
+        term: first_term + 1,
+        last_log_index: 0,
+        last_log_term: 0,
+    }]);
+
+    // We campaign again once that Candidate fails to become the Leader, and
+    // receive a delayed vote from the first election.
+    assert_eq!(next_vote_request(&harness), first_term + 2);
+    harness.deliver(vec![Message::VoteResponse {
+        from_id: 1,
+        term: first_term,
+        vote_granted: true,
+    }]);
+    assert_eq!(harness.leader_id(), None);
+    assert!(!harness
+        .take_sent()
+        .iter()
+        .any(|(_, message)| matches!(message, Message::AppendEntryRequest { .. })));
+
+    harness.stop();
+}
@@ -0,0 +1,62 @@
+mod common;
+
+use common::{new_replica, Harness, NO_ELECTION_TIMEOUT};
+use crossbeam_channel::unbounded;
+use little_raft::{message::Message, replica::RaftError, state_machine::ApplyError};
+use std::{collections::BTreeMap, thread};
+
+#[test]
+fn start_returns_when_its_channel_disconnects() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    let (message_tx, message_rx) = unbounded();
+    let (_transition_tx, transition_rx) = unbounded();
+    drop(message_tx);
+
+    assert_eq!(
+        replica.start(message_rx, transition_rx),
+        Err(RaftError::ChannelDisconnected)
+    );
+}
+
+#[test]
+fn start_returns_when_the_state_machine_is_poisoned() {
+    let (mut replica, _, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    let poisoner = state_machine.clone();
+    assert!(thread::spawn(move || {
+        let _guard = poisoner.lock().unwrap();
+        panic!("poison the state machine");
+    })
+    .join()
+    .is_err());
+
+    let (_message_tx, message_rx) = unbounded();
+    let (_transition_tx, transition_rx) = unbounded();
+    assert_eq!(
+        replica.start(message_rx, transition_rx),
+        Err(RaftError::MutexPoisoned)
+    );
+}
+
+#[test]
+fn start_returns_when_a_snapshot_cannot_be_loaded() {
+    let harness = Harness::new(1, vec![0, 2], NO_ELECTION_TIMEOUT);
+    harness.deliver(vec![Message::InstallSnapshotRequest {
+        from_id: 0,
+        term: 1,
+        last_included_index: 5,
+        last_included_term: 1,
+        data: vec![1, 2, 3],
+        offset: 0,
+        done: true,
+        content_hash: None,
+        applied_sequences: BTreeMap::new(),
+    }]);
+    assert!(harness.halted());
+    assert!(harness.take_sent().is_empty());
+
+    let (replica, result) = harness.join().unwrap();
+    let error = ApplyError::Fatal("cannot load 3 bytes".to_string());
+    assert_eq!(result, Err(RaftError::SnapshotLoad(error.clone())));
+    assert_eq!(replica.fatal_error(), Some(&error));
+    assert_eq!(replica.snapshot(), None);
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use common::{
+    new_replica, settle, ArithmeticOperation, Harness, ELECTION_TIMEOUT, NO_ELECTION_TIMEOUT,
+};
+use little_raft::{message::Message, replica::ReplicaID};
+
+fn heartbeat(from_id: ReplicaID, term: usize) -> Message<ArithmeticOperation> {
+    Message::AppendEntryRequest {
+        from_id,
+        term,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![],
+        commit_index: 0,
+        timestamp: None,
+    }
+}
+
+#[test]
+fn leader_hands_leadership_to_caught_up_peer_on_request() {
+    let peer_ids = vec![1, 2];
+    let harness = Harness::new(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    let term = harness.elect(&peer_ids);
+
+    // Only peer 2 has the Leader's no-op.
+    harness.deliver(vec![Message::AppendEntryResponse {
+        from_id: 2,
+        term,
+        success: true,
+        last_index: 1,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }]);
+    harness.take_sent();
+
+    harness.state_machine.lock().unwrap().step_down = true;
+    settle();
+    let timeout_now = harness
+        .take_sent()
+        .into_iter()
+        .find(|(_, message)| matches!(message, Message::TimeoutNow { .. }))
+        .expect("no TimeoutNow sent");
+    assert_eq!(timeout_now, (2, Message::TimeoutNow { from_id: 0, term }));
+    assert_eq!(harness.leader_id(), None);
+    harness.stop();
+
+    // The successor starts an election as soon as it gets the message, and
+    // wins it with the former Leader's vote.
+    let (mut successor, cluster, _) = new_replica(2, vec![0, 1], NO_ELECTION_TIMEOUT);
+    successor.handle_message(heartbeat(0, term));
+    let outbound = successor.handle_message(timeout_now.1);
+    assert_eq!(outbound.len(), 2);
+    let election_term = match outbound[0].1 {
+        Message::VoteRequest { term, .. } => term,
+        ref message => panic!("unexpected message {:?}", message),
+    };
+    successor.handle_message(Message::VoteResponse {
+        from_id: 0,
+        term: election_term,
+        vote_granted: true,
+    });
+    assert_eq!(cluster.lock().unwrap().leader_id, Some(2));
+}
+
+#[test]
+fn timeout_now_is_only_taken_from_the_current_leader() {
+    let (mut replica, _, _) = new_replica(2, vec![0, 1], NO_ELECTION_TIMEOUT);
+    replica.handle_message(heartbeat(0, 2));
+
+    // Neither another peer nor the Leader of an earlier term can make the
+    // Replica start an election.
+    for (from_id, term) in [(1, 2), (0, 1)] {
+        let outbound = replica.handle_message(Message::TimeoutNow { from_id, term });
+        assert!(outbound.is_empty());
+        assert_eq!(replica.current_term(), 2);
+    }
+
+    let outbound = replica.handle_message(Message::TimeoutNow {
+        from_id: 0,
+        term: 2,
+    });
+    assert_eq!(outbound.len(), 2);
+    assert_eq!(replica.current_term(), 3);
+}
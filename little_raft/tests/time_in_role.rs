@@ -0,0 +1,22 @@
+mod common;
+
+use common::{new_replica, NO_ELECTION_TIMEOUT};
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+const PAUSE: Duration = Duration::from_millis(50);
+
+#[test]
+fn time_in_role_resets_on_role_change() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    thread::sleep(PAUSE);
+    assert!(replica.time_in_role() >= PAUSE);
+
+    // The Follower becomes a Candidate.
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    assert!(replica.time_in_role() < PAUSE);
+    thread::sleep(PAUSE);
+    assert!(replica.time_in_role() >= PAUSE);
+}
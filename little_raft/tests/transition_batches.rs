@@ -0,0 +1,65 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, NO_ELECTION_TIMEOUT};
+use little_raft::{message::Message, state_machine::TransitionState};
+use std::time::Instant;
+
+#[test]
+fn pending_transitions_are_appended_and_committed_as_a_block() {
+    let (mut replica, _, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_confirm_leadership_before_writes(true);
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    assert!(replica.is_leader());
+
+    // The Leader loads the burst once a majority confirmed it, and persists
+    // it in one go after its no-op.
+    state_machine.lock().unwrap().pending_transitions = (1..=5)
+        .map(|id| ArithmeticOperation { id, delta: 1 })
+        .collect();
+    let log_appends = state_machine.lock().unwrap().log_appends;
+    let acknowledge = |last_index| Message::AppendEntryResponse {
+        from_id: 1,
+        term: 1,
+        success: true,
+        last_index,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    };
+    replica.handle_message(acknowledge(1));
+    {
+        let state_machine = state_machine.lock().unwrap();
+        assert_eq!(state_machine.log_appends, log_appends + 1);
+        assert_eq!(
+            state_machine
+                .persisted_log
+                .iter()
+                .map(|entry| entry.index)
+                .collect::<Vec<_>>(),
+            (1..=6).collect::<Vec<_>>()
+        );
+        assert_eq!(
+            state_machine.transition_states,
+            (1..=5)
+                .map(|id| (id, TransitionState::Queued))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    // A single acknowledgement of the whole block commits all of it, and it
+    // is applied right after the no-op.
+    replica.handle_message(acknowledge(6));
+    assert_eq!(replica.handle().commit_index(), 6);
+    let state_machine = state_machine.lock().unwrap();
+    assert_eq!(state_machine.applied_ids, (0..=5).collect::<Vec<_>>());
+    for id in 1..=5 {
+        assert!(state_machine
+            .transition_states
+            .contains(&(id, TransitionState::Committed)));
+    }
+}
@@ -0,0 +1,74 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, Harness, ELECTION_TIMEOUT, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    clock::{Clock, ManualClock},
+    message::Message,
+};
+use std::sync::Arc;
+
+fn ack(from_id: usize, term: usize, last_index: usize) -> Message<ArithmeticOperation> {
+    Message::AppendEntryResponse {
+        from_id,
+        term,
+        success: true,
+        last_index,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    }
+}
+
+#[test]
+fn timeline_steps_are_recorded_in_order() {
+    // Two acknowledgements are needed to commit in a cluster of five.
+    let peer_ids = vec![1, 2, 3, 4];
+    let harness = Harness::new(0, peer_ids.clone(), ELECTION_TIMEOUT);
+    let term = harness.elect(&peer_ids);
+    harness.propose(vec![ArithmeticOperation { id: 1, delta: 1 }]);
+
+    // The log holds the initial entry, the no-op and the transition.
+    harness.deliver(vec![ack(1, term, 2)]);
+    harness.deliver(vec![ack(2, term, 2)]);
+    let state_machine = harness.state_machine.clone();
+    harness.stop();
+
+    let state_machine = state_machine.lock().unwrap();
+    assert_eq!(state_machine.timelines.len(), 1);
+    let (id, timeline) = &state_machine.timelines[0];
+    assert_eq!(*id, 1);
+    let replicated = timeline.replicated.unwrap();
+    let committed = timeline.committed.unwrap();
+    let applied = timeline.applied.unwrap();
+    assert!(timeline.appended <= replicated);
+    assert!(replicated < committed);
+    assert!(committed <= applied);
+}
+
+#[test]
+fn timeline_steps_are_read_from_the_replica_clock() {
+    let clock = ManualClock::new();
+    let (mut replica, _, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_clock(Arc::new(clock.clone()));
+    replica.set_confirm_leadership_before_writes(true);
+    replica.handle_timeout(clock.now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    state_machine.lock().unwrap().pending_transitions =
+        vec![ArithmeticOperation { id: 1, delta: 1 }];
+    replica.handle_message(ack(1, 1, 1));
+    replica.handle_message(ack(1, 1, 2));
+
+    // The clock never moved, so every step happened at the same time.
+    let state_machine = state_machine.lock().unwrap();
+    assert_eq!(state_machine.timelines.len(), 1);
+    let timeline = &state_machine.timelines[0].1;
+    let now = clock.now();
+    assert_eq!(timeline.appended, now);
+    assert_eq!(timeline.replicated, Some(now));
+    assert_eq!(timeline.committed, Some(now));
+    assert_eq!(timeline.applied, Some(now));
+}
@@ -0,0 +1,64 @@
+mod common;
+
+use common::{new_replica, ArithmeticOperation, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    message::Message,
+    state_machine::{TransitionRejectedReason, TransitionState},
+};
+use std::time::Instant;
+
+#[test]
+fn invalid_transitions_are_rejected_before_entering_the_log() {
+    let (mut replica, _, state_machine) = new_replica(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    replica.set_confirm_leadership_before_writes(true);
+    replica.handle_timeout(Instant::now() + NO_ELECTION_TIMEOUT.1);
+    replica.handle_message(Message::VoteResponse {
+        from_id: 1,
+        term: 1,
+        vote_granted: true,
+    });
+    {
+        let mut state_machine = state_machine.lock().unwrap();
+        state_machine.invalid_ids = vec![2];
+        state_machine.pending_transitions = (1..=3)
+            .map(|id| ArithmeticOperation { id, delta: 1 })
+            .collect();
+    }
+    let acknowledge = |last_index| Message::AppendEntryResponse {
+        from_id: 1,
+        term: 1,
+        success: true,
+        last_index,
+        mismatch_index: None,
+        content_hash: None,
+        request_timestamp: None,
+    };
+    replica.handle_message(acknowledge(1));
+    {
+        let state_machine = state_machine.lock().unwrap();
+        assert_eq!(
+            state_machine.transition_states,
+            vec![
+                (1, TransitionState::Queued),
+                (
+                    2,
+                    TransitionState::Rejected(TransitionRejectedReason::Invalid(
+                        "2 is invalid".to_string()
+                    ))
+                ),
+                (3, TransitionState::Queued),
+            ]
+        );
+        assert_eq!(
+            state_machine
+                .persisted_log
+                .iter()
+                .map(|entry| (entry.index, entry.transition.id))
+                .collect::<Vec<_>>(),
+            vec![(1, 0), (2, 1), (3, 3)]
+        );
+    }
+
+    replica.handle_message(acknowledge(3));
+    assert_eq!(state_machine.lock().unwrap().applied_ids, vec![0, 1, 3]);
+}
@@ -0,0 +1,54 @@
+mod common;
+
+use common::{Harness, NO_ELECTION_TIMEOUT};
+use little_raft::message::Message;
+
+#[test]
+fn messages_from_unverified_peers_are_dropped() {
+    let harness = Harness::new(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+    harness.cluster.lock().unwrap().untrusted_ids.push(2);
+
+    // Someone spoofing Replica 2 asks for a vote and pretends to be the
+    // Leader. Neither message is processed.
+    harness.deliver(vec![
+        Message::VoteRequest {
+            from_id: 2,
+            term: 1,
+            last_log_index: 0,
+            last_log_term: 0,
+        },
+        Message::AppendEntryRequest {
+            from_id: 2,
+            term: 1,
+            prev_log_index: 0,
+            prev_log_term: 0,
+            entries: vec![],
+            commit_index: 0,
+            timestamp: None,
+        },
+    ]);
+    assert!(harness.take_sent().is_empty());
+    assert_eq!(harness.leader_id(), None);
+
+    // The same request from a verified peer is answered.
+    harness.deliver(vec![Message::VoteRequest {
+        from_id: 1,
+        term: 1,
+        last_log_index: 0,
+        last_log_term: 0,
+    }]);
+    let sent = harness.take_sent();
+    assert_eq!(sent.len(), 1);
+    assert!(matches!(
+        sent[0],
+        (
+            1,
+            Message::VoteResponse {
+                vote_granted: true,
+                ..
+            }
+        )
+    ));
+
+    harness.stop();
+}
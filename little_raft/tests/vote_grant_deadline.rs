@@ -0,0 +1,43 @@
+mod common;
+
+use common::{new_replica, ELECTION_TIMEOUT};
+use little_raft::message::Message;
+use std::{thread, time::Instant};
+
+#[test]
+fn granting_a_vote_resets_the_election_deadline() {
+    let (mut replica, _, _) = new_replica(0, vec![1, 2], ELECTION_TIMEOUT);
+    replica.set_deterministic(true);
+    replica.handle_timeout(Instant::now() + ELECTION_TIMEOUT.1);
+
+    // Most of the Replica's own election timeout elapses before it hears of a
+    // Candidate of a newer term, steps down and votes for it.
+    let timeout = replica.last_election_timeout();
+    thread::sleep(timeout * 2 / 3);
+    let outbound = replica.handle_message(Message::VoteRequest {
+        from_id: 2,
+        term: 2,
+        last_log_index: 0,
+        last_log_term: 0,
+    });
+    assert!(matches!(
+        outbound[..],
+        [(
+            2,
+            Message::VoteResponse {
+                vote_granted: true,
+                ..
+            }
+        )]
+    ));
+    let voted_at = Instant::now();
+
+    // The Candidate gets a full election timeout to win.
+    assert!(replica
+        .handle_timeout(voted_at + timeout * 2 / 3)
+        .is_empty());
+    assert!(replica
+        .handle_timeout(voted_at + timeout)
+        .iter()
+        .any(|(_, message)| matches!(message, Message::VoteRequest { term: 3, .. })));
+}
@@ -0,0 +1,60 @@
+mod common;
+
+use common::{Harness, NOOP, NO_ELECTION_TIMEOUT};
+use little_raft::{
+    message::{LogEntry, Message},
+    replica::RejectReason,
+};
+
+fn vote_request(from_id: usize, term: usize) -> Message<common::ArithmeticOperation> {
+    Message::VoteRequest {
+        from_id,
+        term,
+        last_log_index: 0,
+        last_log_term: 0,
+    }
+}
+
+#[test]
+fn vote_rejections_are_counted_by_reason() {
+    let harness = Harness::new(0, vec![1, 2], NO_ELECTION_TIMEOUT);
+
+    // Grant the vote to 1, then refuse 2 in the same term.
+    harness.deliver(vec![vote_request(1, 1)]);
+    harness.deliver(vec![vote_request(2, 1)]);
+
+    // Refuse a Candidate from an older term.
+    harness.deliver(vec![vote_request(2, 0)]);
+
+    // Refuse a Candidate in a newer term whose log is behind ours.
+    harness.deliver(vec![Message::AppendEntryRequest {
+        from_id: 1,
+        term: 1,
+        prev_log_index: 0,
+        prev_log_term: 0,
+        entries: vec![LogEntry {
+            transition: NOOP,
+            index: 1,
+            term: 1,
+            config: None,
+        }],
+        commit_index: 0,
+        timestamp: None,
+    }]);
+    harness.deliver(vec![vote_request(2, 2)]);
+
+    let votes: Vec<bool> = harness
+        .take_sent()
+        .into_iter()
+        .filter_map(|(_, message)| match message {
+            Message::VoteResponse { vote_granted, .. } => Some(vote_granted),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(votes, vec![true, false, false, false]);
+
+    let replica = harness.stop();
+    assert_eq!(replica.vote_rejections(RejectReason::AlreadyVoted), 1);
+    assert_eq!(replica.vote_rejections(RejectReason::StaleTerm), 1);
+    assert_eq!(replica.vote_rejections(RejectReason::LogBehind), 1);
+}
@@ -0,0 +1,35 @@
+mod common;
+
+use common::{new_replica, Harness};
+use little_raft::message::Message;
+use std::{thread, time::Duration};
+
+const FAST_ELECTION_TIMEOUT: (Duration, Duration) =
+    (Duration::from_millis(10), Duration::from_millis(20));
+
+// Let elections time out over and over for a second and count the
+// VoteRequests a peer receives.
+fn vote_requests_per_second(min_interval: Duration) -> usize {
+    let (mut replica, cluster, state_machine) = new_replica(0, vec![1, 2], FAST_ELECTION_TIMEOUT);
+    replica.set_min_vote_request_interval(min_interval);
+    let harness = Harness::start(replica, cluster, state_machine);
+    thread::sleep(Duration::from_secs(1));
+
+    let vote_requests = harness
+        .take_sent()
+        .into_iter()
+        .filter(|(to_id, message)| *to_id == 1 && matches!(message, Message::VoteRequest { .. }))
+        .count();
+    harness.stop();
+    vote_requests
+}
+
+#[test]
+fn vote_request_rate_is_bounded() {
+    assert!(vote_requests_per_second(Duration::from_millis(100)) <= 11);
+}
+
+#[test]
+fn vote_request_rate_is_unbounded_by_default() {
+    assert!(vote_requests_per_second(Duration::from_secs(0)) > 20);
+}